@@ -0,0 +1,848 @@
+// Licensed under the Apache License, Version 2.0 or the MIT License.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+// Copyright OXIDOS AUTOMOTIVE 2024.
+
+//! Creation of TBF objects.
+//!
+//! A TBF object is laid out as the TBF header, the protected trailer, the
+//! application binary and, for apps with a Program header, the credentials
+//! footers. [`TbfHeaderBuilder`] encodes the header and lays out the rest of
+//! the object around an application binary. Credentials are added to an
+//! existing object with `reserve_footer_space()` and `write_footer()`, and
+//! the header of an existing object is changed with [`TbfHeaderEditor`].
+
+use core::fmt;
+use std::string::String;
+use std::vec::Vec;
+
+use crate::parse::{
+    parse_tbf, parse_tbf_footer, parse_tbf_header, parse_tbf_header_lengths, parse_tbf_header_tlvs,
+};
+use crate::types::{
+    TbfFooterV2Credentials, TbfFooterV2CredentialsType, TbfHeader, TbfHeaderDriverPermission,
+    TbfHeaderTypes, TbfHeaderV2FixedAddresses, TbfHeaderV2KernelVersion, TbfHeaderV2Main,
+    TbfHeaderV2Program, TbfHeaderV2WriteableFlashRegion, TbfParseError,
+};
+
+/// Takes a value and rounds it up to be aligned % 4
+macro_rules! align4 {
+    ($e:expr $(,)?) => {
+        ($e) + ((4 - (($e) % 4)) % 4)
+    };
+}
+
+/// The longest package name `parse_tbf_header()` accepts.
+const MAX_PACKAGE_NAME_LENGTH: usize = 64;
+/// The most driver permissions `parse_tbf_header()` accepts.
+const MAX_PERMISSIONS: usize = 8;
+/// The most writeable flash regions `parse_tbf_header()` accepts.
+const MAX_WRITEABLE_FLASH_REGIONS: usize = 4;
+
+/// Size of a credentials footer with no data: the TLV header and the format.
+const MIN_FOOTER_SIZE: u32 = 8;
+/// Size of the smallest padding object: a header without TLV entries.
+pub const MIN_PADDING_SIZE: u32 = 16;
+/// Largest reserved footer written at once, so that its length fits the TLV.
+const MAX_FOOTER_SIZE: u32 = 0x8000;
+
+/// Errors when building a TBF object.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TbfBuildError {
+    /// The package name is longer than the 64 bytes the parser supports.
+    PackageNameTooLong,
+    /// More than 8 driver permissions were given.
+    TooManyPermissions,
+    /// More than 4 writeable flash regions were given.
+    TooManyWriteableFlashRegions,
+    /// The header does not fit in the 16 bit header size field.
+    HeaderTooLarge,
+    /// The TBF object would not fit in the 32 bit total size field.
+    ObjectTooLarge,
+    /// The header, protected trailer and binary need this many bytes, which
+    /// is more than the requested total size.
+    TotalSizeTooSmall(u32),
+    /// The data does not hold a complete, valid TBF object.
+    InvalidTbf,
+    /// The TBF object is padding, not an app.
+    NotAnApp,
+    /// Converting to a Program header requires moving the binary, but the app
+    /// was built for a fixed flash address.
+    CannotMoveBinary,
+    /// There is not enough reserved space left for the footer.
+    NoFooterSpace,
+    /// The edited header is this many bytes long, more than the header and
+    /// protected trailer, so it would overlap the binary.
+    HeaderOverlapsBinary(u32),
+    /// The Main and Program entries of this type can not be set or removed
+    /// directly, as they describe the layout of the object.
+    LayoutTlv(u16),
+    /// The edited header would not be accepted by the parser.
+    InvalidHeader(TbfParseError),
+}
+
+impl fmt::Display for TbfBuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TbfBuildError::PackageNameTooLong => {
+                write!(
+                    f,
+                    "The package name is longer than {MAX_PACKAGE_NAME_LENGTH} bytes"
+                )
+            }
+            TbfBuildError::TooManyPermissions => {
+                write!(f, "There are more than {MAX_PERMISSIONS} permissions")
+            }
+            TbfBuildError::TooManyWriteableFlashRegions => write!(
+                f,
+                "There are more than {MAX_WRITEABLE_FLASH_REGIONS} writeable flash regions"
+            ),
+            TbfBuildError::HeaderTooLarge => write!(f, "The header is too large"),
+            TbfBuildError::ObjectTooLarge => write!(f, "The TBF object is too large"),
+            TbfBuildError::TotalSizeTooSmall(size) => {
+                write!(f, "The TBF object needs at least {size} bytes")
+            }
+            TbfBuildError::InvalidTbf => write!(f, "Not a valid TBF object"),
+            TbfBuildError::NotAnApp => write!(f, "The TBF object is padding, not an app"),
+            TbfBuildError::CannotMoveBinary => {
+                write!(
+                    f,
+                    "The binary is built for a fixed address and can not move"
+                )
+            }
+            TbfBuildError::NoFooterSpace => write!(f, "There is no space left for the footer"),
+            TbfBuildError::HeaderOverlapsBinary(size) => {
+                write!(f, "A {size} byte header does not fit before the binary")
+            }
+            TbfBuildError::LayoutTlv(tipe) => {
+                write!(f, "TLV entries of type {tipe} can not be edited")
+            }
+            TbfBuildError::InvalidHeader(error) => write!(f, "Invalid header: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for TbfBuildError {}
+
+/// Builder for the v2 TBF header of an app.
+///
+/// By default the app is enabled, not sticky, and uses a Program header with
+/// version 0.
+#[derive(Clone, Debug)]
+pub struct TbfHeaderBuilder {
+    enabled: bool,
+    sticky: bool,
+    /// `Some` for a Program header with that binary version, `None` for a
+    /// Main header.
+    program_version: Option<u32>,
+    init_fn_offset: u32,
+    minimum_ram_size: u32,
+    package_name: Option<String>,
+    writeable_flash_regions: Vec<TbfHeaderV2WriteableFlashRegion>,
+    fixed_addresses: Option<TbfHeaderV2FixedAddresses>,
+    permissions: Vec<TbfHeaderDriverPermission>,
+    kernel_version: Option<TbfHeaderV2KernelVersion>,
+}
+
+impl Default for TbfHeaderBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TbfHeaderBuilder {
+    pub fn new() -> Self {
+        TbfHeaderBuilder {
+            enabled: true,
+            sticky: false,
+            program_version: Some(0),
+            init_fn_offset: 0,
+            minimum_ram_size: 0,
+            package_name: None,
+            writeable_flash_regions: Vec::new(),
+            fixed_addresses: None,
+            permissions: Vec::new(),
+            kernel_version: None,
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn set_sticky(&mut self, sticky: bool) {
+        self.sticky = sticky;
+    }
+
+    /// Use a Program header with this binary version, or a Main header if
+    /// `None`. Only apps with a Program header can have credentials footers.
+    pub fn set_program_version(&mut self, version: Option<u32>) {
+        self.program_version = version;
+    }
+
+    /// Set the entry point, as an offset from the start of the binary.
+    pub fn set_init_fn_offset(&mut self, offset: u32) {
+        self.init_fn_offset = offset;
+    }
+
+    pub fn set_minimum_ram_size(&mut self, size: u32) {
+        self.minimum_ram_size = size;
+    }
+
+    pub fn set_package_name(&mut self, name: &str) -> Result<(), TbfBuildError> {
+        if name.len() > MAX_PACKAGE_NAME_LENGTH {
+            return Err(TbfBuildError::PackageNameTooLong);
+        }
+        self.package_name = Some(name.into());
+        Ok(())
+    }
+
+    /// Add a writeable flash region. Its offset is relative to the start of
+    /// the TBF object.
+    pub fn add_writeable_flash_region(
+        &mut self,
+        region: TbfHeaderV2WriteableFlashRegion,
+    ) -> Result<(), TbfBuildError> {
+        if self.writeable_flash_regions.len() == MAX_WRITEABLE_FLASH_REGIONS {
+            return Err(TbfBuildError::TooManyWriteableFlashRegions);
+        }
+        self.writeable_flash_regions.push(region);
+        Ok(())
+    }
+
+    pub fn set_fixed_addresses(&mut self, addresses: Option<TbfHeaderV2FixedAddresses>) {
+        self.fixed_addresses = addresses;
+    }
+
+    pub fn add_permission(
+        &mut self,
+        permission: TbfHeaderDriverPermission,
+    ) -> Result<(), TbfBuildError> {
+        if self.permissions.len() == MAX_PERMISSIONS {
+            return Err(TbfBuildError::TooManyPermissions);
+        }
+        self.permissions.push(permission);
+        Ok(())
+    }
+
+    /// Set the kernel version the app was compiled for.
+    pub fn set_kernel_version(&mut self, version: Option<TbfHeaderV2KernelVersion>) {
+        self.kernel_version = version;
+    }
+
+    /// Size of the encoded header, which does not depend on the binary.
+    pub fn header_size(&self) -> u32 {
+        self.tlvs(0, 0, 0).len() as u32 + 16
+    }
+
+    /// Build a TBF object of `total_size` bytes around `binary`.
+    ///
+    /// `protected_trailer_size` bytes of zeroes are placed between the header
+    /// and the binary. The space left after the binary is filled with
+    /// reserved credentials footers if the app has a Program header, or with
+    /// zeroes otherwise.
+    pub fn build(
+        &self,
+        binary: &[u8],
+        protected_trailer_size: u32,
+        total_size: u32,
+    ) -> Result<Vec<u8>, TbfBuildError> {
+        let header_size = self.header_size();
+        if header_size > u16::MAX as u32 {
+            return Err(TbfBuildError::HeaderTooLarge);
+        }
+
+        let mut binary_end = u32::try_from(binary.len())
+            .ok()
+            .and_then(|length| {
+                header_size
+                    .checked_add(protected_trailer_size)?
+                    .checked_add(length)
+            })
+            .ok_or(TbfBuildError::ObjectTooLarge)?;
+        // The entry point is stored relative to the end of the header.
+        let init_fn_offset = protected_trailer_size
+            .checked_add(self.init_fn_offset)
+            .ok_or(TbfBuildError::ObjectTooLarge)?;
+        if binary_end > total_size {
+            return Err(TbfBuildError::TotalSizeTooSmall(binary_end));
+        }
+        // Footers need some room, anything smaller is left as padding at the
+        // end of the binary.
+        if self.program_version.is_some() && total_size - binary_end < MIN_FOOTER_SIZE {
+            binary_end = total_size;
+        }
+
+        let mut tbf = Vec::with_capacity(total_size as usize);
+        tbf.extend_from_slice(&2u16.to_le_bytes());
+        tbf.extend_from_slice(&(header_size as u16).to_le_bytes());
+        tbf.extend_from_slice(&total_size.to_le_bytes());
+        tbf.extend_from_slice(&self.flags().to_le_bytes());
+        tbf.extend_from_slice(&0u32.to_le_bytes());
+        tbf.extend(self.tlvs(protected_trailer_size, init_fn_offset, binary_end));
+
+        update_checksum(&mut tbf);
+
+        tbf.resize((header_size + protected_trailer_size) as usize, 0);
+        tbf.extend_from_slice(binary);
+        tbf.resize(binary_end as usize, 0);
+
+        if self.program_version.is_some() {
+            push_reserved_footers(&mut tbf, total_size - binary_end);
+        }
+        tbf.resize(total_size as usize, 0);
+
+        Ok(tbf)
+    }
+
+    fn flags(&self) -> u32 {
+        (self.enabled as u32) | ((self.sticky as u32) << 1)
+    }
+
+    /// Encode the TLV entries of the header, each padded to 4 bytes.
+    fn tlvs(&self, protected_trailer_size: u32, init_fn_offset: u32, binary_end: u32) -> Vec<u8> {
+        let mut tlvs = Vec::new();
+
+        match self.program_version {
+            Some(version) => push_tlv(
+                &mut tlvs,
+                TbfHeaderTypes::TbfHeaderProgram,
+                &TbfHeaderV2Program::new(
+                    init_fn_offset,
+                    protected_trailer_size,
+                    self.minimum_ram_size,
+                    binary_end,
+                    version,
+                )
+                .to_bytes(),
+            ),
+            None => push_tlv(
+                &mut tlvs,
+                TbfHeaderTypes::TbfHeaderMain,
+                &TbfHeaderV2Main::new(
+                    init_fn_offset,
+                    protected_trailer_size,
+                    self.minimum_ram_size,
+                )
+                .to_bytes(),
+            ),
+        }
+
+        if let Some(name) = &self.package_name {
+            push_tlv(
+                &mut tlvs,
+                TbfHeaderTypes::TbfHeaderPackageName,
+                name.as_bytes(),
+            );
+        }
+
+        if !self.writeable_flash_regions.is_empty() {
+            let regions: Vec<u8> = self
+                .writeable_flash_regions
+                .iter()
+                .flat_map(|region| region.to_bytes())
+                .collect();
+            push_tlv(
+                &mut tlvs,
+                TbfHeaderTypes::TbfHeaderWriteableFlashRegions,
+                &regions,
+            );
+        }
+
+        if let Some(addresses) = &self.fixed_addresses {
+            push_tlv(
+                &mut tlvs,
+                TbfHeaderTypes::TbfHeaderFixedAddresses,
+                &addresses.to_bytes(),
+            );
+        }
+
+        if !self.permissions.is_empty() {
+            let mut permissions = Vec::new();
+            permissions.extend_from_slice(&(self.permissions.len() as u16).to_le_bytes());
+            for permission in &self.permissions {
+                permissions.extend_from_slice(&permission.to_bytes());
+            }
+            push_tlv(
+                &mut tlvs,
+                TbfHeaderTypes::TbfHeaderPermissions,
+                &permissions,
+            );
+        }
+
+        if let Some(version) = &self.kernel_version {
+            push_tlv(
+                &mut tlvs,
+                TbfHeaderTypes::TbfHeaderKernelVersion,
+                &version.to_bytes(),
+            );
+        }
+
+        tlvs
+    }
+}
+
+/// Build a padding TBF object of `total_size` bytes: a header without any
+/// TLV entries, which the kernel skips over. Padding keeps an app at its
+/// address when the space before it is freed.
+pub fn build_padding(total_size: u32) -> Result<Vec<u8>, TbfBuildError> {
+    if total_size < MIN_PADDING_SIZE {
+        return Err(TbfBuildError::TotalSizeTooSmall(MIN_PADDING_SIZE));
+    }
+
+    let mut tbf = Vec::with_capacity(total_size as usize);
+    tbf.extend_from_slice(&2u16.to_le_bytes());
+    tbf.extend_from_slice(&16u16.to_le_bytes());
+    tbf.extend_from_slice(&total_size.to_le_bytes());
+    // No flags, the padding is neither enabled nor sticky.
+    tbf.extend_from_slice(&0u32.to_le_bytes());
+    tbf.extend_from_slice(&0u32.to_le_bytes());
+    update_checksum(&mut tbf);
+    tbf.resize(total_size as usize, 0);
+    Ok(tbf)
+}
+
+/// Encode a credentials footer, including its TLV header.
+pub fn encode_footer(credentials: &TbfFooterV2Credentials) -> Vec<u8> {
+    let (format, data): (_, &[&[u8]]) = match credentials {
+        TbfFooterV2Credentials::Reserved(length) => {
+            let mut footer = Vec::new();
+            push_reserved_footers(&mut footer, length + 4);
+            return footer;
+        }
+        TbfFooterV2Credentials::Rsa3072Key(rsa) => (
+            TbfFooterV2CredentialsType::Rsa3072Key,
+            &[rsa.get_public_key(), rsa.get_signature()],
+        ),
+        TbfFooterV2Credentials::Rsa4096Key(rsa) => (
+            TbfFooterV2CredentialsType::Rsa4096Key,
+            &[rsa.get_public_key(), rsa.get_signature()],
+        ),
+        TbfFooterV2Credentials::SHA256(sha) => {
+            (TbfFooterV2CredentialsType::SHA256, &[sha.get_hash()])
+        }
+        TbfFooterV2Credentials::SHA384(sha) => {
+            (TbfFooterV2CredentialsType::SHA384, &[sha.get_hash()])
+        }
+        TbfFooterV2Credentials::SHA512(sha) => {
+            (TbfFooterV2CredentialsType::SHA512, &[sha.get_hash()])
+        }
+    };
+
+    let length = 4 + data.iter().map(|part| part.len()).sum::<usize>();
+    let mut footer = Vec::with_capacity(4 + length);
+    push_tlv_header(&mut footer, TbfHeaderTypes::TbfFooterCredentials, length);
+    footer.extend_from_slice(&(format as u32).to_le_bytes());
+    for part in data {
+        footer.extend_from_slice(part);
+    }
+    footer
+}
+
+/// Convert a TBF object with a Main header to one with a Program header, so
+/// that it can have credentials footers. Objects that already have a Program
+/// header are returned unchanged.
+///
+/// The Program header entry is 8 bytes longer. These bytes are taken from
+/// the protected trailer if it is large enough. Otherwise the binary is moved
+/// by 8 bytes, which is refused for apps built for a fixed flash address.
+pub fn to_program_header(tbf: &[u8]) -> Result<Vec<u8>, TbfBuildError> {
+    let (header, header_size, total_size) = parse_header(tbf)?;
+    let header_data = &tbf[0..header_size as usize];
+
+    let tlvs = parse_tbf_header_tlvs(header_data)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| TbfBuildError::InvalidTbf)?;
+    if tlvs
+        .iter()
+        .any(|tlv| tlv.tipe == TbfHeaderTypes::TbfHeaderProgram as u16)
+    {
+        return Ok(tbf.to_vec());
+    }
+
+    let protected_trailer_size = header.get_protected_size() - header_size;
+    let shift = if protected_trailer_size >= 8 {
+        0
+    } else if header.get_fixed_address_flash().is_some() {
+        return Err(TbfBuildError::CannotMoveBinary);
+    } else {
+        8
+    };
+    let new_protected_trailer_size = protected_trailer_size + shift - 8;
+    let new_total_size = total_size
+        .checked_add(shift)
+        .ok_or(TbfBuildError::ObjectTooLarge)?;
+
+    let mut new_tlvs = Vec::new();
+    for tlv in tlvs {
+        if tlv.tipe == TbfHeaderTypes::TbfHeaderMain as u16 {
+            // The entry point keeps its offset from the start of the binary.
+            let init_fn_offset = header
+                .get_init_function_offset()
+                .checked_sub(header.get_protected_size())
+                .and_then(|offset| offset.checked_add(new_protected_trailer_size))
+                .ok_or(TbfBuildError::InvalidTbf)?;
+            let program = TbfHeaderV2Program::new(
+                init_fn_offset,
+                new_protected_trailer_size,
+                header.get_minimum_app_ram_size(),
+                new_total_size,
+                0,
+            );
+            push_tlv(
+                &mut new_tlvs,
+                TbfHeaderTypes::TbfHeaderProgram,
+                &program.to_bytes(),
+            );
+        } else {
+            // Keep the other entries as they are, even unknown ones.
+            push_raw_tlv(&mut new_tlvs, tlv.tipe, tlv.value);
+        }
+    }
+
+    let new_header_size = 16 + new_tlvs.len() as u32;
+    if new_header_size > u16::MAX as u32 {
+        return Err(TbfBuildError::HeaderTooLarge);
+    }
+
+    let mut new_tbf = Vec::with_capacity(new_total_size as usize);
+    new_tbf.extend_from_slice(&header_data[0..2]);
+    new_tbf.extend_from_slice(&(new_header_size as u16).to_le_bytes());
+    new_tbf.extend_from_slice(&new_total_size.to_le_bytes());
+    new_tbf.extend_from_slice(&header_data[8..12]);
+    new_tbf.extend_from_slice(&0u32.to_le_bytes());
+    new_tbf.extend(new_tlvs);
+    update_checksum(&mut new_tbf);
+
+    new_tbf.resize(new_tbf.len() + new_protected_trailer_size as usize, 0);
+    new_tbf.extend_from_slice(&tbf[header.get_protected_size() as usize..total_size as usize]);
+
+    Ok(new_tbf)
+}
+
+/// Make room for `size` bytes of credentials footers after the existing
+/// footers of a TBF object, filled with reserved footers. The object is
+/// converted to a Program header if needed, and grown to the next power of
+/// two if it is too small.
+///
+/// Reserving space changes the header, so it must be done before computing
+/// credentials with `write_footer()`.
+pub fn reserve_footer_space(tbf: &[u8], size: u32) -> Result<Vec<u8>, TbfBuildError> {
+    let mut tbf = to_program_header(tbf)?;
+    let (_, header_size, total_size) = parse_header(&tbf)?;
+    let (content_end, _) = find_reserved_space(&tbf)?;
+
+    let needed = content_end
+        .checked_add(size)
+        .ok_or(TbfBuildError::ObjectTooLarge)?;
+    let mut new_total_size = total_size;
+    if needed > total_size || (1..MIN_FOOTER_SIZE).contains(&(total_size - needed)) {
+        new_total_size = needed
+            .checked_next_power_of_two()
+            .ok_or(TbfBuildError::ObjectTooLarge)?;
+        if (1..MIN_FOOTER_SIZE).contains(&(new_total_size - needed)) {
+            new_total_size = needed
+                .checked_add(MIN_FOOTER_SIZE)
+                .and_then(u32::checked_next_power_of_two)
+                .ok_or(TbfBuildError::ObjectTooLarge)?;
+        }
+        tbf[4..8].copy_from_slice(&new_total_size.to_le_bytes());
+        update_checksum(&mut tbf[0..header_size as usize]);
+    }
+
+    tbf.truncate(content_end as usize);
+    push_reserved_footers(&mut tbf, new_total_size - content_end);
+    Ok(tbf)
+}
+
+/// Write a credentials footer at the start of the reserved footers at the
+/// end of a TBF object, see `reserve_footer_space()`.
+pub fn write_footer(
+    tbf: &mut [u8],
+    credentials: &TbfFooterV2Credentials,
+) -> Result<(), TbfBuildError> {
+    let (_, _, total_size) = parse_header(tbf)?;
+    let (start, reserved) = find_reserved_space(tbf)?;
+
+    let footer = encode_footer(credentials);
+    let remaining = reserved
+        .checked_sub(footer.len() as u32)
+        .filter(|remaining| !(1..MIN_FOOTER_SIZE).contains(remaining))
+        .ok_or(TbfBuildError::NoFooterSpace)?;
+
+    let mut footers = footer;
+    push_reserved_footers(&mut footers, remaining);
+    tbf[start as usize..total_size as usize].copy_from_slice(&footers);
+    Ok(())
+}
+
+/// Editor for the header of an existing TBF object.
+///
+/// The binary stays at the same offset, so the header can only grow into
+/// the protected trailer. The TLV entries keep their order, and new ones are
+/// added at the end. Credentials footers cover the header, so they are
+/// replaced by reserved space and the object has to be signed again;
+/// `build()` returns the credentials it dropped.
+#[derive(Clone, Debug)]
+pub struct TbfHeaderEditor {
+    tbf: Vec<u8>,
+    header_size: u32,
+    protected_size: u32,
+    init_fn_offset: u32,
+    binary_end: u32,
+    total_size: u32,
+    minimum_ram_size: u32,
+    /// The raw type and value of every entry, including Main and Program.
+    tlvs: Vec<(u16, Vec<u8>)>,
+    /// The credentials in the footers, without reserved space.
+    credentials: Vec<TbfFooterV2CredentialsType>,
+}
+
+impl TbfHeaderEditor {
+    /// Start editing the header of `tbf`, which must hold a whole app.
+    ///
+    /// The whole object is checked first, so that `build()` can rely on the
+    /// binary and footers fitting in it.
+    pub fn new(tbf: &[u8]) -> Result<Self, TbfBuildError> {
+        let (header, header_size, total_size) = parse_header(tbf)?;
+        let credentials = parse_tbf(tbf)
+            .map_err(|_| TbfBuildError::InvalidTbf)?
+            .footers
+            .map(|footer| footer.credentials.get_format())
+            .filter(|format| *format != TbfFooterV2CredentialsType::Reserved)
+            .collect();
+        // The footers are replaced by reserved footers, which need at least
+        // the space of an empty one.
+        if (1..MIN_FOOTER_SIZE).contains(&(total_size - header.get_binary_end())) {
+            return Err(TbfBuildError::InvalidTbf);
+        }
+        let tlvs = parse_tbf_header_tlvs(&tbf[0..header_size as usize])
+            .map(|tlv| tlv.map(|tlv| (tlv.tipe, tlv.value.to_vec())))
+            .collect::<Result<_, _>>()
+            .map_err(|_| TbfBuildError::InvalidTbf)?;
+
+        Ok(TbfHeaderEditor {
+            tbf: tbf[0..total_size as usize].to_vec(),
+            header_size,
+            protected_size: header.get_protected_size(),
+            init_fn_offset: header.get_init_function_offset(),
+            binary_end: header.get_binary_end(),
+            total_size,
+            minimum_ram_size: header.get_minimum_app_ram_size(),
+            tlvs,
+            credentials,
+        })
+    }
+
+    pub fn set_package_name(&mut self, name: &str) -> Result<(), TbfBuildError> {
+        if name.len() > MAX_PACKAGE_NAME_LENGTH {
+            return Err(TbfBuildError::PackageNameTooLong);
+        }
+        self.set_tlv(TbfHeaderTypes::TbfHeaderPackageName as u16, name.as_bytes())
+    }
+
+    pub fn set_minimum_ram_size(&mut self, size: u32) {
+        self.minimum_ram_size = size;
+    }
+
+    pub fn set_kernel_version(
+        &mut self,
+        version: Option<TbfHeaderV2KernelVersion>,
+    ) -> Result<(), TbfBuildError> {
+        let tipe = TbfHeaderTypes::TbfHeaderKernelVersion as u16;
+        match version {
+            Some(version) => self.set_tlv(tipe, &version.to_bytes()),
+            None => self.remove_tlv(tipe).map(|_| ()),
+        }
+    }
+
+    /// Replace the value of the first entry of type `tipe`, or add an entry
+    /// if there is none. The value is checked when building the object.
+    pub fn set_tlv(&mut self, tipe: u16, value: &[u8]) -> Result<(), TbfBuildError> {
+        check_editable(tipe)?;
+        match self.tlvs.iter_mut().find(|(t, _)| *t == tipe) {
+            Some((_, old)) => *old = value.to_vec(),
+            None => self.tlvs.push((tipe, value.to_vec())),
+        }
+        Ok(())
+    }
+
+    /// Remove every entry of type `tipe`. Returns whether there was any.
+    pub fn remove_tlv(&mut self, tipe: u16) -> Result<bool, TbfBuildError> {
+        check_editable(tipe)?;
+        let count = self.tlvs.len();
+        self.tlvs.retain(|(t, _)| *t != tipe);
+        Ok(self.tlvs.len() != count)
+    }
+
+    /// Build the edited TBF object, with the same total size. Returns the
+    /// object and the types of the credentials that were replaced by
+    /// reserved space.
+    pub fn build(&self) -> Result<(Vec<u8>, Vec<TbfFooterV2CredentialsType>), TbfBuildError> {
+        // The size of the Main and Program entries does not change.
+        let header_size = 16
+            + self
+                .tlvs
+                .iter()
+                .map(|(_, value)| 4 + align4!(value.len() as u32))
+                .sum::<u32>();
+        if header_size > self.protected_size {
+            return Err(TbfBuildError::HeaderOverlapsBinary(header_size));
+        }
+
+        // The entry point keeps its offset in the object.
+        let protected_trailer_size = self.protected_size - header_size;
+        let init_fn_offset = self
+            .init_fn_offset
+            .checked_sub(header_size)
+            .ok_or(TbfBuildError::HeaderOverlapsBinary(header_size))?;
+        let mut tlvs = Vec::new();
+        for (tipe, value) in &self.tlvs {
+            if *tipe == TbfHeaderTypes::TbfHeaderMain as u16 {
+                let main = TbfHeaderV2Main::new(
+                    init_fn_offset,
+                    protected_trailer_size,
+                    self.minimum_ram_size,
+                );
+                push_tlv(&mut tlvs, TbfHeaderTypes::TbfHeaderMain, &main.to_bytes());
+            } else if *tipe == TbfHeaderTypes::TbfHeaderProgram as u16 {
+                let program: TbfHeaderV2Program = value
+                    .as_slice()
+                    .try_into()
+                    .map_err(TbfBuildError::InvalidHeader)?;
+                let program = TbfHeaderV2Program::new(
+                    init_fn_offset,
+                    protected_trailer_size,
+                    self.minimum_ram_size,
+                    program.binary_end_offset,
+                    program.version,
+                );
+                push_tlv(
+                    &mut tlvs,
+                    TbfHeaderTypes::TbfHeaderProgram,
+                    &program.to_bytes(),
+                );
+            } else {
+                push_raw_tlv(&mut tlvs, *tipe, value);
+            }
+        }
+        if header_size > u16::MAX as u32 {
+            return Err(TbfBuildError::HeaderTooLarge);
+        }
+
+        let mut tbf = Vec::with_capacity(self.total_size as usize);
+        tbf.extend_from_slice(&self.tbf[0..2]);
+        tbf.extend_from_slice(&(header_size as u16).to_le_bytes());
+        tbf.extend_from_slice(&self.tbf[4..12]);
+        tbf.extend_from_slice(&0u32.to_le_bytes());
+        tbf.extend(tlvs);
+        update_checksum(&mut tbf);
+        parse_tbf_header(&tbf, 2).map_err(TbfBuildError::InvalidHeader)?;
+
+        // Keep the end of the protected trailer and the binary in place.
+        tbf.resize(self.header_size.max(header_size) as usize, 0);
+        tbf.extend_from_slice(&self.tbf[tbf.len()..self.binary_end as usize]);
+        push_reserved_footers(&mut tbf, self.total_size - self.binary_end);
+
+        Ok((tbf, self.credentials.clone()))
+    }
+}
+
+/// Check that an entry can be set or removed by `TbfHeaderEditor`.
+fn check_editable(tipe: u16) -> Result<(), TbfBuildError> {
+    if tipe == TbfHeaderTypes::TbfHeaderMain as u16
+        || tipe == TbfHeaderTypes::TbfHeaderProgram as u16
+    {
+        return Err(TbfBuildError::LayoutTlv(tipe));
+    }
+    Ok(())
+}
+
+/// Parse the header of a TBF object, making sure the whole object is there.
+fn parse_header(tbf: &[u8]) -> Result<(TbfHeader, u32, u32), TbfBuildError> {
+    let lengths = tbf.get(0..8).ok_or(TbfBuildError::InvalidTbf)?;
+    let (version, header_size, total_size) =
+        parse_tbf_header_lengths(&lengths.try_into().map_err(|_| TbfBuildError::InvalidTbf)?)
+            .map_err(|_| TbfBuildError::InvalidTbf)?;
+    if tbf.len() < total_size as usize {
+        return Err(TbfBuildError::InvalidTbf);
+    }
+
+    let header = parse_tbf_header(&tbf[0..header_size as usize], version)
+        .map_err(|_| TbfBuildError::InvalidTbf)?;
+    if !header.is_app() {
+        return Err(TbfBuildError::NotAnApp);
+    }
+    Ok((header, header_size as u32, total_size))
+}
+
+/// Find the reserved footers at the end of a TBF object. Returns where they
+/// start and their size, which is 0 if the object ends with other footers.
+fn find_reserved_space(tbf: &[u8]) -> Result<(u32, u32), TbfBuildError> {
+    let (header, _, total_size) = parse_header(tbf)?;
+
+    let mut offset = header.get_binary_end();
+    let mut reserved_start = offset;
+    while offset < total_size {
+        let (credentials, length) = parse_tbf_footer(&tbf[offset as usize..total_size as usize])
+            .map_err(|_| TbfBuildError::InvalidTbf)?;
+        offset += length + 4;
+        if !matches!(credentials, TbfFooterV2Credentials::Reserved(_)) {
+            reserved_start = offset;
+        }
+    }
+    if offset != total_size {
+        return Err(TbfBuildError::InvalidTbf);
+    }
+
+    Ok((reserved_start, total_size - reserved_start))
+}
+
+/// Set the checksum of the TBF header at the start of `header`, which must
+/// not contain anything past the header.
+fn update_checksum(header: &mut [u8]) {
+    let checksum = header
+        .chunks_exact(4)
+        .enumerate()
+        .filter(|(i, _)| *i != 3)
+        .fold(0, |checksum, (_, word)| {
+            checksum ^ u32::from_le_bytes([word[0], word[1], word[2], word[3]])
+        });
+    header[12..16].copy_from_slice(&checksum.to_le_bytes());
+}
+
+/// Fill `size` bytes with reserved credentials footers. `size` must be 0 or
+/// at least the size of an empty footer.
+fn push_reserved_footers(buffer: &mut Vec<u8>, size: u32) {
+    let mut remaining = size;
+    while remaining > 0 {
+        let size = if remaining > u16::MAX as u32 {
+            MAX_FOOTER_SIZE
+        } else {
+            remaining
+        };
+        push_tlv_header(
+            buffer,
+            TbfHeaderTypes::TbfFooterCredentials,
+            size as usize - 4,
+        );
+        buffer.extend_from_slice(&(TbfFooterV2CredentialsType::Reserved as u32).to_le_bytes());
+        buffer.resize(buffer.len() + size as usize - MIN_FOOTER_SIZE as usize, 0);
+        remaining -= size;
+    }
+}
+
+fn push_tlv_header(buffer: &mut Vec<u8>, tipe: TbfHeaderTypes, length: usize) {
+    buffer.extend_from_slice(&(tipe as u16).to_le_bytes());
+    buffer.extend_from_slice(&(length as u16).to_le_bytes());
+}
+
+fn push_tlv(buffer: &mut Vec<u8>, tipe: TbfHeaderTypes, value: &[u8]) {
+    push_raw_tlv(buffer, tipe as u16, value);
+}
+
+fn push_raw_tlv(buffer: &mut Vec<u8>, tipe: u16, value: &[u8]) {
+    buffer.extend_from_slice(&tipe.to_le_bytes());
+    buffer.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    buffer.extend_from_slice(value);
+    buffer.resize(align4!(buffer.len()), 0);
+}
@@ -11,6 +11,16 @@
 #![forbid(unsafe_code)]
 #![no_std]
 
+#[cfg(feature = "std")]
+extern crate std;
+
+#[cfg(feature = "std")]
+pub mod builder;
+#[cfg(feature = "std")]
+pub mod owned;
+
 pub mod parse;
 #[allow(dead_code)] // Some fields not read on device, but read when creating headers
 pub mod types;
+#[cfg(feature = "crypto")]
+pub mod verify;
@@ -0,0 +1,654 @@
+// Licensed under the Apache License, Version 2.0 or the MIT License.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+// Copyright OXIDOS AUTOMOTIVE 2024.
+
+//! Heap-backed TBF headers.
+//!
+//! [`TbfHeader`] uses fixed-size buffers so that it can be parsed on-device,
+//! which limits how many writeable flash regions, permissions and storage IDs
+//! an app can have, and how long its package name can be. [`OwnedTbfHeader`]
+//! holds the same information without these limits, so that host tools can
+//! inspect any valid TBF.
+
+use core::mem;
+use core::num::NonZeroU32;
+use std::boxed::Box;
+use std::string::String;
+use std::vec::Vec;
+
+use crate::parse::{
+    check_header_checksum, checked_binary_range, parse_tbf_header_tlvs, parse_tbf_object,
+    TbfBinaryRange, TbfList, TbfObject,
+};
+use crate::types::{
+    CommandPermissions, TbfHeader, TbfHeaderDriverPermission, TbfHeaderTypes, TbfHeaderV2,
+    TbfHeaderV2Base, TbfHeaderV2FixedAddresses, TbfHeaderV2KernelVersion, TbfHeaderV2Main,
+    TbfHeaderV2PackageName, TbfHeaderV2Permissions, TbfHeaderV2PicOption1, TbfHeaderV2Program,
+    TbfHeaderV2ShortId, TbfHeaderV2StoragePermissions, TbfHeaderV2UnknownTlv,
+    TbfHeaderV2WriteableFlashRegion, TbfParseError, TbfTlvEntry, NUM_STORAGE_PERMISSIONS,
+    NUM_UNKNOWN_TLVS,
+};
+
+/// Storage permissions with any number of read and modify IDs.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedStoragePermissions {
+    pub(crate) write_id: Option<NonZeroU32>,
+    pub(crate) read_ids: Vec<u32>,
+    pub(crate) modify_ids: Vec<u32>,
+}
+
+/// All parts of a v2 header, without limits on the variable length entries.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedTbfHeaderV2 {
+    pub(crate) base: TbfHeaderV2Base,
+    pub(crate) main: Option<TbfHeaderV2Main>,
+    pub(crate) program: Option<TbfHeaderV2Program>,
+    pub(crate) package_name: Option<String>,
+    pub(crate) writeable_regions: Vec<TbfHeaderV2WriteableFlashRegion>,
+    pub(crate) fixed_addresses: Option<TbfHeaderV2FixedAddresses>,
+    pub(crate) permissions: Option<Vec<TbfHeaderDriverPermission>>,
+    pub(crate) storage_permissions: Option<OwnedStoragePermissions>,
+    pub(crate) kernel_version: Option<TbfHeaderV2KernelVersion>,
+    pub(crate) pic_option1: Option<TbfHeaderV2PicOption1>,
+    pub(crate) short_id: Option<TbfHeaderV2ShortId>,
+    pub(crate) unknown_tlvs: Vec<TbfHeaderV2UnknownTlv>,
+}
+
+/// Heap-backed equivalent of [`TbfHeader`], with the same accessors.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OwnedTbfHeader {
+    TbfHeaderV2(Box<OwnedTbfHeaderV2>),
+    Padding(TbfHeaderV2Base),
+}
+
+/// Parse a TBF header into an [`OwnedTbfHeader`].
+///
+/// This accepts the same input as `parse::parse_tbf_header()`, but never fails
+/// because an entry is too long for a fixed-size buffer.
+pub fn parse_owned_tbf_header(
+    header: &[u8],
+    version: u16,
+) -> Result<OwnedTbfHeader, TbfParseError> {
+    if version != 2 {
+        return Err(TbfParseError::UnsupportedVersion(version));
+    }
+
+    let base: TbfHeaderV2Base = header.try_into()?;
+    check_header_checksum(header, &base)?;
+
+    // If there is nothing after the base then this is just padding.
+    if header.len() <= 16 {
+        return Ok(OwnedTbfHeader::Padding(base));
+    }
+
+    let mut hd = OwnedTbfHeaderV2 {
+        base,
+        main: None,
+        program: None,
+        package_name: None,
+        writeable_regions: Vec::new(),
+        fixed_addresses: None,
+        permissions: None,
+        storage_permissions: None,
+        kernel_version: None,
+        pic_option1: None,
+        short_id: None,
+        unknown_tlvs: Vec::new(),
+    };
+
+    for tlv in parse_tbf_header_tlvs(header) {
+        let tlv = tlv?;
+        match TbfHeaderTypes::try_from(tlv.tipe)? {
+            // Only the first Main and Program entries count.
+            TbfHeaderTypes::TbfHeaderMain => {
+                let main = parse_fixed(&tlv)?;
+                hd.main.get_or_insert(main);
+            }
+            TbfHeaderTypes::TbfHeaderProgram => {
+                let program = parse_fixed(&tlv)?;
+                hd.program.get_or_insert(program);
+            }
+            TbfHeaderTypes::TbfHeaderWriteableFlashRegions => {
+                let region_len = mem::size_of::<TbfHeaderV2WriteableFlashRegion>();
+                if !tlv.value.len().is_multiple_of(region_len) {
+                    return Err(TbfParseError::BadTlvEntry(tlv.tipe as usize, tlv.offset));
+                }
+                for region in tlv.value.chunks_exact(region_len) {
+                    hd.writeable_regions.push(region.try_into()?);
+                }
+            }
+            TbfHeaderTypes::TbfHeaderPackageName => {
+                let name = core::str::from_utf8(tlv.value)
+                    .map_err(|_| TbfParseError::BadProcessName(tlv.offset))?;
+                hd.package_name = Some(String::from(name));
+            }
+            TbfHeaderTypes::TbfHeaderFixedAddresses => {
+                hd.fixed_addresses = Some(parse_fixed(&tlv)?);
+            }
+            TbfHeaderTypes::TbfHeaderPermissions => {
+                let (count, entries) = split_count(tlv.value)?;
+                let permission_len = mem::size_of::<TbfHeaderDriverPermission>();
+                let permissions = entries
+                    .get(0..count * permission_len)
+                    .ok_or(TbfParseError::NotEnoughFlash)?
+                    .chunks_exact(permission_len)
+                    .map(TbfHeaderDriverPermission::try_from)
+                    .collect::<Result<_, _>>()?;
+                hd.permissions = Some(permissions);
+            }
+            TbfHeaderTypes::TbfHeaderStoragePermissions => {
+                let write_id = NonZeroU32::new(u32::from_le_bytes(
+                    tlv.value
+                        .get(0..4)
+                        .ok_or(TbfParseError::NotEnoughFlash)?
+                        .try_into()?,
+                ));
+                let (read_ids, rest) = split_ids(tlv.value.get(4..).unwrap_or_default())?;
+                let (modify_ids, _) = split_ids(rest)?;
+                hd.storage_permissions = Some(OwnedStoragePermissions {
+                    write_id,
+                    read_ids,
+                    modify_ids,
+                });
+            }
+            TbfHeaderTypes::TbfHeaderKernelVersion => {
+                hd.kernel_version = Some(parse_fixed(&tlv)?);
+            }
+            TbfHeaderTypes::TbfHeaderPicOption1 => {
+                hd.pic_option1 = Some(parse_fixed(&tlv)?);
+            }
+            TbfHeaderTypes::TbfHeaderShortId => {
+                hd.short_id = Some(parse_fixed(&tlv)?);
+            }
+            TbfHeaderTypes::TbfFooterCredentials | TbfHeaderTypes::Unknown => {
+                hd.unknown_tlvs.push(TbfHeaderV2UnknownTlv {
+                    tipe: tlv.tipe,
+                    offset: tlv.offset as u16,
+                    length: tlv.value.len() as u16,
+                });
+            }
+        }
+    }
+
+    Ok(OwnedTbfHeader::TbfHeaderV2(Box::new(hd)))
+}
+
+/// Parse a whole TBF object like `parse::parse_tbf()`, with an
+/// [`OwnedTbfHeader`].
+pub fn parse_owned_tbf(data: &[u8]) -> Result<TbfObject<'_, OwnedTbfHeader>, TbfParseError> {
+    parse_tbf_object(data, parse_owned_tbf_header)
+}
+
+/// Walk the app linked list like `parse::parse_tbf_list()`, with
+/// [`OwnedTbfHeader`]s.
+pub fn parse_owned_tbf_list(data: &[u8], address: u64) -> TbfList<'_, OwnedTbfHeader> {
+    TbfList::new(data, address, parse_owned_tbf)
+}
+
+impl TbfBinaryRange for OwnedTbfHeader {
+    fn binary_range(&self) -> Result<Option<(u32, u32)>, TbfParseError> {
+        match self.program_fields() {
+            Some((_, protected_trailer_size, _)) => checked_binary_range(
+                self.header_size(),
+                protected_trailer_size,
+                self.get_binary_end(),
+            ),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Parse a TLV entry whose value has the exact size of `T`.
+fn parse_fixed<'a, T>(tlv: &TbfTlvEntry<'a>) -> Result<T, TbfParseError>
+where
+    T: TryFrom<&'a [u8], Error = TbfParseError>,
+{
+    if tlv.value.len() != mem::size_of::<T>() {
+        return Err(TbfParseError::BadTlvEntry(tlv.tipe as usize, tlv.offset));
+    }
+    T::try_from(tlv.value)
+}
+
+/// Split a list prefixed with its `u16` length into the length and the rest.
+fn split_count(value: &[u8]) -> Result<(usize, &[u8]), TbfParseError> {
+    let count = u16::from_le_bytes(
+        value
+            .get(0..2)
+            .ok_or(TbfParseError::NotEnoughFlash)?
+            .try_into()?,
+    );
+    Ok((count as usize, &value[2..]))
+}
+
+/// Parse a list of `u32` storage IDs prefixed with its `u16` length, and
+/// return it with whatever follows it.
+fn split_ids(value: &[u8]) -> Result<(Vec<u32>, &[u8]), TbfParseError> {
+    let (count, entries) = split_count(value)?;
+    let ids_len = count * mem::size_of::<u32>();
+    let ids = entries
+        .get(0..ids_len)
+        .ok_or(TbfParseError::NotEnoughFlash)?
+        .chunks_exact(mem::size_of::<u32>())
+        .map(|id| Ok(u32::from_le_bytes(id.try_into()?)))
+        .collect::<Result<_, TbfParseError>>()?;
+    Ok((ids, &entries[ids_len..]))
+}
+
+impl From<&TbfHeader> for OwnedTbfHeader {
+    fn from(header: &TbfHeader) -> Self {
+        let hd = match header {
+            TbfHeader::TbfHeaderV2(hd) => hd,
+            TbfHeader::Padding(base) => return OwnedTbfHeader::Padding(*base),
+        };
+        OwnedTbfHeader::TbfHeaderV2(Box::new(OwnedTbfHeaderV2 {
+            base: hd.base,
+            main: hd.main,
+            program: hd.program,
+            package_name: header.get_package_name().map(String::from),
+            writeable_regions: hd
+                .writeable_regions
+                .iter()
+                .flatten()
+                .flatten()
+                .copied()
+                .collect(),
+            fixed_addresses: hd.fixed_addresses,
+            permissions: header
+                .get_all_command_permissions()
+                .map(|permissions| permissions.to_vec()),
+            storage_permissions: hd.storage_permissions.map(|permissions| {
+                OwnedStoragePermissions {
+                    write_id: permissions.write_id,
+                    read_ids: permissions.read_ids[..permissions.read_length as usize].to_vec(),
+                    modify_ids: permissions.modify_ids[..permissions.modify_length as usize]
+                        .to_vec(),
+                }
+            }),
+            kernel_version: hd.kernel_version,
+            pic_option1: hd.pic_option1,
+            short_id: hd.short_id,
+            unknown_tlvs: header.get_unknown_tlvs().copied().collect(),
+        }))
+    }
+}
+
+/// Copy a list into a fixed-size buffer, failing with `TooManyEntries` if it
+/// does not fit.
+fn to_array<T: Copy + Default, const L: usize>(
+    values: &[T],
+    tipe: TbfHeaderTypes,
+) -> Result<[T; L], TbfParseError> {
+    let mut buffer = [T::default(); L];
+    buffer
+        .get_mut(..values.len())
+        .ok_or(TbfParseError::TooManyEntries(tipe as usize))?
+        .copy_from_slice(values);
+    Ok(buffer)
+}
+
+impl TryFrom<&OwnedTbfHeader> for TbfHeader {
+    type Error = TbfParseError;
+
+    /// Fit the header in the fixed-size buffers of [`TbfHeader`]. Fails with
+    /// `TooManyEntries` or `PackageNameTooLong` if it does not fit. Unknown
+    /// TLV entries past the first `NUM_UNKNOWN_TLVS` are not kept.
+    fn try_from(header: &OwnedTbfHeader) -> Result<Self, Self::Error> {
+        let hd = match header {
+            OwnedTbfHeader::TbfHeaderV2(hd) => hd,
+            OwnedTbfHeader::Padding(base) => return Ok(TbfHeader::Padding(*base)),
+        };
+
+        let regions: Vec<_> = hd.writeable_regions.iter().copied().map(Some).collect();
+        let permissions = match &hd.permissions {
+            Some(permissions) => Some(TbfHeaderV2Permissions {
+                length: permissions.len() as u16,
+                perms: to_array(permissions, TbfHeaderTypes::TbfHeaderPermissions)?,
+            }),
+            None => None,
+        };
+        let storage_permissions = match &hd.storage_permissions {
+            Some(permissions) => Some(TbfHeaderV2StoragePermissions::<NUM_STORAGE_PERMISSIONS> {
+                write_id: permissions.write_id,
+                read_length: permissions.read_ids.len() as u16,
+                read_ids: to_array(
+                    &permissions.read_ids,
+                    TbfHeaderTypes::TbfHeaderStoragePermissions,
+                )?,
+                modify_length: permissions.modify_ids.len() as u16,
+                modify_ids: to_array(
+                    &permissions.modify_ids,
+                    TbfHeaderTypes::TbfHeaderStoragePermissions,
+                )?,
+            }),
+            None => None,
+        };
+        let unknown_tlvs: Vec<_> = hd
+            .unknown_tlvs
+            .iter()
+            .take(NUM_UNKNOWN_TLVS)
+            .copied()
+            .map(Some)
+            .collect();
+
+        Ok(TbfHeader::TbfHeaderV2(TbfHeaderV2 {
+            base: hd.base,
+            main: hd.main,
+            program: hd.program,
+            package_name: hd
+                .package_name
+                .as_deref()
+                .map(|name| TbfHeaderV2PackageName::try_from(name.as_bytes()))
+                .transpose()?,
+            writeable_regions: Some(to_array(
+                &regions,
+                TbfHeaderTypes::TbfHeaderWriteableFlashRegions,
+            )?),
+            fixed_addresses: hd.fixed_addresses,
+            permissions,
+            storage_permissions,
+            kernel_version: hd.kernel_version,
+            pic_option1: hd.pic_option1,
+            short_id: hd.short_id,
+            unknown_tlvs: to_array(&unknown_tlvs, TbfHeaderTypes::Unknown)?,
+        }))
+    }
+}
+
+impl OwnedTbfHeader {
+    /// Return the length of the header.
+    pub fn length(&self) -> u16 {
+        match self {
+            OwnedTbfHeader::TbfHeaderV2(hd) => hd.base.header_size,
+            OwnedTbfHeader::Padding(base) => base.header_size,
+        }
+    }
+
+    /// Return whether this is an app or just padding between apps.
+    pub fn is_app(&self) -> bool {
+        matches!(self, OwnedTbfHeader::TbfHeaderV2(_))
+    }
+
+    /// Return whether the application is enabled or not.
+    /// Disabled applications are not started by the kernel.
+    pub fn enabled(&self) -> bool {
+        match self {
+            // Bit 1 of flags is the enable/disable bit.
+            OwnedTbfHeader::TbfHeaderV2(hd) => hd.base.flags & 0x00000001 == 1,
+            OwnedTbfHeader::Padding(_) => false,
+        }
+    }
+
+    /// Return whether the application is sticky or not.
+    /// Sticky applications require additional confirmation to be erased.
+    pub fn sticky(&self) -> bool {
+        match self {
+            // Bit 2 of flags is the sticky bit.
+            OwnedTbfHeader::TbfHeaderV2(hd) => hd.base.flags & 0x00000002 != 0,
+            OwnedTbfHeader::Padding(_) => false,
+        }
+    }
+
+    /// Return total size of the application.
+    pub fn total_size(&self) -> u32 {
+        match self {
+            OwnedTbfHeader::TbfHeaderV2(hd) => hd.base.total_size,
+            OwnedTbfHeader::Padding(_) => 0,
+        }
+    }
+
+    /// Return checksum of the application.
+    pub fn checksum(&self) -> u32 {
+        match self {
+            OwnedTbfHeader::TbfHeaderV2(hd) => hd.base.checksum,
+            OwnedTbfHeader::Padding(_) => 0,
+        }
+    }
+
+    /// Return header size of the application.
+    pub fn header_size(&self) -> u16 {
+        match self {
+            OwnedTbfHeader::TbfHeaderV2(hd) => hd.base.header_size,
+            OwnedTbfHeader::Padding(_) => 0,
+        }
+    }
+
+    /// The init function offset, protected trailer size and minimum RAM size,
+    /// from the Program header or else from the Main header.
+    fn program_fields(&self) -> Option<(u32, u32, u32)> {
+        let OwnedTbfHeader::TbfHeaderV2(hd) = self else {
+            return None;
+        };
+        match (hd.program, hd.main) {
+            (Some(p), _) => Some((
+                p.init_fn_offset,
+                p.protected_trailer_size,
+                p.minimum_ram_size,
+            )),
+            (None, Some(m)) => Some((
+                m.init_fn_offset,
+                m.protected_trailer_size,
+                m.minimum_ram_size,
+            )),
+            (None, None) => None,
+        }
+    }
+
+    /// Get the total amount of RAM that is needed for this app.
+    pub fn get_minimum_app_ram_size(&self) -> u32 {
+        self.program_fields()
+            .map_or(0, |(_, _, minimum_ram_size)| minimum_ram_size)
+    }
+
+    /// Get the number of bytes from the start of the app's region in flash that
+    /// is for kernel use only. The app cannot write this region.
+    pub fn get_protected_size(&self) -> u32 {
+        self.program_fields()
+            .map_or(0, |(_, protected_trailer_size, _)| {
+                (self.header_size() as u32).saturating_add(protected_trailer_size)
+            })
+    }
+
+    /// Get the start offset of the application binary from the beginning
+    /// of the process binary (start of the TBF header). Only valid if this
+    /// is an app.
+    pub fn get_app_start_offset(&self) -> u32 {
+        self.get_protected_size()
+    }
+
+    /// Get the offset from the beginning of the app's flash region where the
+    /// app should start executing.
+    pub fn get_init_function_offset(&self) -> u32 {
+        self.program_fields().map_or(0, |(init_fn_offset, _, _)| {
+            init_fn_offset.saturating_add(self.header_size() as u32)
+        })
+    }
+
+    /// Get the name of the app.
+    pub fn get_package_name(&self) -> Option<&str> {
+        match self {
+            OwnedTbfHeader::TbfHeaderV2(hd) => hd.package_name.as_deref(),
+            OwnedTbfHeader::Padding(_) => None,
+        }
+    }
+
+    /// Get the number of flash regions this app has specified in its header.
+    pub fn number_writeable_flash_regions(&self) -> usize {
+        match self {
+            OwnedTbfHeader::TbfHeaderV2(hd) => hd.writeable_regions.len(),
+            OwnedTbfHeader::Padding(_) => 0,
+        }
+    }
+
+    /// Get every writeable flash region, in the order they are stored.
+    pub fn get_writeable_flash_regions(&self) -> &[TbfHeaderV2WriteableFlashRegion] {
+        match self {
+            OwnedTbfHeader::TbfHeaderV2(hd) => &hd.writeable_regions,
+            OwnedTbfHeader::Padding(_) => &[],
+        }
+    }
+
+    /// Get the offset and size of a given flash region.
+    pub fn get_writeable_flash_region(&self, index: usize) -> (u32, u32) {
+        match self {
+            OwnedTbfHeader::TbfHeaderV2(hd) => {
+                hd.writeable_regions.get(index).map_or((0, 0), |wr| {
+                    (
+                        wr.writeable_flash_region_offset,
+                        wr.writeable_flash_region_size,
+                    )
+                })
+            }
+            OwnedTbfHeader::Padding(_) => (0, 0),
+        }
+    }
+
+    /// Get the address in RAM this process was specifically compiled for. If
+    /// the process is position independent, return `None`.
+    pub fn get_fixed_address_ram(&self) -> Option<u32> {
+        let OwnedTbfHeader::TbfHeaderV2(hd) = self else {
+            return None;
+        };
+        match hd.fixed_addresses.as_ref()?.start_process_ram {
+            0xFFFFFFFF => None,
+            start => Some(start),
+        }
+    }
+
+    /// Get the address in flash this process was specifically compiled for. If
+    /// the process is position independent, return `None`.
+    pub fn get_fixed_address_flash(&self) -> Option<u32> {
+        let OwnedTbfHeader::TbfHeaderV2(hd) = self else {
+            return None;
+        };
+        match hd.fixed_addresses.as_ref()?.start_process_flash {
+            0xFFFFFFFF => None,
+            start => Some(start),
+        }
+    }
+
+    /// Get the permissions for a specified driver and offset. See
+    /// `TbfHeader::get_command_permissions()`.
+    pub fn get_command_permissions(&self, driver_num: usize, offset: usize) -> CommandPermissions {
+        let Some(permissions) = self.get_all_command_permissions() else {
+            return CommandPermissions::NoPermsAtAll;
+        };
+        let mut found_driver_num = false;
+        for perm in permissions {
+            if perm.get_driver_number() == driver_num as u32 {
+                found_driver_num = true;
+                if perm.get_offset() == offset as u32 {
+                    return CommandPermissions::Mask(perm.get_allowed_commands());
+                }
+            }
+        }
+        if found_driver_num {
+            // Permissions are default off.
+            CommandPermissions::Mask(0)
+        } else {
+            CommandPermissions::NoPermsThisDriver
+        }
+    }
+
+    /// Get every driver permission entry of the app.
+    /// Returns `None` if the permissions header is not included.
+    pub fn get_all_command_permissions(&self) -> Option<&[TbfHeaderDriverPermission]> {
+        match self {
+            OwnedTbfHeader::TbfHeaderV2(hd) => hd.permissions.as_deref(),
+            OwnedTbfHeader::Padding(_) => None,
+        }
+    }
+
+    /// Get the storage permissions of the app.
+    /// Returns `None` if the storage permissions header is not included.
+    pub fn get_storage_permissions(&self) -> Option<&OwnedStoragePermissions> {
+        match self {
+            OwnedTbfHeader::TbfHeaderV2(hd) => hd.storage_permissions.as_ref(),
+            OwnedTbfHeader::Padding(_) => None,
+        }
+    }
+
+    /// Get the process `write_id`.
+    ///
+    /// Returns `None` if a `write_id` is not included. This indicates the TBF
+    /// does not have the ability to store new items.
+    pub fn get_storage_write_id(&self) -> Option<NonZeroU32> {
+        self.get_storage_permissions()?.write_id
+    }
+
+    /// Get the `read_ids`.
+    /// Returns `None` if the storage permissions header is not included.
+    pub fn get_storage_read_ids(&self) -> Option<&[u32]> {
+        self.get_storage_permissions()
+            .map(|permissions| &permissions.read_ids[..])
+    }
+
+    /// Get the `modify_ids`.
+    /// Returns `None` if the storage permissions header is not included.
+    pub fn get_storage_modify_ids(&self) -> Option<&[u32]> {
+        self.get_storage_permissions()
+            .map(|permissions| &permissions.modify_ids[..])
+    }
+
+    /// Get the minimum compatible kernel version this process requires.
+    /// Returns `None` if the kernel compatibility header is not included.
+    pub fn get_kernel_version(&self) -> Option<(u16, u16)> {
+        match self {
+            OwnedTbfHeader::TbfHeaderV2(hd) => hd
+                .kernel_version
+                .map(|kernel_version| (kernel_version.major, kernel_version.minor)),
+            OwnedTbfHeader::Padding(_) => None,
+        }
+    }
+
+    /// Get the fixed ShortId this process requires.
+    /// Returns `None` if the ShortId header is not included or asks for a
+    /// locally unique ShortId.
+    pub fn get_fixed_short_id(&self) -> Option<NonZeroU32> {
+        match self {
+            OwnedTbfHeader::TbfHeaderV2(hd) => hd.short_id.and_then(|short_id| short_id.short_id),
+            OwnedTbfHeader::Padding(_) => None,
+        }
+    }
+
+    /// Get the relocation information of the PIC Option 1 header.
+    /// Returns `None` if the header is not included.
+    pub fn get_pic_option1(&self) -> Option<&TbfHeaderV2PicOption1> {
+        match self {
+            OwnedTbfHeader::TbfHeaderV2(hd) => hd.pic_option1.as_ref(),
+            OwnedTbfHeader::Padding(_) => None,
+        }
+    }
+
+    /// Get the TLV entries the parser did not understand, in the order they
+    /// are stored.
+    pub fn get_unknown_tlvs(&self) -> &[TbfHeaderV2UnknownTlv] {
+        match self {
+            OwnedTbfHeader::TbfHeaderV2(hd) => &hd.unknown_tlvs,
+            OwnedTbfHeader::Padding(_) => &[],
+        }
+    }
+
+    /// Return the offset where the binary ends in the TBF or 0 if there
+    /// is no binary. If there is a Main header the end offset is the size
+    /// of the TBF, while if there is a Program header it can be smaller.
+    pub fn get_binary_end(&self) -> u32 {
+        match self {
+            OwnedTbfHeader::TbfHeaderV2(hd) => hd
+                .program
+                .map_or(hd.base.total_size, |p| p.binary_end_offset),
+            OwnedTbfHeader::Padding(_) => 0,
+        }
+    }
+
+    /// Return the version number of the Userspace Binary in this TBF
+    /// Object, or 0 if there is no binary or no version number.
+    pub fn get_binary_version(&self) -> u32 {
+        match self {
+            OwnedTbfHeader::TbfHeaderV2(hd) => hd.program.map_or(0, |p| p.version),
+            OwnedTbfHeader::Padding(_) => 0,
+        }
+    }
+}
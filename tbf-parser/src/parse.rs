@@ -90,30 +90,7 @@ pub fn parse_tbf_header(
             // first bit of the header already in `parse_tbf_header_lengths()`.
             let tbf_header_base: types::TbfHeaderV2Base = header.try_into()?;
 
-            // Calculate checksum. The checksum is the XOR of each 4 byte word
-            // in the header.
-            let mut checksum: u32 = 0;
-
-            // Get an iterator across 4 byte fields in the header.
-            let header_iter = header.chunks_exact(4);
-
-            // Iterate all chunks and XOR the chunks to compute the checksum.
-            for (i, chunk) in header_iter.enumerate() {
-                let word = u32::from_le_bytes(chunk.try_into()?);
-                if i == 3 {
-                    // Skip the checksum field.
-                } else {
-                    checksum ^= word;
-                }
-            }
-
-            // Verify the header matches.
-            if checksum != tbf_header_base.checksum {
-                return Err(types::TbfParseError::ChecksumMismatch(
-                    tbf_header_base.checksum,
-                    checksum,
-                ));
-            }
+            check_header_checksum(header, &tbf_header_base)?;
 
             // Get the rest of the header. The `remaining` variable will
             // continue to hold the remainder of the header we have not
@@ -143,11 +120,17 @@ pub fn parse_tbf_header(
                     types::TbfHeaderV2StoragePermissions<8>,
                 > = None;
                 let mut kernel_version: Option<types::TbfHeaderV2KernelVersion> = None;
+                let mut pic_option1_pointer: Option<types::TbfHeaderV2PicOption1> = None;
+                let mut short_id_pointer: Option<types::TbfHeaderV2ShortId> = None;
+                let mut unknown_tlvs: [Option<types::TbfHeaderV2UnknownTlv>;
+                    types::NUM_UNKNOWN_TLVS] = Default::default();
+                let mut number_unknown_tlvs = 0;
 
                 // Iterate the remainder of the header looking for TLV entries.
                 while !remaining.is_empty() {
                     // Get the T and L portions of the next header (if it is
                     // there).
+                    let tlv_offset = header.len() - remaining.len();
                     let tlv_header: types::TbfTlv = remaining
                         .get(0..4)
                         .ok_or(types::TbfParseError::NotEnoughFlash)?
@@ -172,6 +155,7 @@ pub fn parse_tbf_header(
                                 } else {
                                     return Err(types::TbfParseError::BadTlvEntry(
                                         tlv_header.tipe as usize,
+                                        tlv_offset,
                                     ));
                                 }
                             }
@@ -189,15 +173,17 @@ pub fn parse_tbf_header(
                                 } else {
                                     return Err(types::TbfParseError::BadTlvEntry(
                                         tlv_header.tipe as usize,
+                                        tlv_offset,
                                     ));
                                 }
                             }
                         }
                         types::TbfHeaderTypes::TbfHeaderWriteableFlashRegions => {
                             // Length must be a multiple of the size of a region definition.
-                            if tlv_header.length as usize
-                                % mem::size_of::<types::TbfHeaderV2WriteableFlashRegion>()
-                                == 0
+                            if (tlv_header.length as usize)
+                                .is_multiple_of(mem::size_of::<
+                                    types::TbfHeaderV2WriteableFlashRegion,
+                                >())
                             {
                                 // Calculate how many writeable flash regions
                                 // there are specified in this header.
@@ -230,6 +216,7 @@ pub fn parse_tbf_header(
                             } else {
                                 return Err(types::TbfParseError::BadTlvEntry(
                                     tlv_header.tipe as usize,
+                                    tlv_offset,
                                 ));
                             }
                         }
@@ -239,7 +226,11 @@ pub fn parse_tbf_header(
                                 .get(0..tlv_header.length as usize)
                                 .ok_or(types::TbfParseError::NotEnoughFlash)?;
 
-                            package_name_pointer = Some(name_buf.try_into()?);
+                            package_name_pointer = Some(
+                                name_buf
+                                    .try_into()
+                                    .map_err(|e: types::TbfParseError| e.in_tlv(tlv_offset))?,
+                            );
                         }
 
                         types::TbfHeaderTypes::TbfHeaderFixedAddresses => {
@@ -254,16 +245,25 @@ pub fn parse_tbf_header(
                             } else {
                                 return Err(types::TbfParseError::BadTlvEntry(
                                     tlv_header.tipe as usize,
+                                    tlv_offset,
                                 ));
                             }
                         }
 
                         types::TbfHeaderTypes::TbfHeaderPermissions => {
-                            permissions_pointer = Some(remaining.try_into()?);
+                            permissions_pointer = Some(
+                                remaining
+                                    .try_into()
+                                    .map_err(|e: types::TbfParseError| e.in_tlv(tlv_offset))?,
+                            );
                         }
 
                         types::TbfHeaderTypes::TbfHeaderStoragePermissions => {
-                            storage_permissions_pointer = Some(remaining.try_into()?);
+                            storage_permissions_pointer = Some(
+                                remaining
+                                    .try_into()
+                                    .map_err(|e: types::TbfParseError| e.in_tlv(tlv_offset))?,
+                            );
                         }
 
                         types::TbfHeaderTypes::TbfHeaderKernelVersion => {
@@ -278,11 +278,62 @@ pub fn parse_tbf_header(
                             } else {
                                 return Err(types::TbfParseError::BadTlvEntry(
                                     tlv_header.tipe as usize,
+                                    tlv_offset,
                                 ));
                             }
                         }
 
-                        _ => {}
+                        types::TbfHeaderTypes::TbfHeaderPicOption1 => {
+                            let entry_len = mem::size_of::<types::TbfHeaderV2PicOption1>();
+                            if tlv_header.length as usize == entry_len {
+                                pic_option1_pointer = Some(
+                                    remaining
+                                        .get(0..entry_len)
+                                        .ok_or(types::TbfParseError::NotEnoughFlash)?
+                                        .try_into()?,
+                                );
+                            } else {
+                                return Err(types::TbfParseError::BadTlvEntry(
+                                    tlv_header.tipe as usize,
+                                    tlv_offset,
+                                ));
+                            }
+                        }
+
+                        types::TbfHeaderTypes::TbfHeaderShortId => {
+                            let entry_len = mem::size_of::<u32>();
+                            if tlv_header.length as usize == entry_len {
+                                short_id_pointer = Some(
+                                    remaining
+                                        .get(0..entry_len)
+                                        .ok_or(types::TbfParseError::NotEnoughFlash)?
+                                        .try_into()?,
+                                );
+                            } else {
+                                return Err(types::TbfParseError::BadTlvEntry(
+                                    tlv_header.tipe as usize,
+                                    tlv_offset,
+                                ));
+                            }
+                        }
+
+                        _ => {
+                            // Remember where the entry is. To enable a static
+                            // buffer, only the first few are kept.
+                            if let Some(unknown_tlv) = unknown_tlvs.get_mut(number_unknown_tlvs) {
+                                *unknown_tlv = Some(types::TbfHeaderV2UnknownTlv {
+                                    tipe: u16::from_le_bytes(
+                                        header
+                                            .get(tlv_offset..tlv_offset + 2)
+                                            .ok_or(types::TbfParseError::InternalError)?
+                                            .try_into()?,
+                                    ),
+                                    offset: tlv_offset as u16,
+                                    length: tlv_header.length,
+                                });
+                                number_unknown_tlvs += 1;
+                            }
+                        }
                     }
 
                     // All TLV blocks are padded to 4 bytes, so we need to skip
@@ -303,6 +354,9 @@ pub fn parse_tbf_header(
                     permissions: permissions_pointer,
                     storage_permissions: storage_permissions_pointer,
                     kernel_version,
+                    pic_option1: pic_option1_pointer,
+                    short_id: short_id_pointer,
+                    unknown_tlvs,
                 };
 
                 Ok(types::TbfHeader::TbfHeaderV2(tbf_header))
@@ -312,6 +366,98 @@ pub fn parse_tbf_header(
     }
 }
 
+/// Check the checksum stored in the base of a TBF header against the header.
+pub(crate) fn check_header_checksum(
+    header: &[u8],
+    base: &types::TbfHeaderV2Base,
+) -> Result<(), types::TbfParseError> {
+    // Calculate checksum. The checksum is the XOR of each 4 byte word
+    // in the header.
+    let mut checksum: u32 = 0;
+
+    // Get an iterator across 4 byte fields in the header.
+    let header_iter = header.chunks_exact(4);
+
+    // Iterate all chunks and XOR the chunks to compute the checksum.
+    for (i, chunk) in header_iter.enumerate() {
+        let word = u32::from_le_bytes(chunk.try_into()?);
+        if i == 3 {
+            // Skip the checksum field.
+        } else {
+            checksum ^= word;
+        }
+    }
+
+    // Verify the header matches.
+    if checksum != base.checksum {
+        return Err(types::TbfParseError::ChecksumMismatch(
+            base.checksum,
+            checksum,
+        ));
+    }
+    Ok(())
+}
+
+/// Iterate over the raw TLV entries of a TBF header.
+///
+/// The `header` must be a slice that only contains the TBF header, just like
+/// for `parse_tbf_header()`. Unlike `parse_tbf_header()`, this does not
+/// interpret the entries, so entries of types this library does not know about
+/// are returned as well. Iteration stops after the first error.
+pub fn parse_tbf_header_tlvs(header: &[u8]) -> TbfTlvEntries<'_> {
+    TbfTlvEntries { header, offset: 16 }
+}
+
+/// Iterator over the raw TLV entries of a TBF header. See
+/// `parse_tbf_header_tlvs()`.
+pub struct TbfTlvEntries<'a> {
+    header: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> TbfTlvEntries<'a> {
+    fn read_entry(&self) -> Result<types::TbfTlvEntry<'a>, types::TbfParseError> {
+        let tlv = self
+            .header
+            .get(self.offset..self.offset + 4)
+            .ok_or(types::TbfParseError::NotEnoughFlash)?;
+        let tipe = u16::from_le_bytes([tlv[0], tlv[1]]);
+        let length = u16::from_le_bytes([tlv[2], tlv[3]]) as usize;
+
+        let value_start = self.offset + 4;
+        let value = self
+            .header
+            .get(value_start..value_start + length)
+            .ok_or(types::TbfParseError::NotEnoughFlash)?;
+
+        Ok(types::TbfTlvEntry {
+            tipe,
+            offset: self.offset,
+            value,
+        })
+    }
+}
+
+impl<'a> Iterator for TbfTlvEntries<'a> {
+    type Item = Result<types::TbfTlvEntry<'a>, types::TbfParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.offset >= self.header.len() {
+            return None;
+        }
+
+        let entry = self.read_entry();
+        match &entry {
+            // All TLV blocks are padded to 4 bytes.
+            Ok(tlv) => self.offset += 4 + align4!(tlv.value.len()),
+            // Do not try to make sense of anything after a broken entry.
+            Err(_) => self.offset = self.header.len(),
+        }
+
+        Some(entry)
+    }
+}
+
 pub fn parse_tbf_footer(
     footers: &[u8],
 ) -> Result<(types::TbfFooterV2Credentials, u32), types::TbfParseError> {
@@ -330,6 +476,298 @@ pub fn parse_tbf_footer(
             let length = tlv_header.length;
             Ok((credential, length as u32))
         }
-        _ => Err(types::TbfParseError::BadTlvEntry(tlv_header.tipe as usize)),
+        _ => Err(types::TbfParseError::BadTlvEntry(
+            tlv_header.tipe as usize,
+            0,
+        )),
+    }
+}
+
+/// A TBF object split into its parts, as returned by `parse_tbf()`.
+#[derive(Clone, Debug)]
+pub struct TbfObject<'a, H = types::TbfHeader> {
+    /// The whole TBF object, `total_size` bytes long.
+    pub data: &'a [u8],
+    pub header: H,
+    /// The raw TBF header.
+    pub header_data: &'a [u8],
+    /// The application binary, after the header and the protected trailer.
+    /// Empty for padding.
+    pub binary: &'a [u8],
+    /// The credentials footers, after the binary.
+    pub footers: TbfFooters<'a>,
+    /// Whatever follows the TBF object in the input, such as the next app.
+    pub trailing: &'a [u8],
+}
+
+/// Iterator over the credentials footers of a TBF object. See `parse_tbf()`.
+#[derive(Clone, Debug)]
+pub struct TbfFooters<'a> {
+    footers: &'a [u8],
+    /// Offset of `footers` from the start of the TBF object.
+    start: usize,
+    offset: usize,
+}
+
+impl Iterator for TbfFooters<'_> {
+    type Item = types::TbfFooterEntry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // The footers were checked by `parse_tbf()`, so this only stops at
+        // the end.
+        let (credentials, length) = parse_tbf_footer(self.footers.get(self.offset..)?).ok()?;
+        let entry = types::TbfFooterEntry {
+            offset: self.start + self.offset,
+            credentials,
+            length,
+        };
+        self.offset += 4 + length as usize;
+        Some(entry)
+    }
+}
+
+/// Headers that `parse_tbf()` and `TbfList` can split TBF objects with.
+pub trait TbfBinaryRange {
+    /// The start and end offsets of the binary, or `None` if the TBF object
+    /// has no binary. Fails if the start does not fit in a `u32`.
+    fn binary_range(&self) -> Result<Option<(u32, u32)>, types::TbfParseError>;
+}
+
+/// Offsets of the binary of an app whose header is `header_size` bytes long,
+/// checking that the protected trailer does not overflow the start.
+pub(crate) fn checked_binary_range(
+    header_size: u16,
+    protected_trailer_size: u32,
+    binary_end: u32,
+) -> Result<Option<(u32, u32)>, types::TbfParseError> {
+    let start = (header_size as u32)
+        .checked_add(protected_trailer_size)
+        .ok_or(types::TbfParseError::BadBinaryRange(u32::MAX, binary_end))?;
+    Ok(Some((start, binary_end)))
+}
+
+impl TbfBinaryRange for types::TbfHeader {
+    fn binary_range(&self) -> Result<Option<(u32, u32)>, types::TbfParseError> {
+        let types::TbfHeader::TbfHeaderV2(hd) = self else {
+            return Ok(None);
+        };
+        let protected_trailer_size = match (hd.program, hd.main) {
+            (Some(p), _) => p.protected_trailer_size,
+            (None, Some(m)) => m.protected_trailer_size,
+            (None, None) => return Ok(None),
+        };
+        checked_binary_range(
+            hd.base.header_size,
+            protected_trailer_size,
+            self.get_binary_end(),
+        )
+    }
+}
+
+/// Parse a whole TBF object: its header, binary and footers.
+///
+/// `data` must start with the TBF object, and may continue past its end.
+/// Unlike parsing the parts separately, this checks that the binary and every
+/// footer fit in the object. TBF objects with a Main header, and padding, have
+/// no footers.
+pub fn parse_tbf(data: &[u8]) -> Result<TbfObject<'_>, types::TbfParseError> {
+    parse_tbf_object(data, parse_tbf_header)
+}
+
+/// Implementation of `parse_tbf()` for any kind of parsed header.
+pub(crate) fn parse_tbf_object<'a, H: TbfBinaryRange>(
+    data: &'a [u8],
+    parse_header: impl FnOnce(&[u8], u16) -> Result<H, types::TbfParseError>,
+) -> Result<TbfObject<'a, H>, types::TbfParseError> {
+    let lengths: &[u8; 8] = data
+        .get(0..8)
+        .ok_or(types::TbfParseError::NotEnoughFlash)?
+        .try_into()?;
+    let (version, header_size, total_size) = match parse_tbf_header_lengths(lengths) {
+        Ok(lengths) => lengths,
+        Err(types::InitialTbfParseError::UnableToParse) => {
+            return Err(types::TbfParseError::UnsupportedVersion(
+                u16::from_le_bytes([lengths[0], lengths[1]]),
+            ))
+        }
+        Err(types::InitialTbfParseError::InvalidHeader(_)) => {
+            return Err(types::TbfParseError::BadHeaderSize(u16::from_le_bytes([
+                lengths[2], lengths[3],
+            ])))
+        }
+    };
+
+    let object = data
+        .get(0..total_size as usize)
+        .ok_or(types::TbfParseError::NotEnoughFlash)?;
+    let trailing = &data[total_size as usize..];
+    // `parse_tbf_header_lengths()` checked that the header fits.
+    let header_data = &object[0..header_size as usize];
+    let header = parse_header(header_data, version)?;
+
+    let binary_range = header.binary_range()?;
+    let (binary_start, binary_end) = binary_range.unwrap_or((header_size as u32, total_size));
+    if binary_start < header_size as u32 || binary_start > binary_end || binary_end > total_size {
+        return Err(types::TbfParseError::BadBinaryRange(
+            binary_start,
+            binary_end,
+        ));
+    }
+    let binary = if binary_range.is_some() {
+        &object[binary_start as usize..binary_end as usize]
+    } else {
+        &[]
+    };
+
+    // Check every footer before handing out the iterator.
+    let footers = &object[binary_end as usize..];
+    let mut offset = 0;
+    while offset < footers.len() {
+        let footer = &footers[offset..];
+        let length = footer
+            .get(2..4)
+            .map(|length| u16::from_le_bytes([length[0], length[1]]) as usize);
+        match length {
+            Some(length) if 4 + length <= footer.len() => {
+                parse_tbf_footer(&footer[..4 + length])?;
+                offset += 4 + length;
+            }
+            _ => {
+                return Err(types::TbfParseError::BadFooter(
+                    binary_end as usize + offset,
+                ))
+            }
+        }
+    }
+
+    Ok(TbfObject {
+        data: object,
+        header,
+        header_data,
+        binary,
+        footers: TbfFooters {
+            footers,
+            start: binary_end as usize,
+            offset: 0,
+        },
+        trailing,
+    })
+}
+
+/// What the app linked list holds at some address, as returned by
+/// `TbfList`.
+#[derive(Clone, Debug)]
+pub enum TbfListEntry<'a, H = types::TbfHeader> {
+    /// A TBF object with a Main or Program header.
+    App {
+        address: u64,
+        object: TbfObject<'a, H>,
+    },
+    /// A TBF object without an app, which only fills space between apps.
+    Padding {
+        address: u64,
+        object: TbfObject<'a, H>,
+    },
+    /// A TBF object that could not be parsed. Its total size is trusted, so
+    /// the list continues after it.
+    Invalid {
+        address: u64,
+        size: u32,
+        error: types::TbfParseError,
+    },
+    /// The buffer ends before the TBF object at `address` does. The list can
+    /// be walked further with the first `needed` bytes at `address`.
+    Truncated { address: u64, needed: u32 },
+    /// The list ends at `address`, for example because the flash is erased.
+    End { address: u64 },
+}
+
+/// Iterator over the app linked list in a flash buffer. See `parse_tbf_list()`.
+///
+/// The last entry is always either `End` or `Truncated`, unless the iterator
+/// is dropped early.
+pub struct TbfList<'a, H = types::TbfHeader> {
+    data: &'a [u8],
+    address: u64,
+    offset: usize,
+    parse: fn(&'a [u8]) -> Result<TbfObject<'a, H>, types::TbfParseError>,
+    done: bool,
+}
+
+/// Walk the app linked list stored in `data`, whose first byte is at
+/// `address` in flash.
+pub fn parse_tbf_list(data: &[u8], address: u64) -> TbfList<'_> {
+    TbfList::new(data, address, parse_tbf)
+}
+
+impl<'a, H: TbfBinaryRange> TbfList<'a, H> {
+    pub(crate) fn new(
+        data: &'a [u8],
+        address: u64,
+        parse: fn(&'a [u8]) -> Result<TbfObject<'a, H>, types::TbfParseError>,
+    ) -> Self {
+        TbfList {
+            data,
+            address,
+            offset: 0,
+            parse,
+            done: false,
+        }
+    }
+
+    fn read_entry(&self) -> TbfListEntry<'a, H> {
+        let address = self.address + self.offset as u64;
+        let remaining = &self.data[self.offset..];
+        let Some(lengths) = remaining.get(0..8) else {
+            return TbfListEntry::Truncated { address, needed: 8 };
+        };
+        let total_size = match parse_tbf_header_lengths(
+            lengths
+                .try_into()
+                .expect("Buffer length must be at least 8 bytes long."),
+        ) {
+            Ok((_, _, total_size)) => total_size,
+            // Sizes that do not even cover the lengths cannot be skipped.
+            Err(types::InitialTbfParseError::InvalidHeader(size)) if size >= 8 => size,
+            Err(_) => return TbfListEntry::End { address },
+        };
+        if remaining.len() < total_size as usize {
+            return TbfListEntry::Truncated {
+                address,
+                needed: total_size,
+            };
+        }
+
+        match (self.parse)(remaining) {
+            Ok(object) if matches!(object.header.binary_range(), Ok(Some(_))) => {
+                TbfListEntry::App { address, object }
+            }
+            Ok(object) => TbfListEntry::Padding { address, object },
+            Err(error) => TbfListEntry::Invalid {
+                address,
+                size: total_size,
+                error,
+            },
+        }
+    }
+}
+
+impl<'a, H: TbfBinaryRange> Iterator for TbfList<'a, H> {
+    type Item = TbfListEntry<'a, H>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let entry = self.read_entry();
+        match &entry {
+            TbfListEntry::App { object, .. } | TbfListEntry::Padding { object, .. } => {
+                self.offset += object.data.len();
+            }
+            TbfListEntry::Invalid { size, .. } => self.offset += *size as usize,
+            TbfListEntry::Truncated { .. } | TbfListEntry::End { .. } => self.done = true,
+        }
+        Some(entry)
     }
 }
@@ -13,10 +13,14 @@ use core::{fmt, str};
 
 /// We only support up to a fixed number of storage permissions for each of read
 /// and modify. This simplification enables us to use fixed sized buffers.
-const NUM_STORAGE_PERMISSIONS: usize = 8;
+pub(crate) const NUM_STORAGE_PERMISSIONS: usize = 8;
+
+/// Number of unknown TLV entries whose location is kept in a parsed header.
+pub const NUM_UNKNOWN_TLVS: usize = 8;
 
 /// Error when parsing just the beginning of the TBF header. This is only used
 /// when establishing the linked list structure of apps installed in flash.
+#[derive(Clone, Copy, Debug)]
 pub enum InitialTbfParseError {
     /// We were unable to parse the beginning of the header. This either means
     /// we ran out of flash, or the trusted values are invalid meaning this is
@@ -31,6 +35,20 @@ pub enum InitialTbfParseError {
     InvalidHeader(u32),
 }
 
+impl fmt::Display for InitialTbfParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InitialTbfParseError::UnableToParse => write!(f, "No TBF header found"),
+            InitialTbfParseError::InvalidHeader(total_size) => {
+                write!(f, "Invalid TBF header in an object of {total_size} bytes")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InitialTbfParseError {}
+
 impl From<core::array::TryFromSliceError> for InitialTbfParseError {
     // Convert a slice to a parsed type. Since we control how long we make our
     // slices, this conversion should never fail. If it does, then this is a bug
@@ -41,6 +59,7 @@ impl From<core::array::TryFromSliceError> for InitialTbfParseError {
 }
 
 /// Error when parsing an app's TBF header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum TbfParseError {
     /// Not enough bytes in the buffer to parse the expected field.
     NotEnoughFlash,
@@ -54,13 +73,14 @@ pub enum TbfParseError {
     ChecksumMismatch(u32, u32),
 
     /// One of the TLV entries did not parse correctly. This could happen if the
-    /// TLV.length does not match the size of a fixed-length entry. The `usize`
-    /// is the value of the "tipe" field.
-    BadTlvEntry(usize),
+    /// TLV.length does not match the size of a fixed-length entry. The first
+    /// value is the value of the "tipe" field, the second value is the offset
+    /// of the entry in the header (0 when parsing a single entry).
+    BadTlvEntry(usize, usize),
 
     /// The app name in the TBF header could not be successfully parsed as a
-    /// UTF-8 string.
-    BadProcessName,
+    /// UTF-8 string. The `usize` is the offset of the entry in the header.
+    BadProcessName(usize),
 
     /// Internal kernel error. This is a bug inside of this library. Likely this
     /// means that for some reason a slice was not sized properly for parsing a
@@ -78,6 +98,18 @@ pub enum TbfParseError {
     /// The package name is too long for Tock to parse.
     /// Consider a shorter name, or increasing the maximum size.
     PackageNameTooLong,
+
+    /// The header size is smaller than the required base or larger than the
+    /// whole TBF object. The `u16` is the header size.
+    BadHeaderSize(u16),
+
+    /// The binary does not fit between the header and the end of the TBF
+    /// object. The values are the start and end offsets of the binary.
+    BadBinaryRange(u32, u32),
+
+    /// A credentials footer is truncated or runs past the end of the TBF
+    /// object. The `usize` is the offset of the footer in the object.
+    BadFooter(usize),
 }
 
 impl From<core::array::TryFromSliceError> for TbfParseError {
@@ -89,7 +121,18 @@ impl From<core::array::TryFromSliceError> for TbfParseError {
     }
 }
 
-impl fmt::Debug for TbfParseError {
+impl TbfParseError {
+    /// Locate an error from parsing the TLV entry at `offset` in the header.
+    pub(crate) fn in_tlv(self, offset: usize) -> Self {
+        match self {
+            TbfParseError::BadTlvEntry(tipe, _) => TbfParseError::BadTlvEntry(tipe, offset),
+            TbfParseError::BadProcessName(_) => TbfParseError::BadProcessName(offset),
+            error => error,
+        }
+    }
+}
+
+impl fmt::Display for TbfParseError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             TbfParseError::NotEnoughFlash => write!(f, "Buffer too short to parse TBF header"),
@@ -100,8 +143,12 @@ impl fmt::Debug for TbfParseError {
                 f,
                 "Checksum verification failed: app:{app:#x}, calc:{calc:#x}"
             ),
-            TbfParseError::BadTlvEntry(tipe) => write!(f, "TLV entry type {tipe} is invalid"),
-            TbfParseError::BadProcessName => write!(f, "Process name not UTF-8"),
+            TbfParseError::BadTlvEntry(tipe, offset) => {
+                write!(f, "TLV entry type {tipe} at offset {offset:#x} is invalid")
+            }
+            TbfParseError::BadProcessName(offset) => {
+                write!(f, "Process name at offset {offset:#x} not UTF-8")
+            }
             TbfParseError::InternalError => write!(f, "Internal kernel error. This is a bug."),
             TbfParseError::TooManyEntries(tipe) => {
                 write!(
@@ -110,14 +157,26 @@ impl fmt::Debug for TbfParseError {
                 )
             }
             TbfParseError::PackageNameTooLong => write!(f, "The package name is too long."),
+            TbfParseError::BadHeaderSize(size) => write!(f, "Header size {size} is invalid"),
+            TbfParseError::BadBinaryRange(start, end) => write!(
+                f,
+                "Binary range {start:#x}..{end:#x} is outside of the TBF object"
+            ),
+            TbfParseError::BadFooter(offset) => {
+                write!(f, "Footer at offset {offset:#x} is truncated")
+            }
         }
     }
 }
 
+#[cfg(feature = "std")]
+impl std::error::Error for TbfParseError {}
+
 // TBF structure
 
 /// TBF fields that must be present in all v2 headers.
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TbfHeaderV2Base {
     pub(crate) version: u16,
     pub(crate) header_size: u16,
@@ -132,11 +191,13 @@ pub enum TbfHeaderTypes {
     TbfHeaderMain = 1,
     TbfHeaderWriteableFlashRegions = 2,
     TbfHeaderPackageName = 3,
+    TbfHeaderPicOption1 = 4,
     TbfHeaderFixedAddresses = 5,
     TbfHeaderPermissions = 6,
     TbfHeaderStoragePermissions = 7,
     TbfHeaderKernelVersion = 8,
     TbfHeaderProgram = 9,
+    TbfHeaderShortId = 10,
     TbfFooterCredentials = 128,
 
     /// Some field in the header that we do not understand. Since the TLV format
@@ -152,6 +213,28 @@ pub struct TbfTlv {
     pub(crate) length: u16,
 }
 
+/// A TLV entry of a TBF header in its raw form, as returned by
+/// `parse::parse_tbf_header_tlvs()`.
+#[derive(Clone, Copy, Debug)]
+pub struct TbfTlvEntry<'a> {
+    /// The raw "tipe" field. This may be a type this library does not know.
+    pub tipe: u16,
+    /// Offset of the entry from the start of the TBF header.
+    pub offset: usize,
+    /// The value of the entry, without the padding to 4 bytes.
+    pub value: &'a [u8],
+}
+
+/// A credentials footer of a TBF object, as returned by `parse::parse_tbf()`.
+#[derive(Clone, Copy, Debug)]
+pub struct TbfFooterEntry {
+    /// Offset of the footer from the start of the TBF object.
+    pub offset: usize,
+    pub credentials: TbfFooterV2Credentials,
+    /// Length of the footer, without the TLV header.
+    pub length: u32,
+}
+
 /// The v2 Main Header for apps.
 ///
 /// All apps must have either a Main Header or a Program Header. Without
@@ -160,10 +243,11 @@ pub struct TbfTlv {
 /// Headers do not, while Program Headers do. A TBF with a Main Header cannot
 /// have any Credentials Footers, while a TBF with a Program Header can.
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TbfHeaderV2Main {
-    init_fn_offset: u32,
-    protected_trailer_size: u32,
-    minimum_ram_size: u32,
+    pub(crate) init_fn_offset: u32,
+    pub(crate) protected_trailer_size: u32,
+    pub(crate) minimum_ram_size: u32,
 }
 
 /// The v2 Program Header for apps.
@@ -176,18 +260,21 @@ pub struct TbfHeaderV2Main {
 /// start. The region between the end of the binary and the end of the TBF
 /// is reserved for Credentials Footers.
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TbfHeaderV2Program {
-    init_fn_offset: u32,
-    protected_trailer_size: u32,
-    minimum_ram_size: u32,
-    binary_end_offset: u32,
-    version: u32,
+    pub(crate) init_fn_offset: u32,
+    pub(crate) protected_trailer_size: u32,
+    pub(crate) minimum_ram_size: u32,
+    pub(crate) binary_end_offset: u32,
+    pub(crate) version: u32,
 }
 
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TbfHeaderV2PackageName<const L: usize> {
-    size: u32,
-    buffer: [u8; L],
+    pub(crate) size: u32,
+    #[cfg_attr(feature = "serde", serde(with = "serde_big_array::BigArray"))]
+    pub(crate) buffer: [u8; L],
 }
 
 /// Writeable flash regions only need an offset and size.
@@ -195,9 +282,70 @@ pub struct TbfHeaderV2PackageName<const L: usize> {
 /// There can be multiple (or zero) flash regions defined, so this is its own
 /// struct.
 #[derive(Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TbfHeaderV2WriteableFlashRegion {
-    writeable_flash_region_offset: u32,
-    writeable_flash_region_size: u32,
+    pub(crate) writeable_flash_region_offset: u32,
+    pub(crate) writeable_flash_region_size: u32,
+}
+
+/// Relocation information for position independent apps, as described by the
+/// "PIC Option 1" TLV. All offsets are relative to the start of the binary.
+#[derive(Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TbfHeaderV2PicOption1 {
+    text_offset: u32,
+    data_offset: u32,
+    data_size: u32,
+    bss_memory_offset: u32,
+    bss_size: u32,
+    relocation_data_offset: u32,
+    relocation_data_size: u32,
+    got_offset: u32,
+    got_size: u32,
+    minimum_stack_length: u32,
+}
+
+impl TbfHeaderV2PicOption1 {
+    pub fn get_text_offset(&self) -> u32 {
+        self.text_offset
+    }
+
+    pub fn get_data_offset(&self) -> u32 {
+        self.data_offset
+    }
+
+    pub fn get_data_size(&self) -> u32 {
+        self.data_size
+    }
+
+    /// Offset of the `.bss` section in the app memory.
+    pub fn get_bss_memory_offset(&self) -> u32 {
+        self.bss_memory_offset
+    }
+
+    pub fn get_bss_size(&self) -> u32 {
+        self.bss_size
+    }
+
+    pub fn get_relocation_data_offset(&self) -> u32 {
+        self.relocation_data_offset
+    }
+
+    pub fn get_relocation_data_size(&self) -> u32 {
+        self.relocation_data_size
+    }
+
+    pub fn get_got_offset(&self) -> u32 {
+        self.got_offset
+    }
+
+    pub fn get_got_size(&self) -> u32 {
+        self.got_size
+    }
+
+    pub fn get_minimum_stack_length(&self) -> u32 {
+        self.minimum_stack_length
+    }
 }
 
 /// Optional fixed addresses for flash and RAM for this process.
@@ -213,45 +361,103 @@ pub struct TbfHeaderV2WriteableFlashRegion {
 /// up the process. If a process wants to set one fixed address but not the other, the unused one
 /// can be set to 0xFFFFFFFF.
 #[derive(Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TbfHeaderV2FixedAddresses {
     /// The absolute address of the start of RAM that the process expects. For
     /// example, if the process was linked with a RAM region starting at
     /// address `0x00023000`, then this would be set to `0x00023000`.
-    start_process_ram: u32,
+    pub(crate) start_process_ram: u32,
     /// The absolute address of the start of the process binary. This does _not_
     /// include the TBF header. This is the address the process used for the
     /// start of flash with the linker.
-    start_process_flash: u32,
+    pub(crate) start_process_flash: u32,
 }
 
+/// The command permissions an app has for one driver.
 #[derive(Clone, Copy, Debug, Default)]
-struct TbfHeaderDriverPermission {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TbfHeaderDriverPermission {
     driver_number: u32,
     offset: u32,
     allowed_commands: u64,
 }
 
+impl TbfHeaderDriverPermission {
+    pub fn get_driver_number(&self) -> u32 {
+        self.driver_number
+    }
+
+    /// The offset of `allowed_commands`, in multiples of 64 commands.
+    pub fn get_offset(&self) -> u32 {
+        self.offset
+    }
+
+    pub fn get_allowed_commands(&self) -> u64 {
+        self.allowed_commands
+    }
+}
+
 /// A list of permissions for this app
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TbfHeaderV2Permissions<const L: usize> {
-    length: u16,
-    perms: [TbfHeaderDriverPermission; L],
+    pub(crate) length: u16,
+    #[cfg_attr(feature = "serde", serde(with = "serde_big_array::BigArray"))]
+    pub(crate) perms: [TbfHeaderDriverPermission; L],
 }
 
 /// A list of storage (read/write/modify) permissions for this app.
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TbfHeaderV2StoragePermissions<const L: usize> {
-    write_id: Option<core::num::NonZeroU32>,
-    read_length: u16,
-    read_ids: [u32; L],
-    modify_length: u16,
-    modify_ids: [u32; L],
+    pub(crate) write_id: Option<core::num::NonZeroU32>,
+    pub(crate) read_length: u16,
+    #[cfg_attr(feature = "serde", serde(with = "serde_big_array::BigArray"))]
+    pub(crate) read_ids: [u32; L],
+    pub(crate) modify_length: u16,
+    #[cfg_attr(feature = "serde", serde(with = "serde_big_array::BigArray"))]
+    pub(crate) modify_ids: [u32; L],
 }
 
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TbfHeaderV2KernelVersion {
-    major: u16,
-    minor: u16,
+    pub(crate) major: u16,
+    pub(crate) minor: u16,
+}
+
+/// The ShortId the kernel should use for this process. A ShortId of 0 means
+/// the app asks for a locally unique one instead.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TbfHeaderV2ShortId {
+    pub(crate) short_id: Option<core::num::NonZeroU32>,
+}
+
+/// Location of a TLV entry the parser does not understand, so that tools can
+/// still show and preserve it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TbfHeaderV2UnknownTlv {
+    pub(crate) tipe: u16,
+    pub(crate) offset: u16,
+    pub(crate) length: u16,
+}
+
+impl TbfHeaderV2UnknownTlv {
+    pub fn get_type(&self) -> u16 {
+        self.tipe
+    }
+
+    /// Offset of the TLV entry from the start of the TBF header.
+    pub fn get_offset(&self) -> u16 {
+        self.offset
+    }
+
+    /// Length of the value, without the TLV header and the padding.
+    pub fn get_length(&self) -> u16 {
+        self.length
+    }
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -266,6 +472,7 @@ pub enum TbfFooterV2CredentialsType {
 
 /// Reference: https://github.com/tock/tock/blob/master/doc/reference/trd-appid.md#52-credentials-footer
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(clippy::large_enum_variant)]
 pub enum TbfFooterV2Credentials {
     Reserved(u32),
@@ -277,13 +484,18 @@ pub enum TbfFooterV2Credentials {
 }
 
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TbfFooterV2SHA<const L: usize> {
+    #[cfg_attr(feature = "serde", serde(with = "serde_big_array::BigArray"))]
     hash: [u8; L],
 }
 
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TbfFooterV2RSA<const L: usize> {
+    #[cfg_attr(feature = "serde", serde(with = "serde_big_array::BigArray"))]
     public_key: [u8; L],
+    #[cfg_attr(feature = "serde", serde(with = "serde_big_array::BigArray"))]
     signature: [u8; L],
 }
 
@@ -320,6 +532,140 @@ impl<const L: usize> TbfFooterV2RSA<L> {
     }
 }
 
+// Constructors and conversion functions from the TBF fields to the bytes
+// stored in flash. These are used when creating headers.
+
+impl TbfHeaderV2Main {
+    /// `init_fn_offset` is relative to the end of the TBF header.
+    pub fn new(init_fn_offset: u32, protected_trailer_size: u32, minimum_ram_size: u32) -> Self {
+        TbfHeaderV2Main {
+            init_fn_offset,
+            protected_trailer_size,
+            minimum_ram_size,
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; 12] {
+        let mut b = [0; 12];
+        b[0..4].copy_from_slice(&self.init_fn_offset.to_le_bytes());
+        b[4..8].copy_from_slice(&self.protected_trailer_size.to_le_bytes());
+        b[8..12].copy_from_slice(&self.minimum_ram_size.to_le_bytes());
+        b
+    }
+}
+
+impl TbfHeaderV2Program {
+    /// `init_fn_offset` is relative to the end of the TBF header, while
+    /// `binary_end_offset` is relative to its start.
+    pub fn new(
+        init_fn_offset: u32,
+        protected_trailer_size: u32,
+        minimum_ram_size: u32,
+        binary_end_offset: u32,
+        version: u32,
+    ) -> Self {
+        TbfHeaderV2Program {
+            init_fn_offset,
+            protected_trailer_size,
+            minimum_ram_size,
+            binary_end_offset,
+            version,
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; 20] {
+        let mut b = [0; 20];
+        b[0..4].copy_from_slice(&self.init_fn_offset.to_le_bytes());
+        b[4..8].copy_from_slice(&self.protected_trailer_size.to_le_bytes());
+        b[8..12].copy_from_slice(&self.minimum_ram_size.to_le_bytes());
+        b[12..16].copy_from_slice(&self.binary_end_offset.to_le_bytes());
+        b[16..20].copy_from_slice(&self.version.to_le_bytes());
+        b
+    }
+}
+
+impl TbfHeaderV2WriteableFlashRegion {
+    pub fn new(offset: u32, size: u32) -> Self {
+        TbfHeaderV2WriteableFlashRegion {
+            writeable_flash_region_offset: offset,
+            writeable_flash_region_size: size,
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; 8] {
+        let mut b = [0; 8];
+        b[0..4].copy_from_slice(&self.writeable_flash_region_offset.to_le_bytes());
+        b[4..8].copy_from_slice(&self.writeable_flash_region_size.to_le_bytes());
+        b
+    }
+}
+
+impl TbfHeaderV2FixedAddresses {
+    /// Use `0xFFFFFFFF` for an address the process does not depend on.
+    pub fn new(start_process_ram: u32, start_process_flash: u32) -> Self {
+        TbfHeaderV2FixedAddresses {
+            start_process_ram,
+            start_process_flash,
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; 8] {
+        let mut b = [0; 8];
+        b[0..4].copy_from_slice(&self.start_process_ram.to_le_bytes());
+        b[4..8].copy_from_slice(&self.start_process_flash.to_le_bytes());
+        b
+    }
+}
+
+impl TbfHeaderDriverPermission {
+    /// Allow the commands set in `allowed_commands`, bit `n` being command
+    /// `offset * 64 + n` of the driver.
+    pub fn new(driver_number: u32, offset: u32, allowed_commands: u64) -> Self {
+        TbfHeaderDriverPermission {
+            driver_number,
+            offset,
+            allowed_commands,
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; 16] {
+        let mut b = [0; 16];
+        b[0..4].copy_from_slice(&self.driver_number.to_le_bytes());
+        b[4..8].copy_from_slice(&self.offset.to_le_bytes());
+        b[8..16].copy_from_slice(&self.allowed_commands.to_le_bytes());
+        b
+    }
+}
+
+impl TbfHeaderV2KernelVersion {
+    pub fn new(major: u16, minor: u16) -> Self {
+        TbfHeaderV2KernelVersion { major, minor }
+    }
+
+    pub fn to_bytes(&self) -> [u8; 4] {
+        let mut b = [0; 4];
+        b[0..2].copy_from_slice(&self.major.to_le_bytes());
+        b[2..4].copy_from_slice(&self.minor.to_le_bytes());
+        b
+    }
+}
+
+impl<const L: usize> TbfFooterV2SHA<L> {
+    pub fn new(hash: [u8; L]) -> Self {
+        TbfFooterV2SHA { hash }
+    }
+}
+
+impl<const L: usize> TbfFooterV2RSA<L> {
+    /// Both the public key (the modulus) and the signature are big-endian.
+    pub fn new(public_key: [u8; L], signature: [u8; L]) -> Self {
+        TbfFooterV2RSA {
+            public_key,
+            signature,
+        }
+    }
+}
+
 // Conversion functions from slices to the various TBF fields.
 
 impl core::convert::TryFrom<&[u8]> for TbfHeaderV2Base {
@@ -367,11 +713,13 @@ impl core::convert::TryFrom<u16> for TbfHeaderTypes {
             1 => Ok(TbfHeaderTypes::TbfHeaderMain),
             2 => Ok(TbfHeaderTypes::TbfHeaderWriteableFlashRegions),
             3 => Ok(TbfHeaderTypes::TbfHeaderPackageName),
+            4 => Ok(TbfHeaderTypes::TbfHeaderPicOption1),
             5 => Ok(TbfHeaderTypes::TbfHeaderFixedAddresses),
             6 => Ok(TbfHeaderTypes::TbfHeaderPermissions),
             7 => Ok(TbfHeaderTypes::TbfHeaderStoragePermissions),
             8 => Ok(TbfHeaderTypes::TbfHeaderKernelVersion),
             9 => Ok(TbfHeaderTypes::TbfHeaderProgram),
+            10 => Ok(TbfHeaderTypes::TbfHeaderShortId),
             128 => Ok(TbfHeaderTypes::TbfFooterCredentials),
             _ => Ok(TbfHeaderTypes::Unknown),
         }
@@ -472,7 +820,7 @@ impl<const L: usize> core::convert::TryFrom<&[u8]> for TbfHeaderV2PackageName<L>
         }
 
         if str::from_utf8(value).is_err() {
-            return Err(TbfParseError::BadProcessName);
+            return Err(TbfParseError::BadProcessName(0));
         }
 
         let mut buffer = [0u8; L];
@@ -577,6 +925,7 @@ impl<const L: usize> core::convert::TryFrom<&[u8]> for TbfHeaderV2Permissions<L>
             } else {
                 return Err(TbfParseError::BadTlvEntry(
                     TbfHeaderTypes::TbfHeaderPermissions as usize,
+                    0,
                 ));
             }
         }
@@ -619,6 +968,7 @@ impl<const L: usize> core::convert::TryFrom<&[u8]> for TbfHeaderV2StoragePermiss
             } else {
                 return Err(TbfParseError::BadTlvEntry(
                     TbfHeaderTypes::TbfHeaderStoragePermissions as usize,
+                    0,
                 ));
             }
         }
@@ -642,6 +992,7 @@ impl<const L: usize> core::convert::TryFrom<&[u8]> for TbfHeaderV2StoragePermiss
             } else {
                 return Err(TbfParseError::BadTlvEntry(
                     TbfHeaderTypes::TbfHeaderStoragePermissions as usize,
+                    0,
                 ));
             }
         }
@@ -675,6 +1026,46 @@ impl core::convert::TryFrom<&[u8]> for TbfHeaderV2KernelVersion {
     }
 }
 
+impl core::convert::TryFrom<&[u8]> for TbfHeaderV2PicOption1 {
+    type Error = TbfParseError;
+
+    fn try_from(b: &[u8]) -> Result<TbfHeaderV2PicOption1, Self::Error> {
+        let word = |index: usize| -> Result<u32, TbfParseError> {
+            Ok(u32::from_le_bytes(
+                b.get(index * 4..(index + 1) * 4)
+                    .ok_or(TbfParseError::InternalError)?
+                    .try_into()?,
+            ))
+        };
+        Ok(TbfHeaderV2PicOption1 {
+            text_offset: word(0)?,
+            data_offset: word(1)?,
+            data_size: word(2)?,
+            bss_memory_offset: word(3)?,
+            bss_size: word(4)?,
+            relocation_data_offset: word(5)?,
+            relocation_data_size: word(6)?,
+            got_offset: word(7)?,
+            got_size: word(8)?,
+            minimum_stack_length: word(9)?,
+        })
+    }
+}
+
+impl core::convert::TryFrom<&[u8]> for TbfHeaderV2ShortId {
+    type Error = TbfParseError;
+
+    fn try_from(b: &[u8]) -> Result<TbfHeaderV2ShortId, Self::Error> {
+        Ok(TbfHeaderV2ShortId {
+            short_id: core::num::NonZeroU32::new(u32::from_le_bytes(
+                b.get(0..4)
+                    .ok_or(TbfParseError::InternalError)?
+                    .try_into()?,
+            )),
+        })
+    }
+}
+
 impl core::convert::TryFrom<&[u8]> for TbfFooterV2Credentials {
     type Error = TbfParseError;
 
@@ -762,6 +1153,17 @@ impl TbfFooterV2Credentials {
             TbfFooterV2Credentials::SHA512(_) => "SHA512",
         }
     }
+
+    pub fn get_format(&self) -> TbfFooterV2CredentialsType {
+        match self {
+            TbfFooterV2Credentials::Reserved(_) => TbfFooterV2CredentialsType::Reserved,
+            TbfFooterV2Credentials::Rsa3072Key(_) => TbfFooterV2CredentialsType::Rsa3072Key,
+            TbfFooterV2Credentials::Rsa4096Key(_) => TbfFooterV2CredentialsType::Rsa4096Key,
+            TbfFooterV2Credentials::SHA256(_) => TbfFooterV2CredentialsType::SHA256,
+            TbfFooterV2Credentials::SHA384(_) => TbfFooterV2CredentialsType::SHA384,
+            TbfFooterV2Credentials::SHA512(_) => TbfFooterV2CredentialsType::SHA512,
+        }
+    }
 }
 
 /// The command permissions specified by the TBF header.
@@ -784,6 +1186,7 @@ pub enum CommandPermissions {
 /// four since we need to statically know the length of the array to store in
 /// this type.
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TbfHeaderV2 {
     pub(crate) base: TbfHeaderV2Base,
     pub(crate) main: Option<TbfHeaderV2Main>,
@@ -794,6 +1197,9 @@ pub struct TbfHeaderV2 {
     pub(crate) permissions: Option<TbfHeaderV2Permissions<8>>,
     pub(crate) storage_permissions: Option<TbfHeaderV2StoragePermissions<NUM_STORAGE_PERMISSIONS>>,
     pub(crate) kernel_version: Option<TbfHeaderV2KernelVersion>,
+    pub(crate) pic_option1: Option<TbfHeaderV2PicOption1>,
+    pub(crate) short_id: Option<TbfHeaderV2ShortId>,
+    pub(crate) unknown_tlvs: [Option<TbfHeaderV2UnknownTlv>; NUM_UNKNOWN_TLVS],
 }
 
 /// Type that represents the fields of the Tock Binary Format header.
@@ -803,6 +1209,7 @@ pub struct TbfHeaderV2 {
 /// The kernel can also use this header to keep persistent state about
 /// the application.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 // Clippy suggests we box TbfHeaderV2. We can't really do that, since
 // we are runnning under no_std, and I don't think it's that big of a issue.
 #[allow(clippy::large_enum_variant)]
@@ -901,11 +1308,11 @@ impl TbfHeader {
             TbfHeader::TbfHeaderV2(hd) => {
                 if hd.program.is_some() {
                     hd.program.map_or(0, |p| {
-                        (hd.base.header_size as u32) + p.protected_trailer_size
+                        (hd.base.header_size as u32).saturating_add(p.protected_trailer_size)
                     })
                 } else if hd.main.is_some() {
                     hd.main.map_or(0, |m| {
-                        (hd.base.header_size as u32) + m.protected_trailer_size
+                        (hd.base.header_size as u32).saturating_add(m.protected_trailer_size)
                     })
                 } else {
                     0
@@ -930,11 +1337,13 @@ impl TbfHeader {
         match *self {
             TbfHeader::TbfHeaderV2(hd) => {
                 if hd.program.is_some() {
-                    hd.program
-                        .map_or(0, |p| p.init_fn_offset + (hd.base.header_size as u32))
+                    hd.program.map_or(0, |p| {
+                        p.init_fn_offset.saturating_add(hd.base.header_size as u32)
+                    })
                 } else if hd.main.is_some() {
-                    hd.main
-                        .map_or(0, |m| m.init_fn_offset + (hd.base.header_size as u32))
+                    hd.main.map_or(0, |m| {
+                        m.init_fn_offset.saturating_add(hd.base.header_size as u32)
+                    })
                 } else {
                     0
                 }
@@ -1047,6 +1456,18 @@ impl TbfHeader {
         }
     }
 
+    /// Get every driver permission entry of the app.
+    /// Returns `None` if the permissions header is not included.
+    pub fn get_all_command_permissions(&self) -> Option<&[TbfHeaderDriverPermission]> {
+        match self {
+            TbfHeader::TbfHeaderV2(hd) => hd
+                .permissions
+                .as_ref()
+                .map(|permissions| &permissions.perms[..permissions.length as usize]),
+            _ => None,
+        }
+    }
+
     /// Get the process `write_id`.
     ///
     /// Returns `None` if a `write_id` is not included. This indicates the TBF
@@ -1094,6 +1515,35 @@ impl TbfHeader {
         }
     }
 
+    /// Get the fixed ShortId this process requires.
+    /// Returns `None` if the ShortId header is not included or asks for a
+    /// locally unique ShortId.
+    pub fn get_fixed_short_id(&self) -> Option<core::num::NonZeroU32> {
+        match self {
+            TbfHeader::TbfHeaderV2(hd) => hd.short_id.and_then(|short_id| short_id.short_id),
+            _ => None,
+        }
+    }
+
+    /// Get the relocation information of the PIC Option 1 header.
+    /// Returns `None` if the header is not included.
+    pub fn get_pic_option1(&self) -> Option<&TbfHeaderV2PicOption1> {
+        match self {
+            TbfHeader::TbfHeaderV2(hd) => hd.pic_option1.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Get the TLV entries the parser did not understand, in the order they
+    /// are stored. Only the first `NUM_UNKNOWN_TLVS` entries are kept.
+    pub fn get_unknown_tlvs(&self) -> impl Iterator<Item = &TbfHeaderV2UnknownTlv> {
+        let tlvs: &[Option<TbfHeaderV2UnknownTlv>] = match self {
+            TbfHeader::TbfHeaderV2(hd) => &hd.unknown_tlvs,
+            _ => &[],
+        };
+        tlvs.iter().map_while(Option::as_ref)
+    }
+
     /// Return the offset where the binary ends in the TBF or 0 if there
     /// is no binary. If there is a Main header the end offset is the size
     /// of the TBF, while if there is a Program header it can be smaller.
@@ -0,0 +1,78 @@
+// Licensed under the Apache License, Version 2.0 or the MIT License.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+// Copyright OXIDOS AUTOMOTIVE 2024.
+
+//! Verification of TBF credentials footers.
+//!
+//! As defined by the AppID TRD, credentials cover the integrity region of a
+//! TBF object: the TBF header and the application binary, that is everything
+//! before the binary end offset. Footers are not part of it.
+//! Reference: https://github.com/tock/tock/blob/master/doc/reference/trd-appid.md
+
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+use crate::types::{TbfFooterV2Credentials, TbfHeader};
+
+/// Outcome of checking one credentials footer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CredentialsCheck {
+    /// The footer matches the integrity region.
+    Valid,
+    /// The footer does not match the integrity region.
+    Invalid,
+    /// The footer is not checked by this library, like reserved space or
+    /// signatures.
+    Unchecked,
+}
+
+impl CredentialsCheck {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CredentialsCheck::Valid => "valid",
+            CredentialsCheck::Invalid => "invalid",
+            CredentialsCheck::Unchecked => "unchecked",
+        }
+    }
+}
+
+/// Get the integrity region of the TBF object `tbf`, whose header is
+/// `header`. Returns `None` if `tbf` is too short.
+pub fn integrity_region<'a>(tbf: &'a [u8], header: &TbfHeader) -> Option<&'a [u8]> {
+    tbf.get(0..header.get_binary_end() as usize)
+}
+
+/// Whether `check_credentials()` can check this kind of footer.
+pub fn is_checkable(credentials: &TbfFooterV2Credentials) -> bool {
+    matches!(
+        credentials,
+        TbfFooterV2Credentials::SHA256(_)
+            | TbfFooterV2Credentials::SHA384(_)
+            | TbfFooterV2Credentials::SHA512(_)
+    )
+}
+
+/// Check a credentials footer against the integrity region of its TBF
+/// object.
+pub fn check_credentials(
+    integrity_region: &[u8],
+    credentials: &TbfFooterV2Credentials,
+) -> CredentialsCheck {
+    let valid = match credentials {
+        TbfFooterV2Credentials::SHA256(footer) => {
+            Sha256::digest(integrity_region).as_slice() == footer.get_hash()
+        }
+        TbfFooterV2Credentials::SHA384(footer) => {
+            Sha384::digest(integrity_region).as_slice() == footer.get_hash()
+        }
+        TbfFooterV2Credentials::SHA512(footer) => {
+            Sha512::digest(integrity_region).as_slice() == footer.get_hash()
+        }
+        _ => return CredentialsCheck::Unchecked,
+    };
+
+    if valid {
+        CredentialsCheck::Valid
+    } else {
+        CredentialsCheck::Invalid
+    }
+}
@@ -0,0 +1,353 @@
+#![cfg(feature = "std")]
+
+use tbf_parser::builder::{
+    build_padding, encode_footer, reserve_footer_space, to_program_header, write_footer,
+    TbfBuildError, TbfHeaderBuilder, TbfHeaderEditor,
+};
+use tbf_parser::parse::*;
+use tbf_parser::types::{
+    TbfFooterV2Credentials, TbfFooterV2CredentialsType, TbfFooterV2RSA, TbfFooterV2SHA,
+    TbfHeaderDriverPermission, TbfHeaderV2FixedAddresses, TbfHeaderV2KernelVersion, TbfParseError,
+};
+
+/// Recompute the checksum of an edited header.
+fn fix_checksum(header: &mut [u8]) {
+    let checksum = header
+        .chunks_exact(4)
+        .enumerate()
+        .filter(|(i, _)| *i != 3)
+        .fold(0, |checksum, (_, word)| {
+            checksum ^ u32::from_le_bytes(word.try_into().unwrap())
+        });
+    header[12..16].copy_from_slice(&checksum.to_le_bytes());
+}
+
+#[test]
+fn program_header_round_trip() {
+    let mut builder = TbfHeaderBuilder::new();
+    builder.set_package_name("blink").unwrap();
+    builder.set_init_fn_offset(0x41);
+    builder.set_minimum_ram_size(4848);
+    builder.set_kernel_version(Some(TbfHeaderV2KernelVersion::new(2, 1)));
+    builder
+        .add_permission(TbfHeaderDriverPermission::new(0x2, 0, 0b11))
+        .unwrap();
+    builder.set_fixed_addresses(Some(TbfHeaderV2FixedAddresses::new(
+        0x2000_8000,
+        0x0004_0080,
+    )));
+
+    let binary = [0xAA; 100];
+    let tbf = builder.build(&binary, 12, 1024).unwrap();
+    assert_eq!(tbf.len(), 1024);
+
+    let (ver, header_len, whole_len) = parse_tbf_header_lengths(&tbf[0..8].try_into().unwrap())
+        .ok()
+        .unwrap();
+    assert_eq!(ver, 2);
+    assert_eq!(header_len as u32, builder.header_size());
+    assert_eq!(whole_len, 1024);
+
+    let header = parse_tbf_header(&tbf[0..header_len as usize], 2).unwrap();
+    assert!(header.enabled());
+    assert!(!header.sticky());
+    assert_eq!(header.get_package_name().unwrap(), "blink");
+    assert_eq!(header.get_minimum_app_ram_size(), 4848);
+    assert_eq!(header.get_protected_size(), header_len as u32 + 12);
+    assert_eq!(
+        header.get_init_function_offset(),
+        header.get_protected_size() + 0x41
+    );
+    assert_eq!(header.get_kernel_version(), Some((2, 1)));
+    assert_eq!(header.get_fixed_address_ram(), Some(0x2000_8000));
+    assert_eq!(header.get_fixed_address_flash(), Some(0x0004_0080));
+    let permissions = header.get_all_command_permissions().unwrap();
+    assert_eq!(permissions.len(), 1);
+    assert_eq!(permissions[0].get_allowed_commands(), 0b11);
+
+    let binary_start = header.get_protected_size() as usize;
+    let binary_end = header.get_binary_end() as usize;
+    assert_eq!(&tbf[binary_start..binary_end], &binary);
+
+    let (footer, length) = parse_tbf_footer(&tbf[binary_end..]).unwrap();
+    assert!(matches!(footer, TbfFooterV2Credentials::Reserved(_)));
+    assert_eq!(binary_end + 4 + length as usize, 1024);
+}
+
+#[test]
+fn main_header_padding() {
+    let mut builder = TbfHeaderBuilder::new();
+    builder.set_program_version(None);
+    builder.set_sticky(true);
+
+    let tbf = builder.build(&[1, 2, 3], 0, 64).unwrap();
+    let header_len = builder.header_size() as usize;
+    let header = parse_tbf_header(&tbf[0..header_len], 2).unwrap();
+    assert!(header.sticky());
+    assert_eq!(header.get_binary_end(), 64);
+    assert!(tbf[header_len + 3..].iter().all(|byte| *byte == 0));
+
+    assert_eq!(
+        builder.build(&[0; 64], 0, 64),
+        Err(TbfBuildError::TotalSizeTooSmall(64 + header_len as u32))
+    );
+}
+
+#[test]
+fn padding_object() {
+    let padding = build_padding(0x200).unwrap();
+    assert_eq!(padding.len(), 0x200);
+
+    let mut list = parse_tbf_list(&padding, 0x40000);
+    assert!(matches!(
+        list.next(),
+        Some(TbfListEntry::Padding {
+            address: 0x40000,
+            ..
+        })
+    ));
+    assert!(matches!(list.next(), Some(TbfListEntry::Truncated { .. })));
+
+    assert_eq!(build_padding(8), Err(TbfBuildError::TotalSizeTooSmall(16)));
+}
+
+#[test]
+fn footers_round_trip() {
+    let key = [0x11; 384];
+    let signature = [0x22; 384];
+    let credentials = [
+        TbfFooterV2Credentials::SHA256(TbfFooterV2SHA::new([0x33; 32])),
+        TbfFooterV2Credentials::Rsa3072Key(TbfFooterV2RSA::new(key, signature)),
+        TbfFooterV2Credentials::Reserved(12),
+    ];
+
+    for credentials in credentials {
+        let footer = encode_footer(&credentials);
+        let (parsed, length) = parse_tbf_footer(&footer).unwrap();
+        assert_eq!(length as usize + 4, footer.len());
+        assert_eq!(parsed.get_type(), credentials.get_type());
+        match (parsed, credentials) {
+            (TbfFooterV2Credentials::SHA256(parsed), TbfFooterV2Credentials::SHA256(sha)) => {
+                assert_eq!(parsed.get_hash(), sha.get_hash());
+            }
+            (TbfFooterV2Credentials::Rsa3072Key(parsed), TbfFooterV2Credentials::Rsa3072Key(_)) => {
+                assert_eq!(parsed.get_public_key(), &key);
+                assert_eq!(parsed.get_signature(), &signature);
+            }
+            (TbfFooterV2Credentials::Reserved(parsed), TbfFooterV2Credentials::Reserved(size)) => {
+                assert_eq!(parsed, size);
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[test]
+fn sign_main_header_app() {
+    let mut builder = TbfHeaderBuilder::new();
+    builder.set_program_version(None);
+    builder.set_package_name("blink").unwrap();
+    builder.set_init_fn_offset(0x21);
+    let binary: Vec<u8> = (0..200).map(|i| i as u8).collect();
+    let tbf = builder.build(&binary, 0, 256).unwrap();
+
+    let sha = TbfFooterV2Credentials::SHA256(TbfFooterV2SHA::new([0x33; 32]));
+    let footer_size = encode_footer(&sha).len() as u32;
+    let mut signed = reserve_footer_space(&tbf, footer_size).unwrap();
+
+    // The header grew, so the binary moved and the object had to grow.
+    let (_, header_len, whole_len) = parse_tbf_header_lengths(&signed[0..8].try_into().unwrap())
+        .ok()
+        .unwrap();
+    assert_eq!(header_len as u32, builder.header_size() + 8);
+    assert_eq!(whole_len, 512);
+    let header = parse_tbf_header(&signed[0..header_len as usize], 2).unwrap();
+    assert_eq!(header.get_package_name(), Some("blink"));
+    assert_eq!(
+        header.get_init_function_offset(),
+        header.get_protected_size() + 0x21
+    );
+    let binary_start = header.get_protected_size() as usize;
+    assert_eq!(signed[binary_start..binary_start + 200], binary);
+    let binary_end = header.get_binary_end() as usize;
+    assert_eq!(binary_end, 256 + 8);
+
+    write_footer(&mut signed, &sha).unwrap();
+    let (footer, length) = parse_tbf_footer(&signed[binary_end..]).unwrap();
+    assert!(matches!(footer, TbfFooterV2Credentials::SHA256(_)));
+    let (footer, _) = parse_tbf_footer(&signed[binary_end + 4 + length as usize..]).unwrap();
+    assert!(matches!(footer, TbfFooterV2Credentials::Reserved(_)));
+
+    // Fill the rest of the reserved space.
+    let sha512 = TbfFooterV2Credentials::SHA512(TbfFooterV2SHA::new([0x44; 64]));
+    let mut written = 0;
+    let error = loop {
+        match write_footer(&mut signed, &sha512) {
+            Ok(()) => written += 1,
+            Err(error) => break error,
+        }
+    };
+    assert_eq!(error, TbfBuildError::NoFooterSpace);
+    assert_eq!(written, (512 - binary_end as u32 - footer_size) / 72);
+
+    // Reserving more space keeps the footers already written.
+    let grown = reserve_footer_space(&signed, 72).unwrap();
+    assert_eq!(grown.len(), 1024);
+    let (footer, _) = parse_tbf_footer(&grown[binary_end..]).unwrap();
+    assert!(matches!(footer, TbfFooterV2Credentials::SHA256(_)));
+}
+
+#[test]
+fn edit_header() {
+    let mut builder = TbfHeaderBuilder::new();
+    builder.set_package_name("blink").unwrap();
+    builder.set_init_fn_offset(0x21);
+    builder.set_minimum_ram_size(4096);
+    let binary: Vec<u8> = (0..200).map(|i| i as u8).collect();
+    let tbf = builder.build(&binary, 64, 1024).unwrap();
+    let sha = TbfFooterV2Credentials::SHA256(TbfFooterV2SHA::new([0x33; 32]));
+    let mut tbf = reserve_footer_space(&tbf, 40).unwrap();
+    write_footer(&mut tbf, &sha).unwrap();
+
+    let header_len = builder.header_size() as usize;
+    let header = parse_tbf_header(&tbf[0..header_len], 2).unwrap();
+
+    let mut editor = TbfHeaderEditor::new(&tbf).unwrap();
+    editor.set_package_name("blink_test").unwrap();
+    editor.set_minimum_ram_size(8192);
+    editor
+        .set_kernel_version(Some(TbfHeaderV2KernelVersion::new(2, 1)))
+        .unwrap();
+    editor.set_tlv(0x80, &[1, 2, 3]).unwrap();
+    let (edited, dropped) = editor.build().unwrap();
+    assert_eq!(edited.len(), tbf.len());
+    assert_eq!(dropped, [TbfFooterV2CredentialsType::SHA256]);
+
+    let (_, new_header_len, _) = parse_tbf_header_lengths(&edited[0..8].try_into().unwrap())
+        .ok()
+        .unwrap();
+    assert_eq!(new_header_len as usize, header_len + 4 + 8 + 8);
+    let new_header = parse_tbf_header(&edited[0..new_header_len as usize], 2).unwrap();
+    assert_eq!(new_header.get_package_name(), Some("blink_test"));
+    assert_eq!(new_header.get_minimum_app_ram_size(), 8192);
+    assert_eq!(new_header.get_kernel_version(), Some((2, 1)));
+    assert_eq!(new_header.get_unknown_tlvs().count(), 1);
+
+    // The binary did not move, and the old hash was dropped.
+    assert_eq!(new_header.get_protected_size(), header.get_protected_size());
+    assert_eq!(
+        new_header.get_init_function_offset(),
+        header.get_init_function_offset()
+    );
+    let binary_end = header.get_binary_end() as usize;
+    assert_eq!(new_header.get_binary_end() as usize, binary_end);
+    let binary_start = header.get_protected_size() as usize;
+    assert_eq!(
+        edited[binary_start..binary_end],
+        tbf[binary_start..binary_end]
+    );
+    let (footer, _) = parse_tbf_footer(&edited[binary_end..]).unwrap();
+    assert!(matches!(footer, TbfFooterV2Credentials::Reserved(_)));
+
+    let mut editor = TbfHeaderEditor::new(&edited).unwrap();
+    assert_eq!(editor.build().unwrap().1, []);
+    assert_eq!(editor.remove_tlv(0x80), Ok(true));
+    assert_eq!(editor.remove_tlv(0x80), Ok(false));
+    assert_eq!(editor.remove_tlv(9), Err(TbfBuildError::LayoutTlv(9)));
+
+    // A value the parser does not accept.
+    let mut broken = editor.clone();
+    broken.set_tlv(8, &[1, 2, 3]).unwrap();
+    assert!(matches!(
+        broken.build(),
+        Err(TbfBuildError::InvalidHeader(TbfParseError::BadTlvEntry(
+            8,
+            _
+        )))
+    ));
+
+    // The protected trailer only has room for 64 more bytes.
+    editor.set_tlv(0x81, &[0; 100]).unwrap();
+    assert!(matches!(
+        editor.build(),
+        Err(TbfBuildError::HeaderOverlapsBinary(_))
+    ));
+}
+
+#[test]
+fn reserve_too_much_footer_space() {
+    let tbf = TbfHeaderBuilder::new()
+        .build(&[0xAA; 200], 0, 1024)
+        .unwrap();
+
+    for size in [u32::MAX, 0x8000_0001] {
+        assert_eq!(
+            reserve_footer_space(&tbf, size),
+            Err(TbfBuildError::ObjectTooLarge)
+        );
+    }
+}
+
+#[test]
+fn edit_malformed_binary_end() {
+    let mut builder = TbfHeaderBuilder::new();
+    builder.set_package_name("blink").unwrap();
+    let tbf = builder.build(&[0xAA; 200], 0, 1024).unwrap();
+    let header_len = builder.header_size() as usize;
+    let program = parse_tbf_header_tlvs(&tbf[0..header_len])
+        .map(Result::unwrap)
+        .find(|tlv| tlv.tipe == 9)
+        .unwrap();
+    // The binary end offset is the fourth field of the Program entry.
+    let field = program.offset + 4 + 12;
+
+    // Past the end of the object, inside the header, and leaving too little
+    // space for a footer.
+    for binary_end in [2048u32, 8, 1020] {
+        let mut corrupt = tbf.clone();
+        corrupt[field..field + 4].copy_from_slice(&binary_end.to_le_bytes());
+        fix_checksum(&mut corrupt[0..header_len]);
+
+        assert!(parse_tbf_header(&corrupt[0..header_len], 2).is_ok());
+        assert_eq!(
+            TbfHeaderEditor::new(&corrupt).unwrap_err(),
+            TbfBuildError::InvalidTbf
+        );
+    }
+}
+
+#[test]
+fn build_too_large() {
+    let binary = [0xAA; 16];
+    assert_eq!(
+        TbfHeaderBuilder::new().build(&binary, u32::MAX - 8, u32::MAX),
+        Err(TbfBuildError::ObjectTooLarge)
+    );
+
+    let mut builder = TbfHeaderBuilder::new();
+    builder.set_init_fn_offset(u32::MAX);
+    assert_eq!(
+        builder.build(&binary, 16, 1024),
+        Err(TbfBuildError::ObjectTooLarge)
+    );
+}
+
+#[test]
+fn program_header_with_entry_point_in_trailer() {
+    let mut builder = TbfHeaderBuilder::new();
+    builder.set_program_version(None);
+    let tbf = builder.build(&[0xAA; 200], 32, 1024).unwrap();
+    let header_len = builder.header_size() as usize;
+    let main = parse_tbf_header_tlvs(&tbf[0..header_len])
+        .map(Result::unwrap)
+        .find(|tlv| tlv.tipe == 1)
+        .unwrap();
+
+    // The entry point lies in the protected trailer, before the binary.
+    let mut corrupt = tbf.clone();
+    corrupt[main.offset + 4..main.offset + 8].copy_from_slice(&0u32.to_le_bytes());
+    fix_checksum(&mut corrupt[0..header_len]);
+    assert!(parse_tbf(&corrupt).is_ok());
+
+    assert_eq!(to_program_header(&corrupt), Err(TbfBuildError::InvalidTbf));
+}
@@ -0,0 +1,126 @@
+#![cfg(feature = "std")]
+
+use tbf_parser::owned::{parse_owned_tbf_header, OwnedTbfHeader};
+use tbf_parser::parse::*;
+use tbf_parser::types::{TbfHeader, TbfParseError};
+
+/// Build a header with a Main TLV followed by `tlvs`.
+fn build_header(tlvs: &[(u16, Vec<u8>)]) -> Vec<u8> {
+    let mut header: Vec<u8> = Vec::new();
+    header.extend(2u16.to_le_bytes());
+    header.extend(0u16.to_le_bytes()); // Header size, set below.
+    header.extend(4096u32.to_le_bytes());
+    header.extend(1u32.to_le_bytes());
+    header.extend(0u32.to_le_bytes()); // Checksum, set below.
+    for (tipe, value) in [(1, vec![0; 12])].iter().chain(tlvs) {
+        header.extend(tipe.to_le_bytes());
+        header.extend((value.len() as u16).to_le_bytes());
+        header.extend(value);
+        header.resize(header.len().next_multiple_of(4), 0);
+    }
+
+    let header_size = header.len() as u16;
+    header[2..4].copy_from_slice(&header_size.to_le_bytes());
+    let checksum = header
+        .chunks_exact(4)
+        .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+        .fold(0, |checksum, word| checksum ^ word);
+    header[12..16].copy_from_slice(&checksum.to_le_bytes());
+    header
+}
+
+#[test]
+fn beyond_fixed_limits() {
+    let name = "a".repeat(100);
+    let mut permissions = 12u16.to_le_bytes().to_vec();
+    for driver in 0..12u32 {
+        permissions.extend(driver.to_le_bytes());
+        permissions.extend(0u32.to_le_bytes());
+        permissions.extend(0b101u64.to_le_bytes());
+    }
+    let mut storage = 7u32.to_le_bytes().to_vec();
+    storage.extend(10u16.to_le_bytes());
+    storage.extend((1..=10u32).flat_map(u32::to_le_bytes));
+    storage.extend(1u16.to_le_bytes());
+    storage.extend(42u32.to_le_bytes());
+    let regions: Vec<u8> = (0..6u32)
+        .flat_map(|i| [i * 0x100, 0x100])
+        .flat_map(u32::to_le_bytes)
+        .collect();
+    let header = build_header(&[
+        (3, name.as_bytes().to_vec()),
+        (6, permissions),
+        (7, storage),
+        (2, regions),
+    ]);
+
+    assert!(parse_tbf_header(&header, 2).is_err());
+
+    let owned = parse_owned_tbf_header(&header, 2).unwrap();
+    assert!(owned.is_app());
+    assert_eq!(owned.get_package_name(), Some(name.as_str()));
+    let permissions = owned.get_all_command_permissions().unwrap();
+    assert_eq!(permissions.len(), 12);
+    assert_eq!(permissions[11].get_driver_number(), 11);
+    assert_eq!(permissions[11].get_allowed_commands(), 0b101);
+    assert_eq!(owned.get_storage_write_id().unwrap().get(), 7);
+    assert_eq!(
+        owned.get_storage_read_ids().unwrap(),
+        &(1..=10).collect::<Vec<_>>()[..]
+    );
+    assert_eq!(owned.get_storage_modify_ids().unwrap(), &[42]);
+    assert_eq!(owned.number_writeable_flash_regions(), 6);
+    assert_eq!(owned.get_writeable_flash_region(5), (0x500, 0x100));
+
+    assert!(matches!(
+        TbfHeader::try_from(&owned),
+        Err(TbfParseError::TooManyEntries(_) | TbfParseError::PackageNameTooLong)
+    ));
+}
+
+#[test]
+fn conversions_round_trip() {
+    let buffer = include_bytes!("./flashes/footerSHA256.dat").to_vec();
+    let header = parse_tbf_header(&buffer[0..76], 2).unwrap();
+
+    let owned = OwnedTbfHeader::from(&header);
+    assert_eq!(owned.get_package_name(), Some("_heart"));
+    assert_eq!(owned.get_kernel_version(), Some((2, 0)));
+    assert_eq!(owned.get_binary_end(), 5836);
+    assert_eq!(
+        owned.get_init_function_offset(),
+        header.get_init_function_offset()
+    );
+
+    let back = TbfHeader::try_from(&owned).unwrap();
+    assert_eq!(back.get_package_name(), Some("_heart"));
+    assert_eq!(back.get_binary_end(), 5836);
+    assert_eq!(back.checksum(), header.checksum());
+
+    let parsed = parse_owned_tbf_header(&buffer[0..76], 2).unwrap();
+    assert_eq!(parsed.get_protected_size(), header.get_protected_size());
+    assert_eq!(
+        parsed.get_minimum_app_ram_size(),
+        header.get_minimum_app_ram_size()
+    );
+}
+
+#[test]
+fn init_function_offset_overflow() {
+    let mut header = build_header(&[]);
+    // The init function offset is the first field of the Main entry.
+    header[20..24].copy_from_slice(&u32::MAX.to_le_bytes());
+    let checksum = header
+        .chunks_exact(4)
+        .enumerate()
+        .filter(|(i, _)| *i != 3)
+        .fold(0, |checksum, (_, word)| {
+            checksum ^ u32::from_le_bytes(word.try_into().unwrap())
+        });
+    header[12..16].copy_from_slice(&checksum.to_le_bytes());
+
+    let owned = parse_owned_tbf_header(&header, 2).unwrap();
+    assert_eq!(owned.get_init_function_offset(), u32::MAX);
+    let fixed = parse_tbf_header(&header, 2).unwrap();
+    assert_eq!(fixed.get_init_function_offset(), u32::MAX);
+}
@@ -1,5 +1,5 @@
 use tbf_parser::parse::*;
-use tbf_parser::types::{TbfFooterV2Credentials, TbfFooterV2CredentialsType};
+use tbf_parser::types::{TbfFooterV2Credentials, TbfFooterV2CredentialsType, TbfParseError};
 
 #[test]
 fn simple_tbf() {
@@ -120,3 +120,252 @@ fn footer_rsa4096() {
         panic!("Footer is not of type 'Reserved'!");
     }
 }
+
+/// Store the checksum of a modified header.
+fn fix_checksum(header: &mut [u8]) {
+    let checksum = header
+        .chunks_exact(4)
+        .enumerate()
+        .filter(|(i, _)| *i != 3)
+        .map(|(_, word)| u32::from_le_bytes(word.try_into().unwrap()))
+        .fold(0, |checksum, word| checksum ^ word);
+    header[12..16].copy_from_slice(&checksum.to_le_bytes());
+}
+
+#[test]
+fn error_offsets() {
+    let buffer = include_bytes!("./flashes/simple.dat").to_vec();
+
+    // A Main TLV with the wrong length.
+    let mut header = buffer.clone();
+    header[18..20].copy_from_slice(&8u16.to_le_bytes());
+    fix_checksum(&mut header);
+    let error = parse_tbf_header(&header, 2).unwrap_err();
+    assert_eq!(error, TbfParseError::BadTlvEntry(1, 16));
+    assert_eq!(
+        error.to_string(),
+        "TLV entry type 1 at offset 0x10 is invalid"
+    );
+
+    // A package name that is not UTF-8.
+    let mut header = buffer.clone();
+    header[36] = 0xFF;
+    fix_checksum(&mut header);
+    let error = parse_tbf_header(&header, 2).unwrap_err();
+    assert_eq!(error, TbfParseError::BadProcessName(32));
+}
+
+#[test]
+fn header_tlvs() {
+    let buffer = include_bytes!("./flashes/footerSHA256.dat").to_vec();
+    let header = &buffer[0..76];
+
+    let tlvs = parse_tbf_header_tlvs(header)
+        .map(|tlv| {
+            let tlv = tlv.unwrap();
+            (tlv.tipe, tlv.offset, tlv.value.len())
+        })
+        .collect::<Vec<_>>();
+    // Main, Program, Package Name and Kernel Version.
+    assert_eq!(tlvs, [(1, 16, 12), (9, 32, 20), (3, 56, 6), (8, 68, 4)]);
+
+    // A truncated header yields an error and stops.
+    let mut truncated = parse_tbf_header_tlvs(&header[0..40]);
+    assert!(truncated.next().unwrap().is_ok());
+    assert!(truncated.next().unwrap().is_err());
+    assert!(truncated.next().is_none());
+}
+
+#[test]
+fn newer_and_unknown_tlvs() {
+    let mut header: Vec<u8> = Vec::new();
+    header.extend(2u16.to_le_bytes());
+    header.extend(0u16.to_le_bytes()); // Header size, set below.
+    header.extend(1024u32.to_le_bytes());
+    header.extend(1u32.to_le_bytes());
+    header.extend(0u32.to_le_bytes()); // Checksum, set below.
+    let mut push_tlv = |tipe: u16, value: &[u8]| {
+        header.extend(tipe.to_le_bytes());
+        header.extend((value.len() as u16).to_le_bytes());
+        header.extend(value);
+        header.resize(header.len().next_multiple_of(4), 0);
+    };
+    push_tlv(1, &[0; 12]);
+    push_tlv(10, &0x1234u32.to_le_bytes());
+    push_tlv(0x77, &[1, 2, 3]);
+    let pic: Vec<u8> = (1..=10u32).flat_map(u32::to_le_bytes).collect();
+    push_tlv(4, &pic);
+    push_tlv(0x78, &[]);
+
+    let header_size = header.len() as u16;
+    header[2..4].copy_from_slice(&header_size.to_le_bytes());
+    let checksum = header
+        .chunks_exact(4)
+        .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+        .fold(0, |checksum, word| checksum ^ word);
+    header[12..16].copy_from_slice(&checksum.to_le_bytes());
+
+    let header = parse_tbf_header(&header, 2).unwrap();
+    assert_eq!(header.get_fixed_short_id().unwrap().get(), 0x1234);
+    let pic = header.get_pic_option1().unwrap();
+    assert_eq!(pic.get_text_offset(), 1);
+    assert_eq!(pic.get_got_size(), 9);
+    assert_eq!(pic.get_minimum_stack_length(), 10);
+
+    let unknown = header
+        .get_unknown_tlvs()
+        .map(|tlv| (tlv.get_type(), tlv.get_offset(), tlv.get_length()))
+        .collect::<Vec<_>>();
+    assert_eq!(unknown, [(0x77, 40, 3), (0x78, 92, 0)]);
+}
+
+#[test]
+fn whole_tbf_object() {
+    let mut buffer = include_bytes!("./flashes/footerSHA256.dat").to_vec();
+    buffer.extend([0xFF; 16]);
+
+    let object = parse_tbf(&buffer).unwrap();
+    assert_eq!(object.header.get_package_name(), Some("_heart"));
+    assert_eq!(object.header_data.len(), 76);
+    assert_eq!(object.binary.len(), 5836 - 76);
+    assert_eq!(object.trailing, &[0xFF; 16]);
+
+    let footers = object.footers.collect::<Vec<_>>();
+    assert_eq!(footers[0].offset, 5836);
+    assert_eq!(footers[0].credentials.get_type(), "SHA256");
+    assert_eq!(footers[0].length, 32 + 4);
+    assert_eq!(footers[1].offset, 5836 + 4 + 36);
+    let end = footers.last().map(|f| f.offset + 4 + f.length as usize);
+    assert_eq!(end, Some(8192));
+
+    // A footer claiming to run past the end of the object.
+    let mut corrupt = buffer.clone();
+    corrupt[5836 + 2..5836 + 4].copy_from_slice(&0xFFFFu16.to_le_bytes());
+    assert!(matches!(
+        parse_tbf(&corrupt),
+        Err(TbfParseError::BadFooter(5836))
+    ));
+
+    // Missing bytes at the end of the object.
+    assert!(matches!(
+        parse_tbf(&buffer[0..8000]),
+        Err(TbfParseError::NotEnoughFlash)
+    ));
+}
+
+#[test]
+fn main_header_object() {
+    // The file only holds the header of the object.
+    let mut buffer = include_bytes!("./flashes/simple.dat").to_vec();
+    buffer.resize(8192, 0);
+
+    let object = parse_tbf(&buffer).unwrap();
+    assert_eq!(object.binary.len(), 8192 - 52);
+    assert_eq!(object.footers.count(), 0);
+    assert!(object.trailing.is_empty());
+}
+
+#[test]
+fn protected_trailer_overflow() {
+    let mut buffer = include_bytes!("./flashes/simple.dat").to_vec();
+    buffer[24..28].copy_from_slice(&u32::MAX.to_le_bytes());
+    fix_checksum(&mut buffer);
+    buffer.resize(8192, 0);
+
+    assert!(matches!(
+        parse_tbf(&buffer),
+        Err(TbfParseError::BadBinaryRange(u32::MAX, _))
+    ));
+}
+
+#[test]
+fn app_list() {
+    let app = include_bytes!("./flashes/footerSHA256.dat");
+    let mut corrupt = include_bytes!("./flashes/simple.dat").to_vec();
+    corrupt.resize(8192, 0);
+    corrupt[12] ^= 1;
+
+    let mut flash = app.to_vec();
+    flash.extend(&corrupt);
+    flash.extend(app);
+    flash.extend([0xFF; 64]);
+
+    let mut list = parse_tbf_list(&flash, 0x40000);
+    assert!(matches!(
+        list.next(),
+        Some(TbfListEntry::App { address: 0x40000, object }) if object.data.len() == 8192
+    ));
+    assert!(matches!(
+        list.next(),
+        Some(TbfListEntry::Invalid {
+            address: 0x42000,
+            size: 8192,
+            error: TbfParseError::ChecksumMismatch(..)
+        })
+    ));
+    assert!(matches!(
+        list.next(),
+        Some(TbfListEntry::App {
+            address: 0x44000,
+            ..
+        })
+    ));
+    assert!(matches!(
+        list.next(),
+        Some(TbfListEntry::End { address: 0x46000 })
+    ));
+    assert!(list.next().is_none());
+
+    // The buffer ends in the middle of the second object.
+    let mut list = parse_tbf_list(&flash[0..8300], 0x40000);
+    assert!(matches!(list.next(), Some(TbfListEntry::App { .. })));
+    assert!(matches!(
+        list.next(),
+        Some(TbfListEntry::Truncated {
+            address: 0x42000,
+            needed: 8192
+        })
+    ));
+    assert!(list.next().is_none());
+
+    // The buffer ends just after the first object.
+    let mut list = parse_tbf_list(&flash[0..8192], 0x40000);
+    assert!(matches!(list.next(), Some(TbfListEntry::App { .. })));
+    assert!(matches!(
+        list.next(),
+        Some(TbfListEntry::Truncated { needed: 8, .. })
+    ));
+}
+
+#[test]
+fn app_list_with_overflowing_trailer() {
+    let mut corrupt = include_bytes!("./flashes/simple.dat").to_vec();
+    corrupt[24..28].copy_from_slice(&u32::MAX.to_le_bytes());
+    fix_checksum(&mut corrupt);
+    corrupt.resize(8192, 0);
+
+    let mut flash = corrupt;
+    flash.extend(include_bytes!("./flashes/footerSHA256.dat"));
+    flash.extend([0xFF; 64]);
+
+    let mut list = parse_tbf_list(&flash, 0x40000);
+    assert!(matches!(
+        list.next(),
+        Some(TbfListEntry::Invalid {
+            address: 0x40000,
+            size: 8192,
+            error: TbfParseError::BadBinaryRange(..)
+        })
+    ));
+    assert!(matches!(
+        list.next(),
+        Some(TbfListEntry::App {
+            address: 0x42000,
+            ..
+        })
+    ));
+    assert!(matches!(
+        list.next(),
+        Some(TbfListEntry::End { address: 0x44000 })
+    ));
+}
@@ -0,0 +1,27 @@
+#![cfg(feature = "serde")]
+
+use tbf_parser::parse::*;
+use tbf_parser::types::{TbfFooterV2Credentials, TbfHeader};
+
+#[test]
+fn header_and_footer_round_trip() {
+    let buffer = include_bytes!("./flashes/footerSHA256.dat").to_vec();
+    let header = parse_tbf_header(&buffer[0..76], 2).unwrap();
+
+    let json = serde_json::to_string(&header).unwrap();
+    let back: TbfHeader = serde_json::from_str(&json).unwrap();
+    assert_eq!(back.get_package_name(), Some("_heart"));
+    assert_eq!(back.get_kernel_version(), Some((2, 0)));
+    assert_eq!(back.get_binary_end(), header.get_binary_end());
+    assert_eq!(back.checksum(), header.checksum());
+
+    let (footer, _) = parse_tbf_footer(&buffer[5836..]).unwrap();
+    let json = serde_json::to_string(&footer).unwrap();
+    let back: TbfFooterV2Credentials = serde_json::from_str(&json).unwrap();
+    match (back, footer) {
+        (TbfFooterV2Credentials::SHA256(back), TbfFooterV2Credentials::SHA256(footer)) => {
+            assert_eq!(back.get_hash(), footer.get_hash());
+        }
+        _ => panic!("Footer type changed"),
+    }
+}
@@ -0,0 +1,49 @@
+#![cfg(feature = "crypto")]
+
+use tbf_parser::parse::*;
+use tbf_parser::verify::{check_credentials, integrity_region, CredentialsCheck};
+
+fn check_footers(mut buffer: Vec<u8>, tamper: bool) -> Vec<CredentialsCheck> {
+    let (ver, header_len, whole_len) = parse_tbf_header_lengths(&buffer[0..8].try_into().unwrap())
+        .ok()
+        .unwrap();
+    let header = parse_tbf_header(&buffer[0..header_len as usize], ver).unwrap();
+
+    let binary_end = header.get_binary_end() as usize;
+    if tamper {
+        buffer[binary_end - 1] ^= 0xFF;
+    }
+    let region = integrity_region(&buffer, &header).unwrap();
+
+    let mut checks = Vec::new();
+    let mut offset = binary_end;
+    while offset < whole_len as usize {
+        let (credentials, length) = parse_tbf_footer(&buffer[offset..]).unwrap();
+        checks.push(check_credentials(region, &credentials));
+        offset += length as usize + 4;
+    }
+    checks
+}
+
+#[test]
+fn sha256_footer() {
+    let buffer = include_bytes!("./flashes/footerSHA256.dat").to_vec();
+
+    assert_eq!(
+        check_footers(buffer.clone(), false),
+        [CredentialsCheck::Valid, CredentialsCheck::Unchecked]
+    );
+    assert_eq!(
+        check_footers(buffer, true),
+        [CredentialsCheck::Invalid, CredentialsCheck::Unchecked]
+    );
+}
+
+#[test]
+fn rsa_footer_is_unchecked() {
+    let buffer = include_bytes!("./flashes/footerRSA4096.dat").to_vec();
+
+    assert!(check_footers(buffer, false)
+        .iter()
+        .all(|check| *check == CredentialsCheck::Unchecked));
+}
@@ -52,12 +52,10 @@ impl StateStore {
                         }
                     },
                     Some(action) = action_receiver.recv() => match action {
-                        Action::SendMessage { content } => {
-                            if !state.active_apps.is_empty() {
-                                command_writer.send(
-                                    content
-                                ).expect("Expected command reader to be open.");
-                            }
+                        Action::SendMessage { content } if !state.active_apps.is_empty() => {
+                            command_writer.send(
+                                content
+                            ).expect("Expected command reader to be open.");
                         },
                         Action::AddScreen { screen_idx } => {
                             state.active_apps.push((screen_idx, None))
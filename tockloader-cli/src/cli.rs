@@ -3,7 +3,12 @@
 // Copyright OXIDOS AUTOMOTIVE 2024.
 
 use clap::error::ErrorKind;
-use clap::{arg, crate_version, value_parser, ArgMatches, Command};
+use std::path::PathBuf;
+
+use clap::{arg, crate_version, value_parser, ArgGroup, ArgMatches, Command};
+
+use tockloader_lib::tabs::metadata::TockKernelVersion;
+use toml::value::Datetime;
 
 use crate::known_boards::KnownBoardNames;
 
@@ -15,7 +20,14 @@ pub fn make_cli() -> Command {
         .subcommand_required(true)
         .subcommands(get_subcommands())
         .args([
-            arg!(--debug "Print additional debugging information").action(clap::ArgAction::SetTrue)
+            arg!(--debug "Print additional debugging information, same as -vv")
+                .action(clap::ArgAction::SetTrue)
+                .global(true),
+            arg!(-v --verbose... "Print more information, repeat up to three times for a protocol trace")
+                .global(true),
+            arg!(--"log-file" <PATH> "Write a full trace of the run to a file, for bug reports")
+                .value_parser(value_parser!(PathBuf))
+                .global(true),
         ])
     // Note: arg_require_else_help will trigger the help command if no argument/subcommand is given.
     // This means that the --debug flag will not trigger the help menu, even if alone it does nothing.
@@ -31,34 +43,264 @@ fn get_subcommands() -> Vec<Command> {
         Command::new("list")
             .about("List and inspect probes")
             .args(get_app_args())
+            .args(get_image_args())
             .args(get_channel_args())
+            .args(get_output_args())
             .arg_required_else_help(false),
         Command::new("info")
             .about("Verbose information about the connected board")
             .args(get_app_args())
+            .args(get_image_args())
+            .args(get_channel_args())
+            .args(get_output_args())
+            .arg_required_else_help(false),
+        Command::new("memory-map")
+            .about("Show the flash and RAM layout of the connected board")
+            .args(get_app_args())
             .args(get_channel_args())
+            .args(get_output_args())
             .arg_required_else_help(false),
+        Command::new("attr")
+            .about("Read and write the bootloader attributes")
+            .subcommand_required(true)
+            .subcommands(get_attr_subcommands()),
+        Command::new("flash-kernel")
+            .about("Flash a kernel image below the apps")
+            .arg(arg!(<KERNEL> "Path of the kernel .bin, .elf or .hex file"))
+            .arg(
+                arg!(--"kernel-address" <ADDRESS> "Address of a .bin kernel, defaults to the current kernel start")
+                    .value_parser(parse_address),
+            )
+            .arg(
+                arg!(--"preserve-apps" "Restore the installed apps if flashing damages them")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .args(get_app_args())
+            .args(get_channel_args())
+            .arg_required_else_help(false),
+        Command::new("read")
+            .about("Read a range of flash and print it as a hexdump")
+            .arg(arg!(<ADDRESS> "Address to start reading from").value_parser(parse_address))
+            .arg(arg!(<LENGTH> "Number of bytes to read").value_parser(parse_length))
+            .arg(arg!(--file <FILE> "Save the data to a file instead of printing it"))
+            .args(get_channel_args())
+            .arg_required_else_help(false),
+        Command::new("write")
+            .about("Write the contents of a file to flash")
+            .arg(arg!(<ADDRESS> "Address to write the file at").value_parser(parse_address))
+            .arg(arg!(<FILE> "Path of the file to write"))
+            .args(get_channel_args())
+            .arg_required_else_help(false),
+        Command::new("dump")
+            .about("Save the app region to an image file, for offline inspection")
+            .arg(arg!(<FILE> "Path of the image file to create"))
+            .args(get_app_args())
+            .args(get_channel_args())
+            .arg_required_else_help(false),
+        Command::new("inspect-tab")
+            .about("Print the metadata and the TBF headers of a TAB file")
+            .arg(arg!(<TAB> "Path of the TAB file")),
+        Command::new("tab")
+            .about("Create and modify TAB files")
+            .subcommand_required(true)
+            .subcommands(get_tab_subcommands()),
+        Command::new("convert")
+            .about("Convert app ELFs named <arch>.elf into a TAB, or into a single TBF if OUTPUT ends with .tbf")
+            .arg(arg!(<OUTPUT> "Path of the TAB or TBF file to create"))
+            .arg(arg!(<ELF> ... "ELF files to convert"))
+            .args(get_tab_metadata_args())
+            .args(get_tbf_header_args()),
+        Command::new("sign")
+            .about("Add credentials footers to a TBF, or to every TBF of a TAB if FILE ends with .tab")
+            .arg(arg!(<FILE> "Path of the TBF or TAB file to sign"))
+            .arg(arg!(--sha256 "Add a SHA-256 hash").action(clap::ArgAction::SetTrue))
+            .arg(arg!(--sha384 "Add a SHA-384 hash").action(clap::ArgAction::SetTrue))
+            .arg(arg!(--sha512 "Add a SHA-512 hash").action(clap::ArgAction::SetTrue))
+            .arg(
+                arg!(--"rsa-key" <PEM> "Add an RSA-3072 or RSA-4096 signature with a private key")
+                    .action(clap::ArgAction::Append),
+            )
+            .arg(arg!(-o --output <FILE> "Path of the signed file, defaults to FILE"))
+            .group(
+                ArgGroup::new("credentials")
+                    .args(["sha256", "sha384", "sha512", "rsa-key"])
+                    .multiple(true)
+                    .required(true),
+            ),
+        Command::new("tbf")
+            .about("Modify TBF files")
+            .subcommand_required(true)
+            .subcommands(get_tbf_subcommands()),
         Command::new("install")
             .about("Install apps")
             .args(get_app_args())
             .args(get_channel_args())
             .arg_required_else_help(false),
+        Command::new("erase-apps")
+            .about("Erase the installed apps, keeping sticky apps")
+            .arg(
+                arg!(--force "Erase sticky apps as well")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .args(get_app_args())
+            .args(get_channel_args())
+            .arg_required_else_help(false),
+    ]
+}
+
+/// Generate the [subcommands](clap::Command) of the `attr` command.
+fn get_attr_subcommands() -> Vec<Command> {
+    vec![
+        Command::new("list")
+            .about("List all bootloader attributes")
+            .args(get_channel_args()),
+        Command::new("get")
+            .about("Print the value of a bootloader attribute")
+            .arg(arg!(<KEY> "Key of the attribute"))
+            .args(get_channel_args()),
+        Command::new("set")
+            .about("Add or overwrite a bootloader attribute")
+            .arg(arg!(<KEY> "Key of the attribute, at most 8 bytes"))
+            .arg(arg!(<VALUE> "Value of the attribute, at most 55 bytes"))
+            .args(get_channel_args()),
+        Command::new("remove")
+            .about("Remove a bootloader attribute")
+            .arg(arg!(<KEY> "Key of the attribute"))
+            .args(get_channel_args()),
+    ]
+}
+
+/// Generate the [subcommands](clap::Command) of the `tab` command.
+fn get_tab_subcommands() -> Vec<Command> {
+    vec![
+        Command::new("create")
+            .about("Package TBF files named <arch>.tbf into a new TAB")
+            .arg(arg!(<TAB> "Path of the TAB file to create"))
+            .arg(arg!(<TBF> ... "TBF files to include"))
+            .args(get_tab_metadata_args()),
+        Command::new("add-arch")
+            .about("Add TBF files to a TAB, replacing the ones with the same name")
+            .arg(arg!(<TAB> "Path of the TAB file to modify"))
+            .arg(arg!(<TBF> ... "TBF files to add")),
+        Command::new("remove-arch")
+            .about("Remove the TBF files of an architecture from a TAB")
+            .arg(arg!(<TAB> "Path of the TAB file to modify"))
+            .arg(arg!(<ARCH> ... "Architectures to remove")),
+    ]
+}
+
+/// Generate the [subcommands](clap::Command) of the `tbf` command.
+fn get_tbf_subcommands() -> Vec<Command> {
+    vec![Command::new("edit")
+        .about("Edit the header of a TBF, or of every TBF of a TAB if FILE ends with .tab")
+        .arg(arg!(<FILE> "Path of the TBF or TAB file to edit"))
+        .arg(arg!(--"package-name" <NAME> "Rename the app"))
+        .arg(arg!(--"minimum-ram" <SIZE> "Minimum RAM size of the app").value_parser(parse_size))
+        .arg(
+            arg!(--"kernel-version" <VERSION> "Kernel version the app needs, as <major>.<minor>")
+                .value_parser(parse_kernel_version),
+        )
+        .arg(
+            arg!(--"remove-tlv" <TYPE> "Remove every TLV entry of a type")
+                .action(clap::ArgAction::Append)
+                .value_parser(parse_tlv_type),
+        )
+        .arg(
+            arg!(--"set-tlv" <TLV> "Add or replace a TLV entry, as <type>,<hex value>")
+                .action(clap::ArgAction::Append)
+                .value_parser(parse_tlv),
+        )
+        .arg(
+            arg!(--"drop-credentials" "Edit signed files, whose credentials footers have to be replaced by reserved space")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(arg!(-o --output <FILE> "Path of the edited file, defaults to FILE"))
+        .group(
+            ArgGroup::new("edits")
+                .args([
+                    "package-name",
+                    "minimum-ram",
+                    "kernel-version",
+                    "remove-tlv",
+                    "set-tlv",
+                ])
+                .multiple(true)
+                .required(true),
+        )]
+}
+
+/// Generate all of the [arguments](clap::Arg) that describe a new TAB.
+fn get_tab_metadata_args() -> Vec<clap::Arg> {
+    vec![
+        arg!(--name <NAME> "Name of the app").required(true),
+        arg!(--"kernel-version" <VERSION> "Minimum kernel version, as <major>.<minor>")
+            .required(true)
+            .value_parser(|value: &str| value.parse::<TockKernelVersion>()),
+        arg!(--boards <BOARDS> "Comma-separated list of the only supported boards"),
+        arg!(--"build-date" <DATE> "Build date in RFC 3339 format, defaults to now")
+            .value_parser(|value: &str| value.parse::<Datetime>()),
+    ]
+}
+
+/// Generate all of the [arguments](clap::Arg) that control the TBF headers built from ELFs.
+fn get_tbf_header_args() -> Vec<clap::Arg> {
+    vec![
+        arg!(--stack <SIZE> "Stack size, for ELFs without a .stack section, defaults to 2048")
+            .value_parser(parse_size),
+        arg!(--"app-heap" <SIZE> "Size of the app heap")
+            .value_parser(parse_size)
+            .default_value("1024"),
+        arg!(--"kernel-heap" <SIZE> "Size of the kernel heap reserved for the app")
+            .value_parser(parse_size)
+            .default_value("1024"),
+        arg!(--permission <PERMISSION> "Allow the app to call a command, as <driver>,<command>")
+            .action(clap::ArgAction::Append)
+            .value_parser(parse_permission),
+        arg!(--"protected-region-size" <SIZE> "Size of the header and the protected trailer")
+            .value_parser(parse_size),
+        arg!(--"minimum-footer-size" <SIZE> "Space to keep after the binary for credentials")
+            .value_parser(parse_size)
+            .default_value("0"),
+        arg!(--"binary-version" <VERSION> "Version of the binary in the Program header")
+            .value_parser(parse_size)
+            .default_value("0"),
+        arg!(--"main-header" "Use a Main header instead of a Program header")
+            .action(clap::ArgAction::SetTrue),
     ]
 }
 
 /// Generate all of the [arguments](clap::Arg) that are required by subcommands which work with apps.
 fn get_app_args() -> Vec<clap::Arg> {
-    let probe_args_ids = get_probe_args().into_iter().map(|arg| arg.get_id().clone());
-
     vec![
-        // Default of ProbeTargetInfo: 0x00030000
+        // Default of BoardSettings: 0x00030000
         arg!(-a --"app-address" <ADDRESS> "Address where apps are located")
-            .conflicts_with_all(probe_args_ids.clone().collect::<Vec<_>>()),
+            .value_parser(parse_address)
+            .conflicts_with("board"),
         arg!(--tab <TAB> "Specify the path of the tab file"),
     ]
     // Note: the .action(clap::ArgAction::SetTrue) doesn't seem to be necessary, though in clap documentation it is used.
 }
 
+/// Generate all of the [arguments](clap::Arg) that are required by subcommands which can
+/// inspect a flash image instead of a board.
+fn get_image_args() -> Vec<clap::Arg> {
+    vec![
+        arg!(--image <FILE> "Read apps from a flash image instead of a board")
+            .conflicts_with_all(["serial", "board"]),
+        arg!(--"image-base" <ADDRESS> "Address of the first byte of the image, defaults to the app address")
+            .value_parser(parse_address)
+            .requires("image"),
+    ]
+}
+
+/// Generate all of the [arguments](clap::Arg) that are required by subcommands which print
+/// results that might be consumed by scripts.
+fn get_output_args() -> Vec<clap::Arg> {
+    vec![arg!(--output <FORMAT> "Output format of the results")
+        .value_parser(["table", "json", "yaml"])
+        .default_value("table")]
+}
+
 /// Generate all of the [arguments](clap::Arg) that are required by subcommands which work
 /// with channels and computer-board communication.
 fn get_channel_args() -> Vec<clap::Arg> {
@@ -91,6 +333,16 @@ fn get_probe_args() -> Vec<clap::Arg> {
         arg!(--chip <CHIP> "Explicitly specify the chip"),
         // Default of ProbeTargetInfo: 0
         arg!(--core <CORE> "Explicitly specify the core").value_parser(clap::value_parser!(usize)),
+        // Default of ProbeTargetInfo: probe default
+        arg!(--protocol <PROTOCOL> "Wire protocol used to talk to the chip")
+            .value_parser(["swd", "jtag"]),
+        // Default of ProbeTargetInfo: probe default
+        arg!(--speed <KHZ> "Wire protocol clock speed in kHz")
+            .value_parser(value_parser!(u32).range(1..)),
+        arg!(--"connect-under-reset" "Hold the chip in reset while attaching")
+            .action(clap::ArgAction::SetTrue),
+        arg!(--"allow-erase-all" "Allow the probe to erase the whole chip if needed")
+            .action(clap::ArgAction::SetTrue),
     ]
     .into_iter()
     .map(|arg| arg.conflicts_with_all(serial_args_ids.clone().collect::<Vec<_>>()))
@@ -99,7 +351,14 @@ fn get_probe_args() -> Vec<clap::Arg> {
 }
 
 fn get_probe_args_ids() -> Vec<clap::Id> {
-    vec!["chip".into(), "core".into()]
+    vec![
+        "chip".into(),
+        "core".into(),
+        "protocol".into(),
+        "speed".into(),
+        "connect-under-reset".into(),
+        "allow-erase-all".into(),
+    ]
 }
 
 fn get_serial_args() -> Vec<clap::Arg> {
@@ -109,8 +368,23 @@ fn get_serial_args() -> Vec<clap::Arg> {
         arg!(-p --port <PORT> "The serial port or device name to use"),
         // Default of SerialTargetInfo: 115200
         arg!(--"baud-rate" <RATE> "If using serial, set the target baud rate")
-            .value_parser(value_parser!(u32)),
-        // TODO: add more serial arguments to match with SerialTargetInfo
+            .value_parser(value_parser!(u32).range(1..)),
+        // Default of SerialTargetInfo: none
+        arg!(--parity <PARITY> "If using serial, set the parity checking mode")
+            .value_parser(["none", "odd", "even"]),
+        // Default of SerialTargetInfo: 1
+        arg!(--"stop-bits" <BITS> "If using serial, set the number of stop bits")
+            .value_parser(["1", "2"]),
+        // Default of SerialTargetInfo: none
+        arg!(--"flow-control" <MODE> "If using serial, set the flow control mode")
+            .value_parser(["none", "software", "hardware"]),
+        // Default of SerialTargetInfo: 500
+        arg!(--timeout <MS> "If using serial, set the read/write timeout in milliseconds")
+            .value_parser(value_parser!(u64).range(1..)),
+        arg!(--rts "If using serial, assert the RTS line after opening the port")
+            .action(clap::ArgAction::SetTrue),
+        arg!(--dtr "If using serial, assert the DTR line after opening the port")
+            .action(clap::ArgAction::SetTrue),
     ]
     .into_iter()
     .map(|arg| arg.conflicts_with_all(probe_args_ids.clone().collect::<Vec<_>>()))
@@ -119,14 +393,103 @@ fn get_serial_args() -> Vec<clap::Arg> {
 }
 
 fn get_serial_args_ids() -> Vec<clap::Id> {
-    vec!["port".into(), "baud-rate".into()]
+    vec![
+        "port".into(),
+        "baud-rate".into(),
+        "parity".into(),
+        "stop-bits".into(),
+        "flow-control".into(),
+        "timeout".into(),
+        "rts".into(),
+        "dtr".into(),
+    ]
+}
+
+/// Parse an address given either in hexadecimal (with a `0x` prefix) or in
+/// decimal.
+pub fn parse_address(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let result = match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => value.parse::<u64>(),
+    };
+
+    result.map_err(|_| format!("'{value}' is not a valid hexadecimal or decimal address"))
+}
+
+/// Parse a length in bytes, written the same way as an address.
+pub fn parse_length(value: &str) -> Result<usize, String> {
+    parse_address(value)
+        .ok()
+        .and_then(|length| usize::try_from(length).ok())
+        .ok_or(format!(
+            "'{value}' is not a valid hexadecimal or decimal length"
+        ))
+}
+
+/// Parse a size that must fit in a TBF header field.
+pub fn parse_size(value: &str) -> Result<u32, String> {
+    parse_address(value)
+        .ok()
+        .and_then(|size| u32::try_from(size).ok())
+        .ok_or(format!(
+            "'{value}' is not a valid hexadecimal or decimal size"
+        ))
+}
+
+/// Parse a driver permission written as `<driver>,<command>`.
+pub fn parse_permission(value: &str) -> Result<(u32, u32), String> {
+    let (driver, command) = value
+        .split_once(',')
+        .ok_or(format!("'{value}' is not written as <driver>,<command>"))?;
+    Ok((parse_size(driver)?, parse_size(command)?))
+}
+
+/// Parse a kernel version written as `<major>.<minor>`.
+pub fn parse_kernel_version(value: &str) -> Result<(u16, u16), String> {
+    value
+        .split_once('.')
+        .and_then(|(major, minor)| Some((major.parse().ok()?, minor.parse().ok()?)))
+        .ok_or(format!("'{value}' is not written as <major>.<minor>"))
+}
+
+/// Parse the type of a TLV entry, written the same way as an address.
+pub fn parse_tlv_type(value: &str) -> Result<u16, String> {
+    parse_address(value)
+        .ok()
+        .and_then(|tipe| u16::try_from(tipe).ok())
+        .ok_or(format!("'{value}' is not a valid TLV type"))
+}
+
+/// Parse a TLV entry written as `<type>,<hex value>`.
+pub fn parse_tlv(value: &str) -> Result<(u16, Vec<u8>), String> {
+    let (tipe, hex) = value
+        .split_once(',')
+        .ok_or(format!("'{value}' is not written as <type>,<hex value>"))?;
+    let hex = hex.trim_start_matches("0x");
+    let invalid = || format!("'{hex}' is not an even number of hexadecimal digits");
+    if hex.len() % 2 != 0 || !hex.is_ascii() {
+        return Err(invalid());
+    }
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+        .collect::<Result<_, _>>()
+        .map_err(|_| invalid())?;
+    Ok((parse_tlv_type(tipe)?, bytes))
 }
 
 pub fn validate(cmd: &mut Command, user_options: &ArgMatches) {
-    // Make 'chip' required if not using serial or board
+    // Make 'chip' required if not using serial, board or a flash image. Not
+    // every subcommand can read flash images.
+    let using_image = matches!(user_options.try_get_one::<String>("image"), Ok(Some(_)));
     if user_options.get_one::<String>("chip").is_none()
         && !user_options.get_one::<bool>("serial").unwrap_or(&false)
         && user_options.get_one::<String>("board").is_none()
+        && !using_image
     {
         cmd.error(
             ErrorKind::MissingRequiredArgument,
@@ -176,4 +539,39 @@ mod test {
         assert_eq!(probe_args_ids, probe_args);
         assert_eq!(serial_args_ids, serial_args);
     }
+
+    #[test]
+    fn addresses_parse_as_hex_or_decimal() {
+        use super::*;
+
+        assert_eq!(parse_address("0x00040000"), Ok(0x40000));
+        assert_eq!(parse_address("0X8040000"), Ok(0x08040000));
+        assert_eq!(parse_address("262144"), Ok(0x40000));
+        assert!(parse_address("0x").is_err());
+        assert!(parse_address("40000h").is_err());
+        assert!(parse_address("-1").is_err());
+
+        assert_eq!(parse_length("0x200"), Ok(512));
+        assert!(parse_length("512 bytes").is_err());
+
+        assert_eq!(parse_size("0x800"), Ok(2048));
+        assert!(parse_size("0x100000000").is_err());
+        assert_eq!(parse_permission("0x2,1"), Ok((2, 1)));
+        assert!(parse_permission("2").is_err());
+    }
+
+    #[test]
+    fn tlv_edits_parse() {
+        use super::*;
+
+        assert_eq!(parse_kernel_version("2.1"), Ok((2, 1)));
+        assert!(parse_kernel_version("2").is_err());
+        assert_eq!(parse_tlv_type("0x80"), Ok(0x80));
+        assert!(parse_tlv_type("0x10000").is_err());
+        assert_eq!(parse_tlv("8,02000100"), Ok((8, vec![2, 0, 1, 0])));
+        assert_eq!(parse_tlv("0x81,0xff"), Ok((0x81, vec![0xFF])));
+        assert!(parse_tlv("8,123").is_err());
+        assert!(parse_tlv("8,zz").is_err());
+        assert!(parse_tlv("8,é0").is_err());
+    }
 }
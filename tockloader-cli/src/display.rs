@@ -2,8 +2,51 @@
 // SPDX-License-Identifier: Apache-2.0 OR MIT
 // Copyright OXIDOS AUTOMOTIVE 2024.
 
+use anyhow::{Context, Result};
+use serde::Serialize;
+use tbf_parser::types::TbfHeaderTypes;
 use tockloader_lib::attributes::app_attributes::AppAttributes;
+use tockloader_lib::attributes::decode::DecodedAttribute;
+use tockloader_lib::attributes::memory_map::{MemoryMap, MemoryRegion, MemoryRegionKind};
 use tockloader_lib::attributes::system_attributes::SystemAttributes;
+use tockloader_lib::known_drivers::driver_name;
+use tockloader_lib::tabs::tab::Tab;
+
+/// The format in which commands print their results.
+#[derive(Clone, Copy)]
+pub enum OutputFormat {
+    /// Human-readable, colored output.
+    Table,
+    Json,
+    Yaml,
+}
+
+impl OutputFormat {
+    pub fn from_str(name: &str) -> Option<Self> {
+        match name {
+            "table" => Some(Self::Table),
+            "json" => Some(Self::Json),
+            "yaml" => Some(Self::Yaml),
+            _ => None,
+        }
+    }
+}
+
+/// Print `value` in one of the machine-readable formats. The table format has
+/// no generic representation, use the dedicated `print_*` functions instead.
+pub fn print_serialized<T: Serialize + ?Sized>(value: &T, format: OutputFormat) -> Result<()> {
+    let output = match format {
+        OutputFormat::Json => {
+            serde_json::to_string_pretty(value).context("Failed to serialize to JSON.")?
+        }
+        OutputFormat::Yaml => {
+            serde_yaml::to_string(value).context("Failed to serialize to YAML.")?
+        }
+        OutputFormat::Table => unreachable!("Table output has no generic representation"),
+    };
+    println!("{output}");
+    Ok(())
+}
 
 // TODO(george-cosma): Fix this
 #[allow(clippy::uninlined_format_args)]
@@ -17,7 +60,7 @@ pub async fn print_list(app_details: &[AppAttributes]) {
         println!("\x1b[0m\x1b[1;33m ┗━━━━━━━━━━━━━━━━┛");
         println!(
             "\n \x1b[1;32m Name:                {}",
-            details.tbf_header.get_package_name().unwrap()
+            details.tbf_header.get_package_name().unwrap_or("N/A")
         );
 
         println!(
@@ -46,193 +89,430 @@ pub async fn print_list(app_details: &[AppAttributes]) {
 #[allow(clippy::uninlined_format_args)]
 pub async fn print_info(app_details: &mut [AppAttributes], system_details: &mut SystemAttributes) {
     for (i, details) in app_details.iter().enumerate() {
-        println!("\n\x1b[0m\x1b[1;35m ┏━━━━━━━━━━━━━━━━┓");
-        println!(
-            "\x1b[0m\x1b[1;31m ┃ \x1b[0m\x1b[1;32m App_{:<9?} \x1b[0m\x1b[1;31m┃",
-            i
-        );
-        println!("\x1b[0m\x1b[1;33m ┗━━━━━━━━━━━━━━━━┛");
-        println!(
-            "\n \x1b[1;32m Name:                {}",
-            details.tbf_header.get_package_name().unwrap()
-        );
+        print_app_details(i, details);
+    }
 
-        println!(
-            " \x1b[1;32m Version:             {}",
-            details.tbf_header.get_binary_version()
-        );
+    // The kernel attributes are either all present or all missing, the
+    // latter happens when inspecting a dump of the app region only.
+    if system_details.sentinel.is_none() {
+        println!("\n\n\x1b[1;32m No kernel attributes found\x1b[0m\n\n");
+        return;
+    }
 
-        println!(
-            " \x1b[1;32m Enabled:             {}",
-            details.tbf_header.enabled()
-        );
+    println!("\n\n\x1b[1;32m Kernel Attributes");
+    println!(
+        "\x1b[1;32m     Sentinel:               {:<10}",
+        system_details.sentinel.clone().unwrap()
+    );
+    println!(
+        "\x1b[1;32m     Version:                {:<10}",
+        system_details.kernel_version.unwrap()
+    );
+    println!("\x1b[1;32m KATLV: APP Memory");
+    println!(
+        "\x1b[1;32m     app_memory_start:       {:<10}",
+        system_details.app_mem_start.unwrap()
+    );
+    println!(
+        "\x1b[1;32m     app_memory_len:         {:<10}",
+        system_details.app_mem_len.unwrap()
+    );
+    println!("\x1b[1;32m KATLV: Kernel Binary");
+    println!(
+        "\x1b[1;32m     kernel_binary_start:    {:<10}",
+        system_details.kernel_bin_start.unwrap()
+    );
+    println!(
+        "\x1b[1;32m     kernel_binary_len:      {:<10}\n\n",
+        system_details.kernel_bin_len.unwrap()
+    );
+}
 
-        println!(
-            " \x1b[1;32m Stricky:             {}",
-            details.tbf_header.sticky()
-        );
+// TODO(george-cosma): Fix this
+#[allow(clippy::uninlined_format_args)]
+fn print_app_details(i: usize, details: &AppAttributes) {
+    println!("\n\x1b[0m\x1b[1;35m ┏━━━━━━━━━━━━━━━━┓");
+    println!(
+        "\x1b[0m\x1b[1;31m ┃ \x1b[0m\x1b[1;32m App_{:<9?} \x1b[0m\x1b[1;31m┃",
+        i
+    );
+    println!("\x1b[0m\x1b[1;33m ┗━━━━━━━━━━━━━━━━┛");
+    println!(
+        "\n \x1b[1;32m Name:                {}",
+        details.tbf_header.get_package_name().unwrap_or("N/A")
+    );
 
-        println!(
-            " \x1b[1;32m Total_Size:          {}",
-            details.tbf_header.total_size()
-        );
+    println!(
+        " \x1b[1;32m Version:             {}",
+        details.tbf_header.get_binary_version()
+    );
 
-        println!(
-            " \x1b[1;32m Address in Flash:  {}",
-            system_details.appaddr.unwrap()
-        );
+    println!(
+        " \x1b[1;32m Enabled:             {}",
+        details.tbf_header.enabled()
+    );
 
-        println!(
-            " \x1b[1;32m    TBF version:    {}",
-            details.tbf_header.get_binary_version()
-        );
+    println!(
+        " \x1b[1;32m Stricky:             {}",
+        details.tbf_header.sticky()
+    );
 
-        println!(
-            " \x1b[1;32m    header_size:    {}",
-            details.tbf_header.header_size()
-        );
+    println!(
+        " \x1b[1;32m Total_Size:          {}",
+        details.tbf_header.total_size()
+    );
 
-        println!(
-            " \x1b[1;32m    total_size:     {}",
-            details.tbf_header.total_size()
-        );
+    println!(" \x1b[1;32m Address in Flash:  {:#x}", details.address);
 
-        println!(
-            " \x1b[1;32m    checksum:       {}",
-            details.tbf_header.checksum()
-        );
+    println!(
+        " \x1b[1;32m    TBF version:    {}",
+        details.tbf_header.get_binary_version()
+    );
 
-        println!(" \x1b[1;32m    flags:");
-        println!(
-            " \x1b[1;32m        enabled:        {}",
-            details.tbf_header.enabled()
-        );
+    println!(
+        " \x1b[1;32m    header_size:    {}",
+        details.tbf_header.header_size()
+    );
 
-        println!(
-            " \x1b[1;32m        sticky:         {}",
-            details.tbf_header.sticky()
-        );
+    println!(
+        " \x1b[1;32m    total_size:     {}",
+        details.tbf_header.total_size()
+    );
 
-        println!(" \x1b[1;32m    TVL: Main (1)",);
+    println!(
+        " \x1b[1;32m    checksum:       {}",
+        details.tbf_header.checksum()
+    );
 
-        println!(
-            " \x1b[1;32m        init_fn_offset:             {}",
-            details.tbf_header.get_init_function_offset()
-        );
+    println!(" \x1b[1;32m    flags:");
+    println!(
+        " \x1b[1;32m        enabled:        {}",
+        details.tbf_header.enabled()
+    );
 
-        println!(
-            " \x1b[1;32m        protected_size:             {}",
-            details.tbf_header.get_protected_size()
-        );
+    println!(
+        " \x1b[1;32m        sticky:         {}",
+        details.tbf_header.sticky()
+    );
 
-        println!(
-            " \x1b[1;32m        minimum_ram_size:           {}",
-            details.tbf_header.get_minimum_app_ram_size()
-        );
+    print_tlvs(details);
 
-        println!(" \x1b[1;32m    TVL: Program (9)",);
+    println!("\n \x1b[1;32m    Footer");
 
-        println!(
-            " \x1b[1;32m        init_fn_offset:             {}",
-            details.tbf_header.get_init_function_offset()
-        );
+    let mut total_footer_size: u32 = 0;
 
-        println!(
-            " \x1b[1;32m        protected_size:             {}",
-            details.tbf_header.get_protected_size()
-        );
+    //  Usage of +4 is a result of the structure of the Tock Binary Format (https://book.tockos.org/doc/tock_binary_format)
+    //  Because we need the real size of the footer including the type and length.
+    for footer_details in details.tbf_footers.iter() {
+        total_footer_size += footer_details.size + 4;
+    }
+
+    println!(
+        " \x1b[1;32m            footer_size:            {}",
+        total_footer_size
+    );
+
+    for (i, footer_details) in details.tbf_footers.iter().enumerate() {
+        println!(" \x1b[1;32m    Footer [{i}] TVL: Credentials");
 
         println!(
-            " \x1b[1;32m        minimum_ram_size:           {}",
-            details.tbf_header.get_minimum_app_ram_size()
+            " \x1b[1;32m        Type:                       {}",
+            footer_details.credentials.get_type()
         );
 
+        //  Usage of -4 is a result of the structure of the Tock Binary Format (https://book.tockos.org/doc/tock_binary_format)
+        //  Because we only need the size of the credentials without the type and length bytes.
         println!(
-            " \x1b[1;32m        binary_end_offset:          {}",
-            details.tbf_header.get_binary_end()
+            " \x1b[1;32m        Length:                     {}",
+            footer_details.size - 4
         );
 
         println!(
-            " \x1b[1;32m        app_version:                {}",
-            details.tbf_header.get_binary_version()
+            " \x1b[1;32m        Verification:               {}",
+            footer_details.check.as_str()
         );
+    }
+}
 
-        println!(" \x1b[1;32m    TVL: Package Name (3)",);
+pub fn print_tab(tab: &Tab, apps: &[(&str, AppAttributes)]) {
+    let metadata = tab.metadata();
+    let version = &metadata.minimum_tock_kernel_version;
 
-        println!(
-            " \x1b[1;32m        package_name:               {}",
-            details.tbf_header.get_package_name().unwrap()
-        );
+    println!("\n\x1b[1;32m Name:                   {}", metadata.name);
+    println!(
+        "\x1b[1;32m TAB version:            {}",
+        metadata.tab_version
+    );
+    println!("\x1b[1;32m Build date:             {}", metadata.build_date);
+    println!(
+        "\x1b[1;32m Minimum kernel version: {}.{}",
+        version.major, version.minor
+    );
+    println!(
+        "\x1b[1;32m Only for boards:        {}",
+        metadata
+            .only_for_boards
+            .as_ref()
+            .map_or("any".to_owned(), |boards| boards.join(", "))
+    );
+    for (key, value) in &metadata.unknown {
+        println!("\x1b[1;37m {key:<23} {value}");
+    }
 
-        println!(" \x1b[1;32m    TVL: Kernel Version (8)",);
+    for (i, (filename, details)) in apps.iter().enumerate() {
+        println!("\n\x1b[1;35m {filename}");
+        print_app_details(i, details);
+    }
+    print!("\x1b[0m");
+}
 
-        println!(
-            " \x1b[1;32m        kernel_major:               {}",
-            details.tbf_header.get_kernel_version().unwrap().0
-        );
+pub fn print_attributes(attributes: &[Option<DecodedAttribute>]) {
+    for (index, attribute) in attributes.iter().enumerate() {
+        match attribute {
+            Some(attribute) => println!(
+                "\x1b[1;32m [{index:02}] {:<8} = {}",
+                attribute.key, attribute.value
+            ),
+            None => println!("\x1b[1;37m [{index:02}] (empty)"),
+        }
+    }
+    print!("\x1b[0m");
+}
+
+/// Print `data` in the classic hexdump layout: address, 16 bytes in hex, then
+/// the same bytes as ASCII.
+pub fn print_hexdump(address: u64, data: &[u8]) {
+    for (index, line) in data.chunks(16).enumerate() {
+        let hex = line
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let ascii: String = line
+            .iter()
+            .map(|&byte| {
+                if byte.is_ascii_graphic() || byte == b' ' {
+                    byte as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
 
         println!(
-            " \x1b[1;32m        kernel_minor:               {}",
-            details.tbf_header.get_kernel_version().unwrap().1,
+            "\x1b[1;32m{:08x}\x1b[0m  {hex:<47}  |{ascii}|",
+            address + (index * 16) as u64
         );
+    }
+}
 
-        println!("\n \x1b[1;32m    Footer");
+pub fn print_memory_map(memory_map: &MemoryMap) {
+    println!("\n\x1b[1;35m Flash");
+    print_regions(&memory_map.flash);
 
-        let mut total_footer_size: u32 = 0;
+    println!("\n\x1b[1;35m App RAM");
+    print_regions(&memory_map.ram);
 
-        //  Usage of +4 is a result of the structure of the Tock Binary Format (https://book.tockos.org/doc/tock_binary_format)
-        //  Because we need the real size of the footer including the type and length.
-        for footer_details in details.tbf_footers.iter() {
-            total_footer_size += footer_details.size + 4;
-        }
+    println!(
+        "\n\x1b[1;32m App RAM required:  {} bytes",
+        memory_map.app_ram_required
+    );
+    if let Some(available) = memory_map.app_ram_available {
+        println!("\x1b[1;32m App RAM available: {available} bytes");
+    }
+    print!("\x1b[0m");
+}
 
+/// Print one line per region, with a bar proportional to the size of the
+/// region relative to the whole area.
+fn print_regions(regions: &[MemoryRegion]) {
+    const BAR_WIDTH: u64 = 40;
+
+    let (Some(first), Some(last)) = (regions.first(), regions.last()) else {
+        println!("\x1b[1;32m     (empty)");
+        return;
+    };
+    let total = (last.end() - first.start).max(1);
+
+    for region in regions {
+        let (color, label) = match &region.kind {
+            MemoryRegionKind::Bootloader => ("1;34", "Bootloader".to_owned()),
+            MemoryRegionKind::BootloaderAttributes => ("1;34", "Bootloader attributes".to_owned()),
+            MemoryRegionKind::Kernel => ("1;31", "Kernel".to_owned()),
+            MemoryRegionKind::App(name) => ("1;32", format!("App: {name}")),
+            MemoryRegionKind::Padding => ("1;33", "Padding".to_owned()),
+            MemoryRegionKind::Free => ("1;37", "Free".to_owned()),
+        };
+        let width = (region.size * BAR_WIDTH / total).max(1) as usize;
         println!(
-            " \x1b[1;32m            footer_size:            {}",
-            total_footer_size
+            "\x1b[{color}m {:#010x} - {:#010x} {:>10} B  {:<40} {label}",
+            region.start,
+            region.end(),
+            region.size,
+            "█".repeat(width),
         );
+    }
+}
 
-        for (i, footer_details) in details.tbf_footers.iter().enumerate() {
-            println!(" \x1b[1;32m    Footer [{i}] TVL: Credentials");
-
-            println!(
-                " \x1b[1;32m        Type:                       {}",
-                footer_details.credentials.get_type()
-            );
-
-            //  Usage of -4 is a result of the structure of the Tock Binary Format (https://book.tockos.org/doc/tock_binary_format)
-            //  Because we only need the size of the credentials without the type and length bytes.
-            println!(
-                " \x1b[1;32m        Length:                     {}",
-                footer_details.size - 4
-            );
+/// Print every TLV entry of the app's header, in the order they are stored.
+fn print_tlvs(details: &AppAttributes) {
+    let header = &details.tbf_header;
+
+    for tlv in details.tlvs.iter() {
+        let tipe = tlv.tipe;
+        match TbfHeaderTypes::try_from(tipe) {
+            Ok(TbfHeaderTypes::TbfHeaderMain) => {
+                println!(" \x1b[1;32m    TLV: Main ({tipe})");
+                print_program_fields(details);
+            }
+            Ok(TbfHeaderTypes::TbfHeaderProgram) => {
+                println!(" \x1b[1;32m    TLV: Program ({tipe})");
+                print_program_fields(details);
+                println!(
+                    " \x1b[1;32m        binary_end_offset:          {}",
+                    header.get_binary_end()
+                );
+                println!(
+                    " \x1b[1;32m        app_version:                {}",
+                    header.get_binary_version()
+                );
+            }
+            Ok(TbfHeaderTypes::TbfHeaderWriteableFlashRegions) => {
+                println!(" \x1b[1;32m    TLV: Writeable Flash Regions ({tipe})");
+                for i in 0..header.number_writeable_flash_regions() {
+                    let (offset, size) = header.get_writeable_flash_region(i);
+                    println!(
+                        " \x1b[1;32m        region [{i}]:                 offset {offset:#x}, size {size}"
+                    );
+                }
+            }
+            Ok(TbfHeaderTypes::TbfHeaderPackageName) => {
+                println!(" \x1b[1;32m    TLV: Package Name ({tipe})");
+                println!(
+                    " \x1b[1;32m        package_name:               {}",
+                    header.get_package_name().unwrap_or("N/A")
+                );
+            }
+            Ok(TbfHeaderTypes::TbfHeaderFixedAddresses) => {
+                println!(" \x1b[1;32m    TLV: Fixed Addresses ({tipe})");
+                println!(
+                    " \x1b[1;32m        start_process_flash:        {}",
+                    format_optional_address(header.get_fixed_address_flash())
+                );
+                println!(
+                    " \x1b[1;32m        start_process_ram:          {}",
+                    format_optional_address(header.get_fixed_address_ram())
+                );
+            }
+            Ok(TbfHeaderTypes::TbfHeaderPermissions) => {
+                println!(" \x1b[1;32m    TLV: Permissions ({tipe})");
+                for permission in header.get_all_command_permissions().unwrap_or_default() {
+                    let driver = permission.get_driver_number();
+                    println!(
+                        " \x1b[1;32m        driver {driver:#07x} ({}): commands {:#018x} (offset {})",
+                        driver_name(driver).unwrap_or("unknown"),
+                        permission.get_allowed_commands(),
+                        permission.get_offset(),
+                    );
+                }
+            }
+            Ok(TbfHeaderTypes::TbfHeaderStoragePermissions) => {
+                println!(" \x1b[1;32m    TLV: Storage Permissions ({tipe})");
+                println!(
+                    " \x1b[1;32m        write_id:                   {}",
+                    header
+                        .get_storage_write_id()
+                        .map_or("N/A".to_owned(), |id| format!("{id:#x}"))
+                );
+                if let Some(ids) = header.get_storage_read_ids() {
+                    println!(
+                        " \x1b[1;32m        read_ids:                   {}",
+                        format_ids(ids)
+                    );
+                }
+                if let Some(ids) = header.get_storage_modify_ids() {
+                    println!(
+                        " \x1b[1;32m        modify_ids:                 {}",
+                        format_ids(ids)
+                    );
+                }
+            }
+            Ok(TbfHeaderTypes::TbfHeaderKernelVersion) => {
+                if let Some((kernel_major, kernel_minor)) = header.get_kernel_version() {
+                    println!(" \x1b[1;32m    TLV: Kernel Version ({tipe})");
+                    println!(" \x1b[1;32m        kernel_major:               {kernel_major}");
+                    println!(" \x1b[1;32m        kernel_minor:               {kernel_minor}");
+                }
+            }
+            Ok(TbfHeaderTypes::TbfHeaderShortId) => {
+                println!(" \x1b[1;32m    TLV: Short ID ({tipe})");
+                println!(
+                    " \x1b[1;32m        short_id:                   {}",
+                    header
+                        .get_fixed_short_id()
+                        .map_or("locally unique".to_owned(), |id| format!("{id:#x}"))
+                );
+            }
+            Ok(TbfHeaderTypes::TbfHeaderPicOption1) => {
+                if let Some(pic) = header.get_pic_option1() {
+                    println!(" \x1b[1;32m    TLV: PIC Option 1 ({tipe})");
+                    for (name, value) in [
+                        ("text_offset", pic.get_text_offset()),
+                        ("data_offset", pic.get_data_offset()),
+                        ("data_size", pic.get_data_size()),
+                        ("bss_memory_offset", pic.get_bss_memory_offset()),
+                        ("bss_size", pic.get_bss_size()),
+                        ("relocation_data_offset", pic.get_relocation_data_offset()),
+                        ("relocation_data_size", pic.get_relocation_data_size()),
+                        ("got_offset", pic.get_got_offset()),
+                        ("got_size", pic.get_got_size()),
+                        ("minimum_stack_length", pic.get_minimum_stack_length()),
+                    ] {
+                        println!(" \x1b[1;32m        {:<28}{value}", format!("{name}:"));
+                    }
+                }
+            }
+            _ => {
+                println!(" \x1b[1;32m    TLV: Unknown ({tipe})");
+                println!(
+                    " \x1b[1;32m        offset:                     {}",
+                    tlv.offset
+                );
+                println!(
+                    " \x1b[1;32m        length:                     {}",
+                    tlv.length
+                );
+            }
         }
     }
+}
 
-    println!("\n\n\x1b[1;32m Kernel Attributes");
-    println!(
-        "\x1b[1;32m     Sentinel:               {:<10}",
-        system_details.sentinel.clone().unwrap()
-    );
-    println!(
-        "\x1b[1;32m     Version:                {:<10}",
-        system_details.kernel_version.unwrap()
-    );
-    println!("\x1b[1;32m KATLV: APP Memory");
+/// Fields shared by the Main and Program TLVs.
+fn print_program_fields(details: &AppAttributes) {
     println!(
-        "\x1b[1;32m     app_memory_start:       {:<10}",
-        system_details.app_mem_start.unwrap()
-    );
-    println!(
-        "\x1b[1;32m     app_memory_len:         {:<10}",
-        system_details.app_mem_len.unwrap()
+        " \x1b[1;32m        init_fn_offset:             {}",
+        details.tbf_header.get_init_function_offset()
     );
-    println!("\x1b[1;32m KATLV: Kernel Binary");
     println!(
-        "\x1b[1;32m     kernel_binary_start:    {:<10}",
-        system_details.kernel_bin_start.unwrap()
+        " \x1b[1;32m        protected_size:             {}",
+        details.tbf_header.get_protected_size()
     );
     println!(
-        "\x1b[1;32m     kernel_binary_len:      {:<10}\n\n",
-        system_details.kernel_bin_len.unwrap()
+        " \x1b[1;32m        minimum_ram_size:           {}",
+        details.tbf_header.get_minimum_app_ram_size()
     );
 }
+
+fn format_optional_address(address: Option<u32>) -> String {
+    address.map_or("N/A".to_owned(), |address| format!("{address:#010x}"))
+}
+
+fn format_ids(ids: &[u32]) -> String {
+    if ids.is_empty() {
+        return "none".to_owned();
+    }
+    ids.iter()
+        .map(|id| format!("{id:#x}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,69 @@
+// Licensed under the Apache License, Version 2.0 or the MIT License.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+// Copyright OXIDOS AUTOMOTIVE 2024.
+
+use std::fs::File;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use clap::{crate_version, ArgMatches};
+use tracing::debug;
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, Layer};
+
+/// Print the logs of tockloader on stderr, at the level asked for with
+/// `--debug` and `-v`, and write all of them to the `--log-file`, if any.
+///
+/// Other crates only ever log warnings, so the log file stays readable.
+pub fn init_logging(matches: &ArgMatches) -> Result<()> {
+    let mut verbosity = matches.get_count("verbose");
+    if matches.get_flag("debug") {
+        verbosity = verbosity.max(2);
+    }
+    let level = match verbosity {
+        0 => LevelFilter::WARN,
+        1 => LevelFilter::INFO,
+        2 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    };
+    let stderr = fmt::layer()
+        .with_writer(io::stderr)
+        .with_filter(tockloader_targets(level));
+
+    let log_file = match matches.get_one::<PathBuf>("log-file") {
+        Some(path) => {
+            let file = File::create(path)
+                .with_context(|| format!("Failed to create log file {}.", path.display()))?;
+            Some(
+                fmt::layer()
+                    .with_ansi(false)
+                    .with_writer(Mutex::new(file))
+                    .with_filter(tockloader_targets(LevelFilter::TRACE)),
+            )
+        }
+        None => None,
+    };
+
+    tracing_subscriber::registry()
+        .with(stderr)
+        .with(log_file)
+        .init();
+
+    debug!(
+        version = crate_version!(),
+        args = ?std::env::args().collect::<Vec<_>>(),
+        "Started tockloader"
+    );
+    Ok(())
+}
+
+fn tockloader_targets(level: LevelFilter) -> Targets {
+    Targets::new()
+        .with_target("tockloader", level)
+        .with_target("tockloader_lib", level)
+        .with_default(LevelFilter::WARN)
+}
@@ -5,21 +5,41 @@
 mod cli;
 mod display;
 mod known_boards;
+mod logging;
+mod progress;
+
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
 
 use anyhow::{Context, Result};
+use clap::error::ErrorKind;
 use clap::ArgMatches;
 use cli::make_cli;
+use display::OutputFormat;
 use known_boards::KnownBoardNames;
+use probe_rs::probe::WireProtocol;
+use tbf_parser::builder::{TbfBuildError, TbfHeaderEditor};
+use tbf_parser::types::{TbfFooterV2CredentialsType, TbfHeaderV2KernelVersion};
 use tockloader_lib::board_settings::BoardSettings;
 use tockloader_lib::connection::{
     Connection, ProbeRSConnection, ProbeTargetInfo, SerialConnection, SerialTargetInfo,
     TockloaderConnection,
 };
+use tockloader_lib::credentials::{sign_tbf, Credential};
+use tockloader_lib::flash_image::FlashImage;
+use tockloader_lib::kernel_image::KernelImage;
 use tockloader_lib::known_boards::KnownBoard;
+use tockloader_lib::tabs::elf::{convert_elf, ElfConversionOptions};
+use tockloader_lib::tabs::metadata::TockKernelVersion;
 use tockloader_lib::tabs::tab::Tab;
+use tockloader_lib::tabs::writer::TabWriter;
 use tockloader_lib::{
-    list_debug_probes, list_serial_ports, CommandInfo, CommandInstall, CommandList,
+    list_debug_probes, list_serial_ports, CommandAttributes, CommandEraseApps, CommandFlashKernel,
+    CommandInfo, CommandInstall, CommandList, CommandMemoryMap, CommandReadWrite,
 };
+use tokio_serial::{FlowControl, Parity, StopBits};
+use toml::value::Datetime;
 
 fn get_serial_target_info(user_options: &ArgMatches) -> SerialTargetInfo {
     let board = get_known_board(user_options);
@@ -33,6 +53,39 @@ fn get_serial_target_info(user_options: &ArgMatches) -> SerialTargetInfo {
         result.baud_rate = *baud_rate;
     }
 
+    if let Some(parity) = user_options.get_one::<String>("parity") {
+        result.parity = match parity.as_str() {
+            "none" => Parity::None,
+            "odd" => Parity::Odd,
+            "even" => Parity::Even,
+            _ => unreachable!("Expected validation to catch invalid parity"),
+        };
+    }
+
+    if let Some(stop_bits) = user_options.get_one::<String>("stop-bits") {
+        result.stop_bits = match stop_bits.as_str() {
+            "1" => StopBits::One,
+            "2" => StopBits::Two,
+            _ => unreachable!("Expected validation to catch invalid stop bits"),
+        };
+    }
+
+    if let Some(flow_control) = user_options.get_one::<String>("flow-control") {
+        result.flow_control = match flow_control.as_str() {
+            "none" => FlowControl::None,
+            "software" => FlowControl::Software,
+            "hardware" => FlowControl::Hardware,
+            _ => unreachable!("Expected validation to catch invalid flow control"),
+        };
+    }
+
+    if let Some(timeout) = user_options.get_one::<u64>("timeout") {
+        result.timeout = Duration::from_millis(*timeout);
+    }
+
+    result.request_to_send = user_options.get_flag("rts");
+    result.data_terminal_ready = user_options.get_flag("dtr");
+
     result
 }
 
@@ -53,6 +106,21 @@ fn get_probe_target_info(user_options: &ArgMatches) -> ProbeTargetInfo {
         result.core = *core;
     }
 
+    if let Some(protocol) = user_options.get_one::<String>("protocol") {
+        result.protocol = Some(
+            protocol
+                .parse::<WireProtocol>()
+                .expect("Expected validation to catch invalid protocol"),
+        );
+    }
+
+    if let Some(speed) = user_options.get_one::<u32>("speed") {
+        result.speed_khz = Some(*speed);
+    }
+
+    result.connect_under_reset = user_options.get_flag("connect-under-reset");
+    result.allow_erase_all = user_options.get_flag("allow-erase-all");
+
     result
 }
 
@@ -62,19 +130,166 @@ fn get_board_settings(user_options: &ArgMatches) -> BoardSettings {
         return board.get_settings();
     }
 
-    let result = BoardSettings::default();
+    let mut result = BoardSettings::default();
 
-    if let Some(_strat_address_str) = user_options.get_one::<String>("app-address") {
-        todo!()
+    if let Some(start_address) = user_options.get_one::<u64>("app-address") {
+        result.start_address = *start_address;
     }
 
     result
 }
 
+fn get_output_format(user_options: &ArgMatches) -> OutputFormat {
+    user_options
+        .get_one::<String>("output")
+        .map(|format| {
+            OutputFormat::from_str(format).expect("Expected validation to catch invalid format")
+        })
+        .unwrap_or(OutputFormat::Table)
+}
+
+/// Open the flash image to inspect instead of a board, if one was given.
+fn get_flash_image(
+    user_options: &ArgMatches,
+    settings: &BoardSettings,
+) -> Result<Option<FlashImage>> {
+    let Some(path) = user_options.get_one::<String>("image") else {
+        return Ok(None);
+    };
+    let base_address = user_options
+        .get_one::<u64>("image-base")
+        .copied()
+        .unwrap_or(settings.start_address);
+
+    FlashImage::open(Path::new(path), base_address)
+        .map(Some)
+        .context("Failed to use provided flash image.")
+}
+
 fn using_serial(user_options: &ArgMatches) -> bool {
     *user_options.get_one::<bool>("serial").unwrap_or(&false)
 }
 
+/// Start a TAB from the metadata given on the command line.
+fn new_tab_writer(user_options: &ArgMatches) -> TabWriter {
+    let mut writer = TabWriter::new(
+        user_options.get_one::<String>("name").unwrap().clone(),
+        *user_options
+            .get_one::<TockKernelVersion>("kernel-version")
+            .unwrap(),
+    );
+    if let Some(build_date) = user_options.get_one::<Datetime>("build-date") {
+        writer.set_build_date(*build_date);
+    }
+    writer.set_only_for_boards(
+        user_options
+            .get_one::<String>("boards")
+            .map(|boards| boards.split(',').map(|s| s.trim().to_owned()).collect()),
+    );
+    writer
+}
+
+fn get_elf_conversion_options(user_options: &ArgMatches) -> ElfConversionOptions {
+    let mut options =
+        ElfConversionOptions::new(user_options.get_one::<String>("name").unwrap().clone());
+    options.kernel_version = user_options
+        .get_one::<TockKernelVersion>("kernel-version")
+        .copied();
+    options.stack_size = user_options.get_one::<u32>("stack").copied();
+    options.app_heap_size = *user_options.get_one::<u32>("app-heap").unwrap();
+    options.kernel_heap_size = *user_options.get_one::<u32>("kernel-heap").unwrap();
+    options.permissions = user_options
+        .get_many::<(u32, u32)>("permission")
+        .map_or(Vec::new(), |permissions| permissions.copied().collect());
+    options.protected_region_size = user_options
+        .get_one::<u32>("protected-region-size")
+        .copied();
+    options.minimum_footer_size = *user_options.get_one::<u32>("minimum-footer-size").unwrap();
+    options.binary_version = if user_options.get_flag("main-header") {
+        None
+    } else {
+        user_options.get_one::<u32>("binary-version").copied()
+    };
+    options
+}
+
+fn get_credentials(user_options: &ArgMatches) -> Result<Vec<Credential>> {
+    let mut credentials = Vec::new();
+    if user_options.get_flag("sha256") {
+        credentials.push(Credential::Sha256);
+    }
+    if user_options.get_flag("sha384") {
+        credentials.push(Credential::Sha384);
+    }
+    if user_options.get_flag("sha512") {
+        credentials.push(Credential::Sha512);
+    }
+    for key in user_options
+        .get_many::<String>("rsa-key")
+        .into_iter()
+        .flatten()
+    {
+        credentials.push(
+            Credential::read_rsa_key(Path::new(key))
+                .with_context(|| format!("Failed to read {key}."))?,
+        );
+    }
+    Ok(credentials)
+}
+
+/// Apply the edits requested with `tbf edit` to a TBF header.
+fn edit_tbf_header(
+    editor: &mut TbfHeaderEditor,
+    user_options: &ArgMatches,
+) -> Result<(), TbfBuildError> {
+    if let Some(name) = user_options.get_one::<String>("package-name") {
+        editor.set_package_name(name)?;
+    }
+    if let Some(size) = user_options.get_one::<u32>("minimum-ram") {
+        editor.set_minimum_ram_size(*size);
+    }
+    if let Some((major, minor)) = user_options.get_one::<(u16, u16)>("kernel-version") {
+        editor.set_kernel_version(Some(TbfHeaderV2KernelVersion::new(*major, *minor)))?;
+    }
+    for tipe in user_options
+        .get_many::<u16>("remove-tlv")
+        .unwrap_or_default()
+    {
+        editor.remove_tlv(*tipe)?;
+    }
+    for (tipe, value) in user_options
+        .get_many::<(u16, Vec<u8>)>("set-tlv")
+        .unwrap_or_default()
+    {
+        editor.set_tlv(*tipe, value)?;
+    }
+    Ok(())
+}
+
+/// Refuse to drop the credentials of a signed file unless `tbf edit` was
+/// given `--drop-credentials`, and warn when they are dropped.
+fn check_dropped_credentials(
+    path: &str,
+    dropped: &[TbfFooterV2CredentialsType],
+    user_options: &ArgMatches,
+) -> Result<()> {
+    if dropped.is_empty() {
+        return Ok(());
+    }
+    let formats = dropped
+        .iter()
+        .map(|format| format!("{format:?}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    if !user_options.get_flag("drop-credentials") {
+        anyhow::bail!(
+            "Editing {path} would drop its {formats} credentials, pass --drop-credentials to edit it anyway."
+        );
+    }
+    eprintln!("warning: dropped the {formats} credentials of {path}, use `sign` to sign it again.");
+    Ok(())
+}
+
 fn get_known_board(user_options: &ArgMatches) -> Option<Box<dyn KnownBoard>> {
     user_options.get_one::<String>("board").map(|board| {
         match KnownBoardNames::from_str(board).expect("validation to ensure valid board") {
@@ -129,6 +344,7 @@ async fn open_connection(user_options: &ArgMatches) -> Result<TockloaderConnecti
 async fn main() -> Result<()> {
     let mut cmd = cli::make_cli();
     let matches = cmd.get_matches_mut();
+    logging::init_logging(&matches)?;
 
     match matches.subcommand() {
         Some(("listen", sub_matches)) => {
@@ -140,24 +356,295 @@ async fn main() -> Result<()> {
         Some(("list", sub_matches)) => {
             cli::validate(&mut cmd, sub_matches);
 
-            let mut conn = open_connection(sub_matches).await?;
             let settings = get_board_settings(sub_matches);
 
-            let app_details = conn.list(&settings).await.context("Failed to list apps.")?;
+            let app_details = match get_flash_image(sub_matches, &settings)? {
+                Some(mut image) => image.list(&settings).await,
+                None => open_connection(sub_matches).await?.list(&settings).await,
+            }
+            .context("Failed to list apps.")?;
 
-            display::print_list(&app_details).await;
+            match get_output_format(sub_matches) {
+                OutputFormat::Table => display::print_list(&app_details).await,
+                format => display::print_serialized(&app_details, format)?,
+            }
         }
         Some(("info", sub_matches)) => {
+            cli::validate(&mut cmd, sub_matches);
+            let settings = get_board_settings(sub_matches);
+
+            let mut attributes = match get_flash_image(sub_matches, &settings)? {
+                Some(mut image) => image.info(&settings).await,
+                None => open_connection(sub_matches).await?.info(&settings).await,
+            }
+            .context("Failed to get data from the board.")?;
+
+            match get_output_format(sub_matches) {
+                OutputFormat::Table => {
+                    display::print_info(&mut attributes.apps, &mut attributes.system).await
+                }
+                format => display::print_serialized(&attributes, format)?,
+            }
+        }
+        Some(("memory-map", sub_matches)) => {
+            cli::validate(&mut cmd, sub_matches);
+            let mut conn = open_connection(sub_matches).await?;
+            let settings = get_board_settings(sub_matches);
+
+            let memory_map = conn
+                .memory_map(&settings)
+                .await
+                .context("Failed to compute the memory map.")?;
+
+            match get_output_format(sub_matches) {
+                OutputFormat::Table => display::print_memory_map(&memory_map),
+                format => display::print_serialized(&memory_map, format)?,
+            }
+
+            if memory_map.ram_overcommitted() {
+                eprintln!(
+                    "warning: apps need {} bytes of RAM, but only {} bytes are available.",
+                    memory_map.app_ram_required,
+                    memory_map.app_ram_available.unwrap_or(0)
+                );
+            }
+        }
+        Some(("attr", sub_matches)) => {
+            let (attr_command, attr_matches) = sub_matches
+                .subcommand()
+                .expect("Expected clap to require a subcommand");
+            cli::validate(&mut cmd, attr_matches);
+            let mut conn = open_connection(attr_matches).await?;
+
+            match attr_command {
+                "list" => {
+                    let attributes = conn
+                        .list_attributes()
+                        .await
+                        .context("Failed to read attributes.")?;
+                    display::print_attributes(&attributes);
+                }
+                "get" => {
+                    let key = attr_matches.get_one::<String>("KEY").unwrap();
+                    let value = conn
+                        .get_attribute(key)
+                        .await
+                        .context("Failed to read attributes.")?
+                        .with_context(|| format!("No attribute with key '{key}' found."))?;
+                    println!("{value}");
+                }
+                "set" => {
+                    let key = attr_matches.get_one::<String>("KEY").unwrap();
+                    let value = attr_matches.get_one::<String>("VALUE").unwrap();
+                    conn.set_attribute(key, value)
+                        .await
+                        .context("Failed to set attribute.")?;
+                }
+                "remove" => {
+                    let key = attr_matches.get_one::<String>("KEY").unwrap();
+                    conn.remove_attribute(key)
+                        .await
+                        .context("Failed to remove attribute.")?;
+                }
+                _ => unreachable!("Expected clap to reject unknown subcommands"),
+            }
+        }
+        Some(("flash-kernel", sub_matches)) => {
+            cli::validate(&mut cmd, sub_matches);
+            let path = sub_matches.get_one::<String>("KERNEL").unwrap();
+            let kernel = KernelImage::open(
+                Path::new(path),
+                sub_matches.get_one::<u64>("kernel-address").copied(),
+            )
+            .context("Failed to use provided kernel image.")?;
+
+            let mut conn = open_connection(sub_matches).await?;
+            let settings = get_board_settings(sub_matches);
+
+            conn.flash_kernel(
+                &settings,
+                kernel,
+                sub_matches.get_flag("preserve-apps"),
+                &progress::progress_bars(),
+            )
+            .await
+            .context("Failed to flash kernel.")?;
+        }
+        Some(("read", sub_matches)) => {
+            cli::validate(&mut cmd, sub_matches);
+            let address = *sub_matches.get_one::<u64>("ADDRESS").unwrap();
+            let length = *sub_matches.get_one::<usize>("LENGTH").unwrap();
+
+            let mut conn = open_connection(sub_matches).await?;
+            let data = conn
+                .read_memory(address, length)
+                .await
+                .context("Failed to read from the board.")?;
+
+            match sub_matches.get_one::<String>("file") {
+                Some(path) => fs::write(path, data).context("Failed to save data.")?,
+                None => display::print_hexdump(address, &data),
+            }
+        }
+        Some(("write", sub_matches)) => {
+            cli::validate(&mut cmd, sub_matches);
+            let address = *sub_matches.get_one::<u64>("ADDRESS").unwrap();
+            let data = fs::read(sub_matches.get_one::<String>("FILE").unwrap())
+                .context("Failed to read provided file.")?;
+
+            let mut conn = open_connection(sub_matches).await?;
+            conn.write_memory(address, &data, &progress::progress_bars())
+                .await
+                .context("Failed to write to the board.")?;
+        }
+        Some(("dump", sub_matches)) => {
             cli::validate(&mut cmd, sub_matches);
             let mut conn = open_connection(sub_matches).await?;
             let settings = get_board_settings(sub_matches);
 
-            let mut attributes = conn
-                .info(&settings)
+            let image = conn
+                .dump_apps(&settings)
                 .await
-                .context("Failed to get data from the board.")?;
+                .context("Failed to read apps from the board.")?;
+            fs::write(sub_matches.get_one::<String>("FILE").unwrap(), image)
+                .context("Failed to save image.")?;
+        }
+        Some(("inspect-tab", sub_matches)) => {
+            let tab = Tab::open(sub_matches.get_one::<String>("TAB").unwrap().to_string())
+                .context("Failed to use provided tab file.")?;
+
+            let mut apps = Vec::new();
+            for file in tab.tbf_files() {
+                let app = file
+                    .parse()
+                    .with_context(|| format!("Failed to parse {}.", file.filename()))?;
+                apps.push((file.filename(), app));
+            }
+
+            display::print_tab(&tab, &apps);
+        }
+        Some(("tab", sub_matches)) => {
+            let (tab_command, tab_matches) = sub_matches
+                .subcommand()
+                .expect("Expected clap to require a subcommand");
+            let path = tab_matches.get_one::<String>("TAB").unwrap();
+
+            let mut writer = match tab_command {
+                "create" => new_tab_writer(tab_matches),
+                _ => TabWriter::from_tab(
+                    Tab::open(path.to_string()).context("Failed to use provided tab file.")?,
+                ),
+            };
+
+            match tab_command {
+                "create" | "add-arch" => {
+                    for tbf in tab_matches.get_many::<String>("TBF").unwrap() {
+                        let filename = Path::new(tbf)
+                            .file_name()
+                            .and_then(|name| name.to_str())
+                            .with_context(|| format!("Invalid TBF path {tbf}."))?;
+                        let data =
+                            fs::read(tbf).with_context(|| format!("Failed to read {tbf}."))?;
+                        writer
+                            .add_tbf(filename.to_owned(), data)
+                            .with_context(|| format!("Failed to add {tbf}."))?;
+                    }
+                }
+                "remove-arch" => {
+                    for arch in tab_matches.get_many::<String>("ARCH").unwrap() {
+                        writer
+                            .remove_arch(arch)
+                            .with_context(|| format!("Failed to remove {arch}."))?;
+                    }
+                }
+                _ => unreachable!("Expected clap to reject unknown subcommands"),
+            }
 
-            display::print_info(&mut attributes.apps, &mut attributes.system).await;
+            writer
+                .save(Path::new(path))
+                .context("Failed to save tab file.")?;
+        }
+        Some(("convert", sub_matches)) => {
+            let output = sub_matches.get_one::<String>("OUTPUT").unwrap();
+            let elfs: Vec<&String> = sub_matches.get_many::<String>("ELF").unwrap().collect();
+            let options = get_elf_conversion_options(sub_matches);
+
+            if output.ends_with(".tbf") {
+                let [elf] = elfs[..] else {
+                    cmd.error(
+                        ErrorKind::TooManyValues,
+                        "a single ELF is needed to create a TBF file.",
+                    )
+                    .exit();
+                };
+                let data = fs::read(elf).with_context(|| format!("Failed to read {elf}."))?;
+                let app = convert_elf(&data, &options)
+                    .with_context(|| format!("Failed to convert {elf}."))?;
+                fs::write(output, app.tbf).context("Failed to save TBF file.")?;
+            } else {
+                let mut writer = new_tab_writer(sub_matches);
+                for elf in elfs {
+                    let filename = Path::new(elf)
+                        .file_name()
+                        .and_then(|name| name.to_str())
+                        .with_context(|| format!("Invalid ELF path {elf}."))?;
+                    let data = fs::read(elf).with_context(|| format!("Failed to read {elf}."))?;
+                    writer
+                        .add_elf(filename, data, &options)
+                        .with_context(|| format!("Failed to convert {elf}."))?;
+                }
+                writer
+                    .save(Path::new(output))
+                    .context("Failed to save tab file.")?;
+            }
+        }
+        Some(("sign", sub_matches)) => {
+            let path = sub_matches.get_one::<String>("FILE").unwrap();
+            let output = sub_matches.get_one::<String>("output").unwrap_or(path);
+            let credentials = get_credentials(sub_matches)?;
+
+            if path.ends_with(".tab") {
+                let mut writer = TabWriter::from_tab(
+                    Tab::open(path.to_string()).context("Failed to use provided tab file.")?,
+                );
+                writer
+                    .sign(&credentials)
+                    .context("Failed to sign tab file.")?;
+                writer
+                    .save(Path::new(output))
+                    .context("Failed to save tab file.")?;
+            } else {
+                let data = fs::read(path).with_context(|| format!("Failed to read {path}."))?;
+                let signed = sign_tbf(&data, &credentials).context("Failed to sign TBF file.")?;
+                fs::write(output, signed).context("Failed to save TBF file.")?;
+            }
+        }
+        Some(("tbf", sub_matches)) => {
+            let (_, edit_matches) = sub_matches
+                .subcommand()
+                .expect("Expected clap to require a subcommand");
+            let path = edit_matches.get_one::<String>("FILE").unwrap();
+            let output = edit_matches.get_one::<String>("output").unwrap_or(path);
+
+            if path.ends_with(".tab") {
+                let mut writer = TabWriter::from_tab(
+                    Tab::open(path.to_string()).context("Failed to use provided tab file.")?,
+                );
+                let dropped = writer
+                    .edit_headers(|editor| edit_tbf_header(editor, edit_matches))
+                    .context("Failed to edit tab file.")?;
+                check_dropped_credentials(path, &dropped, edit_matches)?;
+                writer
+                    .save(Path::new(output))
+                    .context("Failed to save tab file.")?;
+            } else {
+                let data = fs::read(path).with_context(|| format!("Failed to read {path}."))?;
+                let mut editor = TbfHeaderEditor::new(&data).context("Failed to edit TBF file.")?;
+                edit_tbf_header(&mut editor, edit_matches).context("Failed to edit TBF file.")?;
+                let (edited, dropped) = editor.build().context("Failed to edit TBF file.")?;
+                check_dropped_credentials(path, &dropped, edit_matches)?;
+                fs::write(output, edited).context("Failed to save TBF file.")?;
+            }
         }
         Some(("install", sub_matches)) => {
             cli::validate(&mut cmd, sub_matches);
@@ -167,10 +654,29 @@ async fn main() -> Result<()> {
             let mut conn = open_connection(sub_matches).await?;
             let settings = get_board_settings(sub_matches);
 
-            conn.install_app(&settings, tab_file)
+            conn.install_app(&settings, tab_file, &progress::progress_bars())
                 .await
                 .context("Failed to install app.")?;
         }
+        Some(("erase-apps", sub_matches)) => {
+            cli::validate(&mut cmd, sub_matches);
+            let mut conn = open_connection(sub_matches).await?;
+            let settings = get_board_settings(sub_matches);
+
+            let kept = conn
+                .erase_apps(
+                    &settings,
+                    sub_matches.get_flag("force"),
+                    &progress::progress_bars(),
+                )
+                .await
+                .context("Failed to erase apps.")?;
+            let sticky = kept.iter().filter(|app| app.tbf_header.is_app()).count();
+            if sticky > 0 {
+                println!("Kept {sticky} sticky app(s), use --force to erase them:");
+                display::print_list(&kept).await;
+            }
+        }
         _ => {
             println!("Could not run the provided subcommand.");
             _ = make_cli().print_help();
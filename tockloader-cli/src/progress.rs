@@ -0,0 +1,76 @@
+// Licensed under the Apache License, Version 2.0 or the MIT License.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+// Copyright OXIDOS AUTOMOTIVE 2024.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use indicatif::{ProgressBar, ProgressStyle};
+use tockloader_lib::progress::{CancellationToken, Progress, ProgressEvent, ProgressObserver};
+
+/// Shows each phase of a command as a progress bar on stderr, or as a spinner
+/// if its size is not known. Nothing is shown if stderr is not a terminal.
+#[derive(Default)]
+struct ProgressBars {
+    bar: Mutex<Option<ProgressBar>>,
+}
+
+impl ProgressObserver for ProgressBars {
+    fn on_progress(&self, event: ProgressEvent) {
+        let mut bar = self.bar.lock().expect("Progress bar lock is poisoned");
+        match event {
+            ProgressEvent::Started { phase, total } => {
+                let new_bar = match total {
+                    Some(total) => ProgressBar::new(total).with_style(
+                        ProgressStyle::with_template(
+                            "{msg:10} [{bar:40}] {bytes}/{total_bytes} ({eta})",
+                        )
+                        .expect("Template is valid")
+                        .progress_chars("=> "),
+                    ),
+                    None => {
+                        let spinner = ProgressBar::new_spinner().with_style(
+                            ProgressStyle::with_template("{msg:10} {spinner} {bytes}")
+                                .expect("Template is valid"),
+                        );
+                        spinner.enable_steady_tick(Duration::from_millis(100));
+                        spinner
+                    }
+                };
+                new_bar.set_message(phase.to_string());
+                if let Some(old_bar) = bar.replace(new_bar) {
+                    old_bar.abandon();
+                }
+            }
+            ProgressEvent::Advanced { done, .. } => {
+                if let Some(bar) = bar.as_ref() {
+                    bar.set_position(done);
+                }
+            }
+            ProgressEvent::Finished { .. } => {
+                if let Some(bar) = bar.take() {
+                    bar.finish();
+                }
+            }
+        }
+    }
+}
+
+/// Progress reporting for a command that writes to the board. The first
+/// Ctrl-C cancels the command before its next page, the second one exits
+/// right away.
+pub fn progress_bars() -> Progress {
+    let cancellation = CancellationToken::new();
+    let token = cancellation.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            eprintln!("Cancelling, press Ctrl-C again to exit right away.");
+            token.cancel();
+        }
+        if tokio::signal::ctrl_c().await.is_ok() {
+            std::process::exit(130);
+        }
+    });
+
+    Progress::new(ProgressBars::default()).with_cancellation(cancellation)
+}
@@ -2,220 +2,703 @@
 // SPDX-License-Identifier: Apache-2.0 OR MIT
 // Copyright OXIDOS AUTOMOTIVE 2024.
 
-use probe_rs::{Core, MemoryInterface};
-
-use tbf_parser::parse::{parse_tbf_footer, parse_tbf_header, parse_tbf_header_lengths};
-use tbf_parser::types::{TbfFooterV2Credentials, TbfHeader};
+use async_trait::async_trait;
+use serde::{Serialize, Serializer};
+
+use tbf_parser::owned::{
+    parse_owned_tbf_header, parse_owned_tbf_list, OwnedStoragePermissions, OwnedTbfHeader,
+};
+use tbf_parser::parse::{
+    parse_tbf_header_lengths, parse_tbf_header_tlvs, TbfBinaryRange, TbfListEntry,
+};
+use tbf_parser::types::{
+    TbfFooterV2Credentials, TbfHeaderDriverPermission, TbfHeaderV2KernelVersion,
+    TbfHeaderV2PicOption1, TbfHeaderV2UnknownTlv, TbfHeaderV2WriteableFlashRegion, TbfParseError,
+};
+use tbf_parser::verify::{check_credentials, CredentialsCheck};
 use tbf_parser::{self};
-use tokio_serial::SerialStream;
+use tracing::warn;
 
-use crate::bootloader_serial::{issue_command, Command, Response};
-use crate::errors::TockloaderError;
+use crate::errors::{ParseError, TockloaderError};
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct AppAttributes {
-    pub tbf_header: TbfHeader,
+    /// Address in flash where the TBF object of the app starts.
+    pub address: u64,
+    /// Size of the whole TBF object, including footers. Unlike
+    /// `OwnedTbfHeader::total_size`, this is also set for padding.
+    pub size: u32,
+    #[serde(rename = "header", serialize_with = "serialize_tbf_header")]
+    pub tbf_header: OwnedTbfHeader,
+    /// Every TLV entry found in the header, in the order they are stored,
+    /// including the ones `tbf_header` does not understand.
+    pub tlvs: Vec<TbfTlvInfo>,
+    #[serde(rename = "footers")]
     pub tbf_footers: Vec<TbfFooter>,
 }
 
-#[derive(Debug)]
+/// Type and location of a TLV entry inside a TBF header.
+#[derive(Debug, Serialize)]
+pub struct TbfTlvInfo {
+    #[serde(rename = "type")]
+    pub tipe: u16,
+    /// Offset from the start of the TBF header.
+    pub offset: u32,
+    pub length: u16,
+}
+
+impl TbfTlvInfo {
+    fn read_all(header_data: &[u8], address: u64) -> Result<Vec<TbfTlvInfo>, TockloaderError> {
+        parse_tbf_header_tlvs(header_data)
+            .map(|tlv| {
+                tlv.map(|tlv| TbfTlvInfo {
+                    tipe: tlv.tipe,
+                    offset: tlv.offset as u32,
+                    length: tlv.value.len() as u16,
+                })
+                .map_err(|source| ParseError::App { address, source }.into())
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Serialize)]
 pub struct TbfFooter {
+    #[serde(rename = "type", serialize_with = "serialize_credentials_type")]
     pub credentials: TbfFooterV2Credentials,
     pub size: u32,
+    /// Whether the footer matches the header and binary of the app.
+    #[serde(serialize_with = "serialize_credentials_check")]
+    pub check: CredentialsCheck,
+}
+
+/// Serializable view of an [`OwnedTbfHeader`]. The values computed from
+/// several fields are flattened here, and the entries of the header are
+/// serialized with the types of the parser crate. The field order is part of
+/// the machine-readable output format, keep it stable.
+#[derive(Serialize)]
+struct TbfHeaderView<'a> {
+    is_app: bool,
+    package_name: Option<&'a str>,
+    enabled: bool,
+    sticky: bool,
+    header_size: u16,
+    total_size: u32,
+    checksum: u32,
+    binary_version: u32,
+    minimum_ram_size: u32,
+    init_fn_offset: u32,
+    protected_size: u32,
+    binary_end_offset: u32,
+    kernel_version: Option<TbfHeaderV2KernelVersion>,
+    fixed_address_flash: Option<u32>,
+    fixed_address_ram: Option<u32>,
+    short_id: Option<u32>,
+    writeable_flash_regions: &'a [TbfHeaderV2WriteableFlashRegion],
+    permissions: Option<&'a [TbfHeaderDriverPermission]>,
+    storage_permissions: Option<&'a OwnedStoragePermissions>,
+    pic_option1: Option<&'a TbfHeaderV2PicOption1>,
+    unknown_tlvs: &'a [TbfHeaderV2UnknownTlv],
+}
+
+fn serialize_tbf_header<S: Serializer>(
+    header: &OwnedTbfHeader,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    TbfHeaderView {
+        is_app: header.is_app(),
+        package_name: header.get_package_name(),
+        enabled: header.enabled(),
+        sticky: header.sticky(),
+        header_size: header.header_size(),
+        total_size: header.total_size(),
+        checksum: header.checksum(),
+        binary_version: header.get_binary_version(),
+        minimum_ram_size: header.get_minimum_app_ram_size(),
+        init_fn_offset: header.get_init_function_offset(),
+        protected_size: header.get_protected_size(),
+        binary_end_offset: header.get_binary_end(),
+        kernel_version: header
+            .get_kernel_version()
+            .map(|(major, minor)| TbfHeaderV2KernelVersion::new(major, minor)),
+        fixed_address_flash: header.get_fixed_address_flash(),
+        fixed_address_ram: header.get_fixed_address_ram(),
+        short_id: header.get_fixed_short_id().map(|id| id.get()),
+        writeable_flash_regions: header.get_writeable_flash_regions(),
+        permissions: header.get_all_command_permissions(),
+        storage_permissions: header.get_storage_permissions(),
+        pic_option1: header.get_pic_option1(),
+        unknown_tlvs: header.get_unknown_tlvs(),
+    }
+    .serialize(serializer)
+}
+
+fn serialize_credentials_type<S: Serializer>(
+    credentials: &TbfFooterV2Credentials,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(credentials.get_type())
+}
+
+fn serialize_credentials_check<S: Serializer>(
+    check: &CredentialsCheck,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(check.as_str())
 }
 
 impl TbfFooter {
     pub fn new(credentials: TbfFooterV2Credentials, size: u32) -> TbfFooter {
-        TbfFooter { credentials, size }
+        TbfFooter {
+            credentials,
+            size,
+            check: CredentialsCheck::Unchecked,
+        }
+    }
+
+    /// Whether the footer holds a hash, which can only be checked against the
+    /// whole binary.
+    fn is_hash(&self) -> bool {
+        matches!(
+            self.credentials,
+            TbfFooterV2Credentials::SHA256(_)
+                | TbfFooterV2Credentials::SHA384(_)
+                | TbfFooterV2Credentials::SHA512(_)
+        )
     }
 }
 
 // TODO(george-cosma): Could take advantages of the trait rework
 
 impl AppAttributes {
-    pub(crate) fn new(header_data: TbfHeader, footers_data: Vec<TbfFooter>) -> AppAttributes {
+    pub(crate) fn new(
+        address: u64,
+        size: u32,
+        header_data: OwnedTbfHeader,
+        tlvs: Vec<TbfTlvInfo>,
+        footers_data: Vec<TbfFooter>,
+    ) -> AppAttributes {
         AppAttributes {
+            address,
+            size,
             tbf_header: header_data,
+            tlvs,
             tbf_footers: footers_data,
         }
     }
 
-    // TODO: Document this function
-    pub(crate) fn read_apps_data_probe(
-        board_core: &mut Core,
+    /// Check the footers against the integrity region of the app: its header
+    /// and binary.
+    pub(crate) fn verify_footers(&mut self, integrity_region: &[u8]) {
+        for footer in &mut self.tbf_footers {
+            footer.check = check_credentials(integrity_region, &footer.credentials);
+        }
+    }
+
+    /// Parse the TBF object at the start of `data`, which is located at
+    /// `address`. Returns `None` if `data` does not start with a TBF header.
+    pub fn read_app_data(data: &[u8], address: u64) -> Result<Option<Self>, TockloaderError> {
+        parse_owned_tbf_list(data, address)
+            .next()
+            .map_or(Ok(None), AppAttributes::from_list_entry)
+    }
+
+    /// Get the attributes of an entry of the app linked list. Returns `None`
+    /// at the end of the list.
+    fn from_list_entry(
+        entry: TbfListEntry<'_, OwnedTbfHeader>,
+    ) -> Result<Option<Self>, TockloaderError> {
+        let (address, object) = match entry {
+            TbfListEntry::App { address, object } | TbfListEntry::Padding { address, object } => {
+                (address, object)
+            }
+            TbfListEntry::Invalid { address, error, .. } => {
+                return Err(ParseError::App {
+                    address,
+                    source: error,
+                }
+                .into());
+            }
+            // The lengths of the next object were read, but the data ends
+            // before the object does.
+            TbfListEntry::Truncated { address, needed } if needed > 8 => {
+                return Err(ParseError::App {
+                    address,
+                    source: TbfParseError::NotEnoughFlash,
+                }
+                .into());
+            }
+            TbfListEntry::Truncated { .. } | TbfListEntry::End { .. } => return Ok(None),
+        };
+
+        let tlvs = TbfTlvInfo::read_all(object.header_data, address)?;
+        let footers = object
+            .footers
+            .map(|footer| TbfFooter::new(footer.credentials, footer.length))
+            .collect();
+
+        // `parse_owned_tbf()` checked that the binary fits in the object.
+        let region = &object.data[0..object.header.get_binary_end() as usize];
+        let size = object.data.len() as u32;
+        let mut details = AppAttributes::new(address, size, object.header, tlvs, footers);
+        details.verify_footers(region);
+
+        Ok(Some(details))
+    }
+
+    /// Read the apps from a flash image that starts at `base_address`,
+    /// beginning with the app at `addr`.
+    pub(crate) fn read_apps_data_image(
+        image: &[u8],
+        base_address: u64,
+        addr: u64,
+    ) -> Result<Vec<AppAttributes>, TockloaderError> {
+        let Some(data) = addr
+            .checked_sub(base_address)
+            .and_then(|offset| image.get(offset as usize..))
+        else {
+            return Ok(vec![]);
+        };
+
+        parse_owned_tbf_list(data, addr)
+            .map_while(|entry| {
+                AppAttributes::end_at_invalid(AppAttributes::from_list_entry(entry)).transpose()
+            })
+            .collect()
+    }
+
+    /// Read the apps from the flash of a board, beginning with the app at
+    /// `addr`.
+    pub(crate) async fn read_apps_data(
+        flash: &mut impl FlashReader,
         addr: u64,
     ) -> Result<Vec<AppAttributes>, TockloaderError> {
         let mut appaddr: u64 = addr;
-        let mut apps_counter = 0;
         let mut apps_details: Vec<AppAttributes> = vec![];
 
-        loop {
-            let mut appdata = vec![0u8; 8];
-
-            board_core
-                .read(appaddr, &mut appdata)
-                .map_err(TockloaderError::ProbeRsReadError)?;
-
-            let tbf_version: u16;
-            let header_size: u16;
-            let total_size: u32;
-
-            match parse_tbf_header_lengths(
-                &appdata
-                    .try_into()
-                    .expect("Buffer length must be at least 8 bytes long."),
-            ) {
-                Ok(data) => {
-                    tbf_version = data.0;
-                    header_size = data.1;
-                    total_size = data.2;
-                }
-                _ => return Ok(apps_details),
-            };
-
-            let mut header_data = vec![0u8; header_size as usize];
+        while let Some(details) =
+            AppAttributes::end_at_invalid(AppAttributes::read_list_entry(flash, appaddr).await)?
+        {
+            appaddr += details.size as u64;
+            apps_details.push(details);
+        }
+        Ok(apps_details)
+    }
 
-            board_core
-                .read(appaddr, &mut header_data)
-                .map_err(TockloaderError::ProbeRsReadError)?;
-            let header = parse_tbf_header(&header_data, tbf_version)
-                .map_err(TockloaderError::ParsingError)?;
+    /// End the app list at an entry that can not be parsed, so that the apps
+    /// before it are still listed. Other errors, like failing to read the
+    /// flash, are returned.
+    fn end_at_invalid(
+        entry: Result<Option<Self>, TockloaderError>,
+    ) -> Result<Option<Self>, TockloaderError> {
+        match entry {
+            Err(TockloaderError::Parse(ParseError::App { address, source })) => {
+                warn!("App data at {address:#x} could not be parsed, skipping it and the apps after it: {source}");
+                Ok(None)
+            }
+            entry => entry,
+        }
+    }
 
-            let binary_end_offset = header.get_binary_end();
+    /// Read the entry of the app linked list at `address`, or `None` at the
+    /// end of the list. Only the header and footers of the object are read,
+    /// and its binary if a footer holds a hash of it. The entry is then built
+    /// by `parse_owned_tbf_list()`, with the bytes that were not read left
+    /// zeroed.
+    #[tracing::instrument(level = "debug", skip(flash), fields(address = format_args!("{address:#x}")))]
+    async fn read_list_entry(
+        flash: &mut impl FlashReader,
+        address: u64,
+    ) -> Result<Option<AppAttributes>, TockloaderError> {
+        let flash_end = flash.flash_end();
+        let fits = |size: u32| flash_end.is_none_or(|end| address + size as u64 <= end);
+        if !fits(8) {
+            return Ok(None);
+        }
 
-            let mut footers: Vec<TbfFooter> = vec![];
-            let total_footers_size = total_size - binary_end_offset;
-            let mut footer_offset = binary_end_offset;
-            let mut footer_number = 0;
+        let lengths = flash.read(address, 8).await?;
+        let needed = match parse_owned_tbf_list(&lengths, address).next() {
+            Some(TbfListEntry::Truncated { needed, .. }) => needed,
+            entry => return entry.map_or(Ok(None), AppAttributes::from_list_entry),
+        };
+        if !fits(needed) {
+            return Err(ParseError::App {
+                address,
+                source: TbfParseError::NotEnoughFlash,
+            }
+            .into());
+        }
 
-            loop {
-                let mut appfooter =
-                    vec![0u8; (total_footers_size - (footer_offset - binary_end_offset)) as usize];
+        let mut data = vec![0; needed as usize];
+        data[0..8].copy_from_slice(&lengths);
+        let binary_end = Self::read_header_and_footers(flash, address, &mut data).await?;
+
+        let mut details = parse_owned_tbf_list(&data, address)
+            .next()
+            .map_or(Ok(None), AppAttributes::from_list_entry)?;
+        if let (Some(details), Some(binary_end)) = (&mut details, binary_end) {
+            if details.tbf_footers.iter().any(TbfFooter::is_hash) {
+                let header_size = details.tbf_header.header_size() as usize;
+                let binary = flash
+                    .read(address + header_size as u64, binary_end - header_size)
+                    .await?;
+                data[header_size..binary_end].copy_from_slice(&binary);
+                details.verify_footers(&data[0..binary_end]);
+            }
+        }
+        Ok(details)
+    }
 
-                board_core
-                    .read(appaddr + footer_offset as u64, &mut appfooter)
-                    .map_err(TockloaderError::ProbeRsReadError)?;
+    /// Read the header and footers of the TBF object at `address` into
+    /// `data`, which holds its lengths. Returns where its binary ends, if the
+    /// header is valid enough to tell.
+    async fn read_header_and_footers(
+        flash: &mut impl FlashReader,
+        address: u64,
+        data: &mut [u8],
+    ) -> Result<Option<usize>, TockloaderError> {
+        let Ok((version, header_size, _)) = parse_tbf_header_lengths(
+            data[0..8]
+                .try_into()
+                .expect("Buffer length must be at least 8 bytes long."),
+        ) else {
+            return Ok(None);
+        };
+        let header_size = header_size as usize;
+        data[0..header_size].copy_from_slice(&flash.read(address, header_size).await?);
+
+        let Ok(header) = parse_owned_tbf_header(&data[0..header_size], version) else {
+            return Ok(None);
+        };
+        let Ok(Some((_, binary_end))) = header.binary_range() else {
+            return Ok(None);
+        };
+        let binary_end = binary_end as usize;
+        if binary_end < header_size || binary_end > data.len() {
+            return Ok(None);
+        }
+        let footers = flash
+            .read(address + binary_end as u64, data.len() - binary_end)
+            .await?;
+        data[binary_end..].copy_from_slice(&footers);
+        Ok(Some(binary_end))
+    }
+}
 
-                let footer_info =
-                    parse_tbf_footer(&appfooter).map_err(TockloaderError::ParsingError)?;
+/// Flash that the apps of a board can be read from, a few bytes at a time.
+#[async_trait]
+pub(crate) trait FlashReader: Send {
+    async fn read(&mut self, address: u64, length: usize) -> Result<Vec<u8>, TockloaderError>;
 
-                footers.insert(footer_number, TbfFooter::new(footer_info.0, footer_info.1));
+    /// End of the flash bank holding the apps, if known. Nothing is read past
+    /// it, so a corrupt TBF object can not make the reader run off the flash.
+    fn flash_end(&self) -> Option<u64>;
+}
 
-                footer_number += 1;
-                footer_offset += footer_info.1 + 4;
+#[cfg(test)]
+mod test {
+    use tbf_parser::builder::{TbfHeaderBuilder, TbfHeaderEditor};
+    use tbf_parser::types::TbfHeaderV2FixedAddresses;
 
-                if footer_offset == total_size {
-                    break;
-                }
-            }
+    use super::*;
+    use crate::credentials::{sign_tbf, Credential};
 
-            let details: AppAttributes = AppAttributes::new(header, footers);
+    const START: u64 = 0x40000;
 
-            apps_details.insert(apps_counter, details);
-            apps_counter += 1;
-            appaddr += total_size as u64;
-        }
+    /// Flash held in memory, which records every read.
+    struct MemoryFlash {
+        data: Vec<u8>,
+        reads: Vec<(u64, usize)>,
     }
 
-    // TODO: Document this function
-    pub(crate) async fn read_apps_data_serial(
-        port: &mut SerialStream,
-        addr: u64,
-    ) -> Result<Vec<AppAttributes>, TockloaderError> {
-        let mut appaddr: u64 = addr;
-        let mut apps_counter = 0;
-        let mut apps_details: Vec<AppAttributes> = vec![];
-
-        loop {
-            let mut pkt = (appaddr as u32).to_le_bytes().to_vec();
-            let length = (8_u16).to_le_bytes().to_vec();
-            for i in length {
-                pkt.push(i);
-            }
+    #[async_trait]
+    impl FlashReader for MemoryFlash {
+        async fn read(&mut self, address: u64, length: usize) -> Result<Vec<u8>, TockloaderError> {
+            let offset = (address - START) as usize;
+            assert!(
+                offset + length <= self.data.len(),
+                "Read past the flash end"
+            );
+            self.reads.push((address, length));
+            Ok(self.data[offset..offset + length].to_vec())
+        }
 
-            let (_, appdata) =
-                issue_command(port, Command::ReadRange, pkt, true, 8, Response::ReadRange).await?;
-
-            let tbf_version: u16;
-            let header_size: u16;
-            let total_size: u32;
-
-            match parse_tbf_header_lengths(
-                &appdata[0..8]
-                    .try_into()
-                    .expect("Buffer length must be at least 8 bytes long."),
-            ) {
-                Ok(data) => {
-                    tbf_version = data.0;
-                    header_size = data.1;
-                    total_size = data.2;
-                }
-                _ => break,
-            };
+        fn flash_end(&self) -> Option<u64> {
+            Some(START + self.data.len() as u64)
+        }
+    }
 
-            let mut pkt = (appaddr as u32).to_le_bytes().to_vec();
-            let length = (header_size).to_le_bytes().to_vec();
-            for i in length {
-                pkt.push(i);
-            }
+    fn app(name: &str) -> Vec<u8> {
+        let mut builder = TbfHeaderBuilder::new();
+        builder.set_program_version(None);
+        builder.set_package_name(name).unwrap();
+        builder.build(&[0x5A; 0x200], 0, 0x400).unwrap()
+    }
 
-            let (_, header_data) = issue_command(
-                port,
-                Command::ReadRange,
-                pkt,
-                true,
-                header_size.into(),
-                Response::ReadRange,
-            )
-            .await?;
+    fn flash(tbfs: &[Vec<u8>]) -> MemoryFlash {
+        let mut data = tbfs.concat();
+        data.extend([0xFF; 16]);
+        MemoryFlash {
+            data,
+            reads: Vec::new(),
+        }
+    }
 
-            let header = parse_tbf_header(&header_data, tbf_version)
-                .map_err(TockloaderError::ParsingError)?;
-            let binary_end_offset = header.get_binary_end();
-
-            let mut footers: Vec<TbfFooter> = vec![];
-            let total_footers_size = total_size - binary_end_offset;
-            let mut footer_offset = binary_end_offset;
-            let mut footer_number = 0;
-
-            loop {
-                let mut pkt = (appaddr as u32 + footer_offset).to_le_bytes().to_vec();
-                let length = ((total_footers_size - (footer_offset - binary_end_offset)) as u16)
-                    .to_le_bytes()
-                    .to_vec();
-                for i in length {
-                    pkt.push(i);
-                }
+    /// Bytes read from `[start, end)`.
+    fn read_between(flash: &MemoryFlash, start: u64, end: u64) -> usize {
+        flash
+            .reads
+            .iter()
+            .filter(|(address, _)| (start..end).contains(address))
+            .map(|(_, length)| length)
+            .sum()
+    }
 
-                let (_, appfooter) = issue_command(
-                    port,
-                    Command::ReadRange,
-                    pkt,
-                    true,
-                    (total_footers_size - (footer_offset - binary_end_offset)) as usize,
-                    Response::ReadRange,
-                )
-                .await?;
+    #[tokio::test]
+    async fn reads_binary_only_to_check_hashes() {
+        let unsigned = app("blink");
+        let signed = sign_tbf(&app("hello"), &[Credential::Sha256]).unwrap();
+        let mut flash = flash(&[unsigned.clone(), signed.clone()]);
+
+        let apps = AppAttributes::read_apps_data(&mut flash, START)
+            .await
+            .unwrap();
+        let expected = AppAttributes::read_apps_data_image(&flash.data, START, START).unwrap();
+        assert_eq!(apps.len(), 2);
+        for (app, expected) in apps.iter().zip(&expected) {
+            assert_eq!(app.address, expected.address);
+            assert_eq!(app.size, expected.size);
+            assert_eq!(
+                app.tbf_footers
+                    .iter()
+                    .map(|footer| (footer.size, footer.check))
+                    .collect::<Vec<_>>(),
+                expected
+                    .tbf_footers
+                    .iter()
+                    .map(|footer| (footer.size, footer.check))
+                    .collect::<Vec<_>>(),
+            );
+        }
+        assert_eq!(apps[1].tbf_footers[0].check, CredentialsCheck::Valid);
 
-                let footer_info =
-                    parse_tbf_footer(&appfooter).map_err(TockloaderError::ParsingError)?;
+        let second = START + unsigned.len() as u64;
+        assert!(read_between(&flash, START, second) < 0x200);
+        assert!(read_between(&flash, second, second + signed.len() as u64) > 0x200);
+    }
 
-                footers.insert(footer_number, TbfFooter::new(footer_info.0, footer_info.1));
+    #[tokio::test]
+    async fn detects_tampered_binary() {
+        let mut signed = sign_tbf(&app("hello"), &[Credential::Sha256]).unwrap();
+        signed[0x100] ^= 0xFF;
+        let mut flash = flash(&[signed]);
 
-                footer_number += 1;
-                footer_offset += footer_info.1 + 4;
+        let apps = AppAttributes::read_apps_data(&mut flash, START)
+            .await
+            .unwrap();
+        assert_eq!(apps[0].tbf_footers[0].check, CredentialsCheck::Invalid);
+    }
 
-                if footer_offset == total_size {
-                    break;
-                }
-            }
+    #[tokio::test]
+    async fn does_not_read_past_flash_end() {
+        let mut tbf = app("blink");
+        tbf[4..8].copy_from_slice(&0x10_0000u32.to_le_bytes());
+        let mut flash = flash(&[app("hello"), tbf]);
+
+        let apps = AppAttributes::read_apps_data(&mut flash, START)
+            .await
+            .unwrap();
+        assert_eq!(apps.len(), 1);
+        assert_eq!(flash.reads.last(), Some(&(START + 0x400, 8)));
+    }
 
-            let details: AppAttributes = AppAttributes::new(header, footers);
+    #[tokio::test]
+    async fn lists_apps_before_invalid_entry() {
+        let mut corrupt = app("blink");
+        // The package name is covered by the header checksum.
+        let name = corrupt.windows(5).position(|w| w == b"blink").unwrap();
+        corrupt[name] = b'B';
+        let mut flash = flash(&[app("hello"), corrupt, app("sensors")]);
+
+        let apps = AppAttributes::read_apps_data(&mut flash, START)
+            .await
+            .unwrap();
+        assert_eq!(apps.len(), 1);
+        assert_eq!(apps[0].tbf_header.get_package_name(), Some("hello"));
+
+        let apps = AppAttributes::read_apps_data_image(&flash.data, START, START).unwrap();
+        assert_eq!(apps.len(), 1);
+    }
 
-            apps_details.insert(apps_counter, details);
-            apps_counter += 1;
-            appaddr += total_size as u64;
-        }
-        Ok(apps_details)
+    /// The JSON of `list` and `info` is read by scripts, so the names and
+    /// order of its fields must not change by accident.
+    #[test]
+    fn json_output_is_stable() {
+        let mut builder = TbfHeaderBuilder::new();
+        builder.set_program_version(Some(3));
+        builder.set_package_name("blink").unwrap();
+        builder.set_sticky(true);
+        builder.set_minimum_ram_size(0x1000);
+        builder.set_kernel_version(Some(TbfHeaderV2KernelVersion::new(2, 1)));
+        builder.set_fixed_addresses(Some(TbfHeaderV2FixedAddresses::new(
+            0x2000_8000,
+            0xFFFF_FFFF,
+        )));
+        builder
+            .add_writeable_flash_region(TbfHeaderV2WriteableFlashRegion::new(0x80, 0x40))
+            .unwrap();
+        builder
+            .add_permission(TbfHeaderDriverPermission::new(0x1, 0, 0b11))
+            .unwrap();
+        // Leave room in the protected trailer for the entries added below.
+        let tbf = builder.build(&[0x5A; 0x100], 0x80, 0x400).unwrap();
+
+        // The builder has no setters for the remaining entries.
+        let mut editor = TbfHeaderEditor::new(&tbf).unwrap();
+        let mut storage = 7u32.to_le_bytes().to_vec();
+        storage.extend(1u16.to_le_bytes());
+        storage.extend(3u32.to_le_bytes());
+        storage.extend(0u16.to_le_bytes());
+        editor.set_tlv(7, &storage).unwrap();
+        let pic: Vec<u8> = (1..=10u32).flat_map(u32::to_le_bytes).collect();
+        editor.set_tlv(4, &pic).unwrap();
+        editor.set_tlv(10, &42u32.to_le_bytes()).unwrap();
+        editor.set_tlv(0x99, &[1, 2]).unwrap();
+        let (tbf, _) = editor.build().unwrap();
+
+        let tbf = sign_tbf(&tbf, &[Credential::Sha256]).unwrap();
+        let app = AppAttributes::read_app_data(&tbf, START).unwrap().unwrap();
+
+        assert_eq!(
+            serde_json::to_string_pretty(&app).unwrap(),
+            r#"{
+  "address": 262144,
+  "size": 1024,
+  "header": {
+    "is_app": true,
+    "package_name": "blink",
+    "enabled": true,
+    "sticky": true,
+    "header_size": 184,
+    "total_size": 1024,
+    "checksum": 2970289262,
+    "binary_version": 3,
+    "minimum_ram_size": 4096,
+    "init_fn_offset": 236,
+    "protected_size": 236,
+    "binary_end_offset": 492,
+    "kernel_version": {
+      "major": 2,
+      "minor": 1
+    },
+    "fixed_address_flash": null,
+    "fixed_address_ram": 536903680,
+    "short_id": 42,
+    "writeable_flash_regions": [
+      {
+        "writeable_flash_region_offset": 128,
+        "writeable_flash_region_size": 64
+      }
+    ],
+    "permissions": [
+      {
+        "driver_number": 1,
+        "offset": 0,
+        "allowed_commands": 3
+      }
+    ],
+    "storage_permissions": {
+      "write_id": 7,
+      "read_ids": [
+        3
+      ],
+      "modify_ids": []
+    },
+    "pic_option1": {
+      "text_offset": 1,
+      "data_offset": 2,
+      "data_size": 3,
+      "bss_memory_offset": 4,
+      "bss_size": 5,
+      "relocation_data_offset": 6,
+      "relocation_data_size": 7,
+      "got_offset": 8,
+      "got_size": 9,
+      "minimum_stack_length": 10
+    },
+    "unknown_tlvs": [
+      {
+        "tipe": 153,
+        "offset": 176,
+        "length": 2
+      }
+    ]
+  },
+  "tlvs": [
+    {
+      "type": 9,
+      "offset": 16,
+      "length": 20
+    },
+    {
+      "type": 3,
+      "offset": 40,
+      "length": 5
+    },
+    {
+      "type": 2,
+      "offset": 52,
+      "length": 8
+    },
+    {
+      "type": 5,
+      "offset": 64,
+      "length": 8
+    },
+    {
+      "type": 6,
+      "offset": 76,
+      "length": 18
+    },
+    {
+      "type": 8,
+      "offset": 100,
+      "length": 4
+    },
+    {
+      "type": 7,
+      "offset": 108,
+      "length": 12
+    },
+    {
+      "type": 4,
+      "offset": 124,
+      "length": 40
+    },
+    {
+      "type": 10,
+      "offset": 168,
+      "length": 4
+    },
+    {
+      "type": 153,
+      "offset": 176,
+      "length": 2
+    }
+  ],
+  "footers": [
+    {
+      "type": "SHA256",
+      "size": 36,
+      "check": "valid"
+    },
+    {
+      "type": "Reserved",
+      "size": 488,
+      "check": "unchecked"
+    }
+  ]
+}"#
+        );
     }
 }
@@ -2,6 +2,19 @@
 // SPDX-License-Identifier: Apache-2.0 OR MIT
 // Copyright OXIDOS AUTOMOTIVE 2024.
 
+use crate::errors::{BoardError, TockloaderError};
+
+/// Address of the first bootloader attribute slot.
+pub const ATTRIBUTES_ADDRESS: u64 = 0x600;
+/// Number of attribute slots kept by the bootloader.
+pub const ATTRIBUTES_COUNT: usize = 16;
+/// Size of one attribute slot: key, value length and value.
+pub const ATTRIBUTE_SIZE: usize = 64;
+/// Maximum length of an attribute key, in bytes.
+pub const ATTRIBUTE_KEY_MAX: usize = 8;
+/// Maximum length of an attribute value, in bytes.
+pub const ATTRIBUTE_VALUE_MAX: usize = 55;
+
 #[derive(Debug)]
 pub struct DecodedAttribute {
     pub key: String,
@@ -17,33 +30,28 @@ impl DecodedAttribute {
     }
 }
 
-// TODO: explain what is happening here
+/// Decode a bootloader attribute slot. Returns `None` for empty slots (value
+/// length of 0 or, for erased flash, larger than the maximum) and for slots
+/// that do not hold valid UTF-8.
 pub(crate) fn decode_attribute(step: &[u8]) -> Option<DecodedAttribute> {
-    let raw_key = &step[0..8];
-
-    let decoder_key = utf8_decode::Decoder::new(raw_key.iter().cloned());
-
-    let mut key = String::new();
-    for n in decoder_key {
-        key.push(n.expect("Error getting key for attributes."));
-    }
+    let vlen = step[ATTRIBUTE_KEY_MAX];
 
-    key = key.trim_end_matches('\0').to_string();
-    let vlen = step[8];
-
-    if vlen > 55 || vlen == 0 {
+    if vlen as usize > ATTRIBUTE_VALUE_MAX || vlen == 0 {
         return None;
     }
-    let raw_value = &step[9..(9 + vlen as usize)];
-    let decoder_value = utf8_decode::Decoder::new(raw_value.iter().cloned());
 
-    let mut value = String::new();
+    let raw_key = &step[0..ATTRIBUTE_KEY_MAX];
+    let key = utf8_decode::Decoder::new(raw_key.iter().cloned())
+        .collect::<Result<String, _>>()
+        .ok()?;
+    let key = key.trim_end_matches('\0').to_string();
 
-    for n in decoder_value {
-        value.push(n.expect("Error getting key for attributes."));
-    }
+    let raw_value = &step[(ATTRIBUTE_KEY_MAX + 1)..(ATTRIBUTE_KEY_MAX + 1 + vlen as usize)];
+    let value = utf8_decode::Decoder::new(raw_value.iter().cloned())
+        .collect::<Result<String, _>>()
+        .ok()?;
+    let value = value.trim_end_matches('\0').to_string();
 
-    value = value.trim_end_matches('\0').to_string();
     Some(DecodedAttribute::new(key, value))
 }
 
@@ -57,3 +65,75 @@ pub(crate) fn bytes_to_string(raw: &[u8]) -> String {
     }
     string
 }
+
+/// Encode an attribute into the layout of a bootloader attribute slot: 8 bytes
+/// of zero-padded key, one byte of value length, and up to 55 bytes of value.
+pub(crate) fn encode_attribute(
+    key: &str,
+    value: &str,
+) -> Result<[u8; ATTRIBUTE_SIZE], TockloaderError> {
+    if key.is_empty() || key.len() > ATTRIBUTE_KEY_MAX {
+        return Err(BoardError::InvalidAttribute(format!(
+            "Key must be between 1 and {ATTRIBUTE_KEY_MAX} bytes long."
+        ))
+        .into());
+    }
+    if key.contains('\0') {
+        return Err(BoardError::InvalidAttribute(
+            "Key must not contain NUL characters.".to_owned(),
+        )
+        .into());
+    }
+    if value.is_empty() || value.len() > ATTRIBUTE_VALUE_MAX {
+        return Err(BoardError::InvalidAttribute(format!(
+            "Value must be between 1 and {ATTRIBUTE_VALUE_MAX} bytes long."
+        ))
+        .into());
+    }
+
+    let mut slot = [0u8; ATTRIBUTE_SIZE];
+    slot[..key.len()].copy_from_slice(key.as_bytes());
+    slot[ATTRIBUTE_KEY_MAX] = value.len() as u8;
+    slot[ATTRIBUTE_KEY_MAX + 1..ATTRIBUTE_KEY_MAX + 1 + value.len()]
+        .copy_from_slice(value.as_bytes());
+    Ok(slot)
+}
+
+/// Find the slot where `key` should be written: the slot already holding it,
+/// or else the first empty one.
+pub(crate) fn find_slot_for_key(
+    slots: &[Option<DecodedAttribute>],
+    key: &str,
+) -> Result<usize, TockloaderError> {
+    find_key(slots, key)
+        .or_else(|| slots.iter().position(|slot| slot.is_none()))
+        .ok_or(BoardError::InvalidAttribute("All attribute slots are in use.".to_owned()).into())
+}
+
+/// Find the slot holding `key`.
+pub(crate) fn find_key(slots: &[Option<DecodedAttribute>], key: &str) -> Option<usize> {
+    slots
+        .iter()
+        .position(|slot| slot.as_ref().is_some_and(|attribute| attribute.key == key))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn attributes_round_trip() {
+        let slot = encode_attribute("board", "microbit_v2").unwrap();
+        let decoded = decode_attribute(&slot).unwrap();
+        assert_eq!(decoded.key, "board");
+        assert_eq!(decoded.value, "microbit_v2");
+
+        // Erased and removed slots are both empty.
+        assert!(decode_attribute(&[0xFF; ATTRIBUTE_SIZE]).is_none());
+        assert!(decode_attribute(&[0x00; ATTRIBUTE_SIZE]).is_none());
+
+        assert!(encode_attribute("too_long_key", "value").is_err());
+        assert!(encode_attribute("key", &"v".repeat(ATTRIBUTE_VALUE_MAX + 1)).is_err());
+        assert!(encode_attribute("key", "").is_err());
+    }
+}
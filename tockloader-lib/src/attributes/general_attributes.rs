@@ -2,10 +2,12 @@
 // SPDX-License-Identifier: Apache-2.0 OR MIT
 // Copyright OXIDOS AUTOMOTIVE 2024.
 
+use serde::Serialize;
+
 use super::app_attributes::AppAttributes;
 use super::system_attributes::SystemAttributes;
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct GeneralAttributes {
     pub system: SystemAttributes,
     pub apps: Vec<AppAttributes>,
@@ -0,0 +1,352 @@
+// Licensed under the Apache License, Version 2.0 or the MIT License.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+// Copyright OXIDOS AUTOMOTIVE 2024.
+
+use serde::Serialize;
+
+use super::app_attributes::AppAttributes;
+use super::system_attributes::SystemAttributes;
+use crate::errors::{BoardError, TockloaderError};
+
+/// Start of the bootloader flags page. The bootloader itself lives below it.
+const BOOTLOADER_FLAGS_START: u64 = 0x400;
+/// End of the bootloader attributes page (16 slots of 64 bytes at 0x600).
+const BOOTLOADER_ATTRIBUTES_END: u64 = 0x600 + 64 * 16;
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", content = "name", rename_all = "snake_case")]
+pub enum MemoryRegionKind {
+    Bootloader,
+    /// The bootloader flags and attributes pages.
+    BootloaderAttributes,
+    Kernel,
+    /// Flash or RAM used by the app with the given package name.
+    App(String),
+    /// Space between or inside apps that is lost to alignment.
+    Padding,
+    Free,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MemoryRegion {
+    pub start: u64,
+    pub size: u64,
+    #[serde(flatten)]
+    pub kind: MemoryRegionKind,
+}
+
+impl MemoryRegion {
+    fn new(start: u64, size: u64, kind: MemoryRegionKind) -> MemoryRegion {
+        MemoryRegion { start, size, kind }
+    }
+
+    pub fn end(&self) -> u64 {
+        self.start + self.size
+    }
+}
+
+/// Layout of the flash and of the app RAM of a board, as deduced from the
+/// system attributes and the installed apps.
+#[derive(Debug, Serialize)]
+pub struct MemoryMap {
+    pub flash: Vec<MemoryRegion>,
+    /// The app RAM region, with the minimum RAM of each app placed one after
+    /// the other, or at its fixed address if it has one. The kernel may need
+    /// more because of alignment constraints.
+    pub ram: Vec<MemoryRegion>,
+    /// Sum of the minimum RAM sizes of all apps.
+    pub app_ram_required: u64,
+    /// Size of the app RAM region, if known.
+    pub app_ram_available: Option<u64>,
+}
+
+impl MemoryMap {
+    /// Compute the memory map. `flash_end` is the end of the flash bank
+    /// holding the apps; if it is not known, no free space is reported after
+    /// the last app.
+    pub(crate) fn new(
+        system: &SystemAttributes,
+        apps: &[AppAttributes],
+        apps_start: u64,
+        flash_end: Option<u64>,
+    ) -> Result<MemoryMap, TockloaderError> {
+        let kernel_start = system
+            .kernel_bin_start
+            .ok_or(BoardError::MissingKernelAttribute("flash start"))?
+            as u64;
+        let kernel_len = system
+            .kernel_bin_len
+            .ok_or(BoardError::MissingKernelAttribute("flash length"))?
+            as u64;
+
+        let mut flash = Vec::new();
+
+        // The bootloader, if any, occupies the space before the kernel.
+        if kernel_start >= BOOTLOADER_ATTRIBUTES_END {
+            flash.push(MemoryRegion::new(
+                0,
+                BOOTLOADER_FLAGS_START,
+                MemoryRegionKind::Bootloader,
+            ));
+            flash.push(MemoryRegion::new(
+                BOOTLOADER_FLAGS_START,
+                BOOTLOADER_ATTRIBUTES_END - BOOTLOADER_FLAGS_START,
+                MemoryRegionKind::BootloaderAttributes,
+            ));
+            push_region(
+                &mut flash,
+                BOOTLOADER_ATTRIBUTES_END,
+                kernel_start,
+                MemoryRegionKind::Bootloader,
+            );
+        }
+
+        flash.push(MemoryRegion::new(
+            kernel_start,
+            kernel_len,
+            MemoryRegionKind::Kernel,
+        ));
+        push_region(
+            &mut flash,
+            kernel_start + kernel_len,
+            apps_start,
+            MemoryRegionKind::Free,
+        );
+
+        let mut address = apps_start;
+        for app in apps {
+            push_region(&mut flash, address, app.address, MemoryRegionKind::Padding);
+
+            let kind = if app.tbf_header.is_app() {
+                MemoryRegionKind::App(app_name(app))
+            } else {
+                MemoryRegionKind::Padding
+            };
+            flash.push(MemoryRegion::new(app.address, app.size as u64, kind));
+            address = app.address + app.size as u64;
+        }
+
+        if let Some(flash_end) = flash_end {
+            push_region(&mut flash, address, flash_end, MemoryRegionKind::Free);
+        }
+
+        let mut ram = Vec::new();
+        let ram_start = system.app_mem_start.unwrap_or(0) as u64;
+        let mut ram_address = ram_start;
+        for app in apps.iter().filter(|app| app.tbf_header.is_app()) {
+            if let Some(fixed) = app.tbf_header.get_fixed_address_ram() {
+                push_region(
+                    &mut ram,
+                    ram_address,
+                    fixed as u64,
+                    MemoryRegionKind::Padding,
+                );
+                ram_address = ram_address.max(fixed as u64);
+            }
+            let size = app.tbf_header.get_minimum_app_ram_size() as u64;
+            ram.push(MemoryRegion::new(
+                ram_address,
+                size,
+                MemoryRegionKind::App(app_name(app)),
+            ));
+            ram_address += size;
+        }
+
+        let app_ram_available = system.app_mem_len.map(u64::from);
+        if let Some(available) = app_ram_available {
+            push_region(
+                &mut ram,
+                ram_address,
+                ram_start + available,
+                MemoryRegionKind::Free,
+            );
+        }
+
+        Ok(MemoryMap {
+            flash,
+            ram,
+            app_ram_required: ram_address - ram_start,
+            app_ram_available,
+        })
+    }
+
+    /// Whether the apps need more RAM than the kernel reserved for them.
+    pub fn ram_overcommitted(&self) -> bool {
+        self.app_ram_available
+            .is_some_and(|available| self.app_ram_required > available)
+    }
+}
+
+/// Add a region spanning from `start` to `end`, if it is not empty.
+fn push_region(regions: &mut Vec<MemoryRegion>, start: u64, end: u64, kind: MemoryRegionKind) {
+    if end > start {
+        regions.push(MemoryRegion::new(start, end - start, kind));
+    }
+}
+
+fn app_name(app: &AppAttributes) -> String {
+    app.tbf_header
+        .get_package_name()
+        .unwrap_or("N/A")
+        .to_owned()
+}
+
+#[cfg(test)]
+mod test {
+    use tbf_parser::builder::{build_padding, TbfHeaderBuilder};
+    use tbf_parser::types::TbfHeaderV2FixedAddresses;
+
+    use super::*;
+
+    const APPS_START: u64 = 0x40000;
+    const RAM_START: u32 = 0x2000_8000;
+
+    fn system() -> SystemAttributes {
+        let mut system = SystemAttributes::new();
+        system.kernel_bin_start = Some(0x10000);
+        system.kernel_bin_len = Some(0x20000);
+        system.app_mem_start = Some(RAM_START);
+        system.app_mem_len = Some(0x8000);
+        system
+    }
+
+    fn app(name: &str, address: u64, ram: u32, fixed_ram: Option<u32>) -> AppAttributes {
+        let mut builder = TbfHeaderBuilder::new();
+        builder.set_package_name(name).unwrap();
+        builder.set_minimum_ram_size(ram);
+        builder.set_fixed_addresses(
+            fixed_ram.map(|ram| TbfHeaderV2FixedAddresses::new(ram, 0xFFFF_FFFF)),
+        );
+        let tbf = builder.build(&[0xAA; 64], 0, 0x1000).unwrap();
+        AppAttributes::read_app_data(&tbf, address)
+            .unwrap()
+            .unwrap()
+    }
+
+    fn padding(address: u64, size: u32) -> AppAttributes {
+        AppAttributes::read_app_data(&build_padding(size).unwrap(), address)
+            .unwrap()
+            .unwrap()
+    }
+
+    fn layout(regions: &[MemoryRegion]) -> Vec<(u64, u64, &MemoryRegionKind)> {
+        regions
+            .iter()
+            .map(|region| (region.start, region.end(), &region.kind))
+            .collect()
+    }
+
+    fn named(name: &str) -> MemoryRegionKind {
+        MemoryRegionKind::App(name.to_owned())
+    }
+
+    #[test]
+    fn flash_layout() {
+        let apps = [
+            app("blink", APPS_START, 0x1000, None),
+            padding(APPS_START + 0x1000, 0x1000),
+            app("hello", APPS_START + 0x2000, 0x1000, None),
+        ];
+        let map = MemoryMap::new(&system(), &apps, APPS_START, Some(0x80000)).unwrap();
+
+        assert_eq!(
+            layout(&map.flash),
+            [
+                (0, 0x400, &MemoryRegionKind::Bootloader),
+                (0x400, 0xA00, &MemoryRegionKind::BootloaderAttributes),
+                (0xA00, 0x10000, &MemoryRegionKind::Bootloader),
+                (0x10000, 0x30000, &MemoryRegionKind::Kernel),
+                (0x30000, 0x40000, &MemoryRegionKind::Free),
+                (0x40000, 0x41000, &named("blink")),
+                (0x41000, 0x42000, &MemoryRegionKind::Padding),
+                (0x42000, 0x43000, &named("hello")),
+                (0x43000, 0x80000, &MemoryRegionKind::Free),
+            ]
+        );
+    }
+
+    #[test]
+    fn flash_without_bootloader_or_flash_end() {
+        let mut system = system();
+        system.kernel_bin_start = Some(0);
+        let apps = [app("blink", APPS_START, 0x1000, None)];
+        let map = MemoryMap::new(&system, &apps, APPS_START, None).unwrap();
+
+        assert_eq!(
+            layout(&map.flash),
+            [
+                (0, 0x20000, &MemoryRegionKind::Kernel),
+                (0x20000, 0x40000, &MemoryRegionKind::Free),
+                (0x40000, 0x41000, &named("blink")),
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_kernel_attributes() {
+        let mut system = system();
+        system.kernel_bin_len = None;
+        assert!(MemoryMap::new(&system, &[], APPS_START, None).is_err());
+    }
+
+    #[test]
+    fn ram_layout() {
+        let apps = [
+            app("blink", APPS_START, 0x1000, None),
+            padding(APPS_START + 0x1000, 0x1000),
+            app("hello", APPS_START + 0x2000, 0x2000, None),
+        ];
+        let map = MemoryMap::new(&system(), &apps, APPS_START, None).unwrap();
+        let ram = RAM_START as u64;
+
+        assert_eq!(
+            layout(&map.ram),
+            [
+                (ram, ram + 0x1000, &named("blink")),
+                (ram + 0x1000, ram + 0x3000, &named("hello")),
+                (ram + 0x3000, ram + 0x8000, &MemoryRegionKind::Free),
+            ]
+        );
+        assert_eq!(map.app_ram_required, 0x3000);
+        assert!(!map.ram_overcommitted());
+    }
+
+    #[test]
+    fn ram_overcommitted() {
+        let apps = [
+            app("blink", APPS_START, 0x6000, None),
+            app("hello", APPS_START + 0x1000, 0x6000, None),
+        ];
+        let map = MemoryMap::new(&system(), &apps, APPS_START, None).unwrap();
+
+        assert_eq!(map.app_ram_required, 0xC000);
+        assert_eq!(map.app_ram_available, Some(0x8000));
+        assert!(map.ram_overcommitted());
+    }
+
+    #[test]
+    fn fixed_ram_app_is_placed_at_its_address() {
+        let ram = RAM_START as u64;
+        let apps = [
+            app("blink", APPS_START, 0x1000, None),
+            app(
+                "hello",
+                APPS_START + 0x1000,
+                0x1000,
+                Some(RAM_START + 0x4000),
+            ),
+        ];
+        let map = MemoryMap::new(&system(), &apps, APPS_START, None).unwrap();
+
+        assert_eq!(
+            layout(&map.ram),
+            [
+                (ram, ram + 0x1000, &named("blink")),
+                (ram + 0x1000, ram + 0x4000, &MemoryRegionKind::Padding),
+                (ram + 0x4000, ram + 0x5000, &named("hello")),
+                (ram + 0x5000, ram + 0x8000, &MemoryRegionKind::Free),
+            ]
+        );
+        assert_eq!(map.app_ram_required, 0x5000);
+    }
+}
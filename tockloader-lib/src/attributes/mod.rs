@@ -5,4 +5,5 @@
 pub mod app_attributes;
 pub mod decode;
 pub mod general_attributes;
+pub mod memory_map;
 pub mod system_attributes;
@@ -4,14 +4,18 @@
 
 use byteorder::{ByteOrder, LittleEndian};
 use probe_rs::{Core, MemoryInterface};
+use serde::Serialize;
 use tokio_serial::SerialStream;
 
 use crate::bootloader_serial::{issue_command, Command, Response};
-use crate::errors::TockloaderError;
+use crate::errors::{BoardError, FlashError, TockloaderError};
 
-use super::decode::{bytes_to_string, decode_attribute};
+use super::decode::{
+    bytes_to_string, decode_attribute, DecodedAttribute, ATTRIBUTES_ADDRESS, ATTRIBUTES_COUNT,
+    ATTRIBUTE_SIZE,
+};
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct SystemAttributes {
     pub board: Option<String>,
     pub arch: Option<String>,
@@ -43,57 +47,99 @@ impl SystemAttributes {
         }
     }
 
+    /// Store a bootloader attribute in the matching field, based on its key.
+    /// Attributes with unknown keys are ignored.
+    fn apply_attribute(&mut self, attribute: DecodedAttribute) -> Result<(), TockloaderError> {
+        match attribute.key.as_str() {
+            "board" => self.board = Some(attribute.value),
+            "arch" => self.arch = Some(attribute.value),
+            "appaddr" => {
+                self.appaddr = Some(
+                    u64::from_str_radix(attribute.value.trim_start_matches("0x"), 16)
+                        .map_err(|_| BoardError::InvalidStartAddress(attribute.value))?,
+                )
+            }
+            "boothash" => self.boothash = Some(attribute.value),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Store the kernel attributes, which the kernel places in the 100 bytes
+    /// right before the start of the apps.
+    fn apply_kernel_attributes(&mut self, kernel_attr_binary: &[u8]) {
+        let sentinel = bytes_to_string(&kernel_attr_binary[96..100]);
+        let kernel_version = LittleEndian::read_uint(&kernel_attr_binary[95..96], 1);
+
+        let app_memory_len = LittleEndian::read_u32(&kernel_attr_binary[84..92]);
+        let app_memory_start = LittleEndian::read_u32(&kernel_attr_binary[80..84]);
+
+        let kernel_binary_start = LittleEndian::read_u32(&kernel_attr_binary[68..72]);
+        let kernel_binary_len = LittleEndian::read_u32(&kernel_attr_binary[72..76]);
+
+        self.sentinel = Some(sentinel);
+        self.kernel_version = Some(kernel_version);
+        self.app_mem_start = Some(app_memory_start);
+        self.app_mem_len = Some(app_memory_len);
+        self.kernel_bin_start = Some(kernel_binary_start);
+        self.kernel_bin_len = Some(kernel_binary_len);
+    }
+
+    /// Read the attributes from a flash image starting at `base_address`.
+    /// Unlike on a board, the parts missing from the image are left empty.
+    pub(crate) fn read_system_attributes_image(
+        image: &[u8],
+        base_address: u64,
+    ) -> Result<Self, TockloaderError> {
+        let mut result = SystemAttributes::new();
+        let slice = |address: u64, length: usize| {
+            let start = usize::try_from(address.checked_sub(base_address)?).ok()?;
+            image.get(start..start.checked_add(length)?)
+        };
+
+        if let Some(buf) = slice(ATTRIBUTES_ADDRESS, ATTRIBUTE_SIZE * ATTRIBUTES_COUNT) {
+            for step in buf.chunks(ATTRIBUTE_SIZE) {
+                if let Some(decoded_attribute) = decode_attribute(step) {
+                    result.apply_attribute(decoded_attribute)?;
+                }
+            }
+        }
+
+        if let Some(buf) = slice(0x40E, 8) {
+            if let Ok(string) = std::str::from_utf8(buf) {
+                result.bootloader_version = Some(string.trim_matches(char::from(0)).to_owned());
+            }
+        }
+
+        if let Some(kernel_attr_binary) = result
+            .appaddr
+            .and_then(|appaddr| slice(appaddr.checked_sub(100)?, 100))
+        {
+            result.apply_kernel_attributes(kernel_attr_binary);
+        }
+
+        Ok(result)
+    }
+
     // TODO: explain what is happening here
     pub(crate) fn read_system_attributes_probe(
         board_core: &mut Core,
     ) -> Result<Self, TockloaderError> {
         let mut result = SystemAttributes::new();
-        let address = 0x600;
-        let mut buf = [0u8; 64 * 16];
-
-        let _ = board_core.read(address, &mut buf);
-
-        let mut data = buf.chunks(64);
-
-        for index_data in 0..data.len() {
-            let step = match data.next() {
-                Some(data) => data,
-                None => break,
-            };
-
-            let step_option = decode_attribute(step);
-
-            if let Some(decoded_attributes) = step_option {
-                match index_data {
-                    0 => {
-                        result.board = Some(decoded_attributes.value.to_string());
-                    }
-                    1 => {
-                        result.arch = Some(decoded_attributes.value.to_string());
-                    }
-                    2 => {
-                        result.appaddr = Some(
-                            u64::from_str_radix(
-                                decoded_attributes
-                                    .value
-                                    .to_string()
-                                    .trim_start_matches("0x"),
-                                16,
-                            )
-                            .map_err(|_| {
-                                TockloaderError::MisconfiguredBoard(
-                                    "Invalid start address.".to_owned(),
-                                )
-                            })?,
-                        );
-                    }
-                    3 => {
-                        result.boothash = Some(decoded_attributes.value.to_string());
-                    }
-                    _ => {}
-                }
-            } else {
-                continue;
+        let address = ATTRIBUTES_ADDRESS;
+        let mut buf = [0u8; ATTRIBUTE_SIZE * ATTRIBUTES_COUNT];
+
+        board_core
+            .read(address, &mut buf)
+            .map_err(|source| FlashError::Read {
+                address,
+                length: buf.len(),
+                source,
+            })?;
+
+        for step in buf.chunks(ATTRIBUTE_SIZE) {
+            if let Some(decoded_attribute) = decode_attribute(step) {
+                result.apply_attribute(decoded_attribute)?;
             }
         }
 
@@ -101,43 +147,35 @@ impl SystemAttributes {
 
         let mut buf = [0u8; 8];
 
-        let _ = board_core.read_8(address, &mut buf);
+        board_core
+            .read_8(address, &mut buf)
+            .map_err(|source| FlashError::Read {
+                address,
+                length: buf.len(),
+                source,
+            })?;
 
-        let string = String::from_utf8(buf.to_vec()).map_err(|_| {
-            TockloaderError::MisconfiguredBoard(
-                "Data may be corrupted. System attribure is not UTF-8.".to_owned(),
-            )
-        })?;
+        let string =
+            String::from_utf8(buf.to_vec()).map_err(|_| BoardError::CorruptBootloaderVersion)?;
 
         let string = string.trim_matches(char::from(0));
 
         result.bootloader_version = Some(string.to_owned());
 
+        let kernel_attr_address = result
+            .appaddr
+            .ok_or(BoardError::MissingAttribute("appaddr"))?
+            - 100;
         let mut kernel_attr_binary = [0u8; 100];
         board_core
-            .read(
-                result.appaddr.ok_or(TockloaderError::MisconfiguredBoard(
-                    "No start address found.".to_owned(),
-                ))? - 100,
-                &mut kernel_attr_binary,
-            )
-            .map_err(TockloaderError::ProbeRsReadError)?;
-
-        let sentinel = bytes_to_string(&kernel_attr_binary[96..100]);
-        let kernel_version = LittleEndian::read_uint(&kernel_attr_binary[95..96], 1);
+            .read(kernel_attr_address, &mut kernel_attr_binary)
+            .map_err(|source| FlashError::Read {
+                address: kernel_attr_address,
+                length: kernel_attr_binary.len(),
+                source,
+            })?;
 
-        let app_memory_len = LittleEndian::read_u32(&kernel_attr_binary[84..92]);
-        let app_memory_start = LittleEndian::read_u32(&kernel_attr_binary[80..84]);
-
-        let kernel_binary_start = LittleEndian::read_u32(&kernel_attr_binary[68..72]);
-        let kernel_binary_len = LittleEndian::read_u32(&kernel_attr_binary[72..76]);
-
-        result.sentinel = Some(sentinel);
-        result.kernel_version = Some(kernel_version);
-        result.app_mem_start = Some(app_memory_start);
-        result.app_mem_len = Some(app_memory_len);
-        result.kernel_bin_start = Some(kernel_binary_start);
-        result.kernel_bin_len = Some(kernel_binary_len);
+        result.apply_kernel_attributes(&kernel_attr_binary);
 
         Ok(result)
     }
@@ -148,8 +186,10 @@ impl SystemAttributes {
     ) -> Result<Self, TockloaderError> {
         let mut result = SystemAttributes::new();
 
-        let mut pkt = (0x600_u32).to_le_bytes().to_vec();
-        let length = (1024_u16).to_le_bytes().to_vec();
+        let mut pkt = (ATTRIBUTES_ADDRESS as u32).to_le_bytes().to_vec();
+        let length = ((ATTRIBUTE_SIZE * ATTRIBUTES_COUNT) as u16)
+            .to_le_bytes()
+            .to_vec();
         for i in length {
             pkt.push(i);
         }
@@ -159,52 +199,14 @@ impl SystemAttributes {
             Command::ReadRange,
             pkt,
             true,
-            64 * 16,
+            ATTRIBUTE_SIZE * ATTRIBUTES_COUNT,
             Response::ReadRange,
         )
         .await?;
 
-        let mut data = buf.chunks(64);
-
-        for index_data in 0..data.len() {
-            let step = match data.next() {
-                Some(data) => data,
-                None => break,
-            };
-
-            let step_option = decode_attribute(step);
-
-            if let Some(decoded_attributes) = step_option {
-                match index_data {
-                    0 => {
-                        result.board = Some(decoded_attributes.value.to_string());
-                    }
-                    1 => {
-                        result.arch = Some(decoded_attributes.value.to_string());
-                    }
-                    2 => {
-                        result.appaddr = Some(
-                            u64::from_str_radix(
-                                decoded_attributes
-                                    .value
-                                    .to_string()
-                                    .trim_start_matches("0x"),
-                                16,
-                            )
-                            .map_err(|_| {
-                                TockloaderError::MisconfiguredBoard(
-                                    "Invalid start address.".to_owned(),
-                                )
-                            })?,
-                        );
-                    }
-                    3 => {
-                        result.boothash = Some(decoded_attributes.value.to_string());
-                    }
-                    _ => {}
-                }
-            } else {
-                continue;
+        for step in buf.chunks(ATTRIBUTE_SIZE) {
+            if let Some(decoded_attribute) = decode_attribute(step) {
+                result.apply_attribute(decoded_attribute)?;
             }
         }
 
@@ -217,19 +219,16 @@ impl SystemAttributes {
         let (_, buf) =
             issue_command(port, Command::ReadRange, pkt, true, 8, Response::ReadRange).await?;
 
-        let string = String::from_utf8(buf).map_err(|_| {
-            TockloaderError::MisconfiguredBoard(
-                "Data may be corrupted. System attribure is not UTF-8.".to_owned(),
-            )
-        })?;
+        let string = String::from_utf8(buf).map_err(|_| BoardError::CorruptBootloaderVersion)?;
 
         let string = string.trim_matches(char::from(0));
 
         result.bootloader_version = Some(string.to_owned());
 
-        let mut pkt = ((result.appaddr.ok_or(TockloaderError::MisconfiguredBoard(
-            "No start address found.".to_owned(),
-        ))? - 100) as u32)
+        let mut pkt = ((result
+            .appaddr
+            .ok_or(BoardError::MissingAttribute("appaddr"))?
+            - 100) as u32)
             .to_le_bytes()
             .to_vec();
         let length = (100_u16).to_le_bytes().to_vec();
@@ -247,21 +246,7 @@ impl SystemAttributes {
         )
         .await?;
 
-        let sentinel = bytes_to_string(&kernel_attr_binary[96..100]);
-        let kernel_version = LittleEndian::read_uint(&kernel_attr_binary[95..96], 1);
-
-        let app_memory_len = LittleEndian::read_u32(&kernel_attr_binary[84..92]);
-        let app_memory_start = LittleEndian::read_u32(&kernel_attr_binary[80..84]);
-
-        let kernel_binary_start = LittleEndian::read_u32(&kernel_attr_binary[68..72]);
-        let kernel_binary_len = LittleEndian::read_u32(&kernel_attr_binary[72..76]);
-
-        result.sentinel = Some(sentinel);
-        result.kernel_version = Some(kernel_version);
-        result.app_mem_start = Some(app_memory_start);
-        result.app_mem_len = Some(app_memory_len);
-        result.kernel_bin_start = Some(kernel_binary_start);
-        result.kernel_bin_len = Some(kernel_binary_len);
+        result.apply_kernel_attributes(&kernel_attr_binary);
 
         Ok(result)
     }
@@ -4,12 +4,16 @@
 
 // The "X" commands are for external flash
 
-use crate::errors;
+use crate::attributes::app_attributes::FlashReader;
+use crate::errors::{BootloaderError, ConnectionError, TockloaderError};
+use crate::progress::{Phase, Progress};
+use async_trait::async_trait;
 use bytes::BytesMut;
-use errors::TockloaderError;
+use std::fmt;
 use std::time::Duration;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio_serial::{SerialPort, SerialStream};
+use tracing::{trace, warn};
 
 // Tell the bootloader to reset its buffer to handle a new command
 pub const SYNC_MESSAGE: [u8; 3] = [0x00, 0xFC, 0x05];
@@ -18,6 +22,7 @@ pub const SYNC_MESSAGE: [u8; 3] = [0x00, 0xFC, 0x05];
 pub const ESCAPE_CHAR: u8 = 0xFC;
 
 #[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Command {
     // Commands from this tool to the bootloader
     Ping = 0x01,
@@ -44,7 +49,7 @@ pub enum Command {
     SetStartAddress = 0x23,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Response {
     // Responses from the bootloader
     Overflow = 0x10,
@@ -100,24 +105,40 @@ pub async fn toggle_bootloader_entry_dtr_rts(
     port: &mut SerialStream,
 ) -> Result<(), TockloaderError> {
     port.write_data_terminal_ready(true)
-        .map_err(TockloaderError::SerialInitializationError)?;
+        .map_err(ConnectionError::SerialInitialization)?;
     port.write_request_to_send(true)
-        .map_err(TockloaderError::SerialInitializationError)?;
+        .map_err(ConnectionError::SerialInitialization)?;
 
     tokio::time::sleep(Duration::from_millis(100)).await;
 
     port.write_data_terminal_ready(false)
-        .map_err(TockloaderError::SerialInitializationError)?;
+        .map_err(ConnectionError::SerialInitialization)?;
 
     tokio::time::sleep(Duration::from_millis(500)).await;
 
     port.write_request_to_send(false)
-        .map_err(TockloaderError::SerialInitializationError)?;
+        .map_err(ConnectionError::SerialInitialization)?;
 
     Ok(())
 }
 
+/// Formats bytes as space-separated hex, for tracing serial frames.
+pub(crate) struct Hex<'a>(pub &'a [u8]);
+
+impl fmt::Display for Hex<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, byte) in self.0.iter().enumerate() {
+            if i != 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
 #[allow(dead_code)]
+#[tracing::instrument(level = "debug", skip_all)]
 pub async fn ping_bootloader_and_wait_for_response(
     port: &mut SerialStream,
 ) -> Result<Response, TockloaderError> {
@@ -126,14 +147,22 @@ pub async fn ping_bootloader_and_wait_for_response(
     let mut ret = BytesMut::with_capacity(200);
 
     for _ in 0..30 {
+        trace!(frame = %Hex(&ping_pkt), "Sending");
         let mut bytes_written = 0;
         while bytes_written != ping_pkt.len() {
-            bytes_written += port.write_buf(&mut &ping_pkt[bytes_written..]).await?;
+            bytes_written += port
+                .write_buf(&mut &ping_pkt[bytes_written..])
+                .await
+                .map_err(ConnectionError::SerialIo)?;
         }
         let mut read_bytes = 0;
         while read_bytes < 2 {
-            read_bytes += port.read_buf(&mut ret).await?;
+            read_bytes += port
+                .read_buf(&mut ret)
+                .await
+                .map_err(ConnectionError::SerialIo)?;
         }
+        trace!(frame = %Hex(&ret), "Received");
         if ret[1] == Response::Pong as u8 {
             return Ok(Response::from(ret[1]));
         }
@@ -141,7 +170,28 @@ pub async fn ping_bootloader_and_wait_for_response(
     Ok(Response::from(ret[1]))
 }
 
+/// Make sure the bootloader is listening before issuing commands. It is
+/// pinged a second time if it did not answer the first time, as it may have
+/// still been starting up.
+pub async fn wake_bootloader(port: &mut SerialStream) -> Result<(), TockloaderError> {
+    if ping_bootloader_and_wait_for_response(port).await? == Response::Pong {
+        return Ok(());
+    }
+
+    // TODO: more robust retry system (and configurable)
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let response = ping_bootloader_and_wait_for_response(port).await?;
+    if response != Response::Pong {
+        warn!(
+            ?response,
+            "Bootloader did not answer the ping, trying to continue"
+        );
+    }
+    Ok(())
+}
+
 #[allow(dead_code)]
+#[tracing::instrument(level = "debug", skip(port, message), fields(length = message.len()))]
 pub async fn issue_command(
     port: &mut SerialStream,
     command: Command,
@@ -174,9 +224,13 @@ pub async fn issue_command(
     }
 
     // Write the command message
+    trace!(frame = %Hex(&message), "Sending");
     let mut bytes_written = 0;
     while bytes_written != message.len() {
-        bytes_written += port.write_buf(&mut &message[bytes_written..]).await?;
+        bytes_written += port
+            .write_buf(&mut &message[bytes_written..])
+            .await
+            .map_err(ConnectionError::SerialIo)?;
     }
 
     // Response has a two byte header, then response_len bytes
@@ -186,15 +240,28 @@ pub async fn issue_command(
     // We are waiting for 2 bytes to be read
     let mut read_bytes = 0;
     while read_bytes < 2 {
-        read_bytes += port.read_buf(&mut ret).await?;
+        read_bytes += port
+            .read_buf(&mut ret)
+            .await
+            .map_err(ConnectionError::SerialIo)?;
     }
+    trace!(frame = %Hex(&ret), "Received");
 
     if ret[0] != ESCAPE_CHAR {
-        return Err(TockloaderError::BootloaderError(ret[0]));
+        return Err(BootloaderError::NotAResponse {
+            command,
+            received: ret[0],
+        }
+        .into());
     }
 
-    if ret[1] != response_code.clone() as u8 {
-        return Err(TockloaderError::BootloaderError(ret[1]));
+    if ret[1] != response_code as u8 {
+        return Err(BootloaderError::UnexpectedResponse {
+            command,
+            expected: response_code,
+            received: ret[1],
+        }
+        .into());
     }
 
     let mut new_data: Vec<u8> = Vec::new();
@@ -202,9 +269,14 @@ pub async fn issue_command(
 
     if response_len != 0 {
         while bytes_to_read > value {
-            value += port.read_buf(&mut new_data).await?;
+            value += port
+                .read_buf(&mut new_data)
+                .await
+                .map_err(ConnectionError::SerialIo)?;
         }
 
+        trace!(frame = %Hex(&new_data), "Received");
+
         // De-escape and add array of read in the bytes
         for i in 0..(new_data.len() - 1) {
             if new_data[i] == ESCAPE_CHAR && new_data[i + 1] == ESCAPE_CHAR {
@@ -217,3 +289,117 @@ pub async fn issue_command(
 
     Ok((Response::from(ret[1]), ret[2..].to_vec()))
 }
+
+// TODO(george-cosma): check if the page-size differs between boards.
+pub const PAGE_SIZE: usize = 512;
+
+/// Read `length` bytes of flash starting at `address`, in page-sized chunks.
+#[tracing::instrument(level = "debug", skip(port), fields(address = format_args!("{address:#x}")))]
+pub async fn read_range(
+    port: &mut SerialStream,
+    address: u64,
+    length: usize,
+) -> Result<Vec<u8>, TockloaderError> {
+    let mut data = Vec::with_capacity(length);
+    while data.len() < length {
+        let chunk = PAGE_SIZE.min(length - data.len());
+
+        let mut pkt = ((address as usize + data.len()) as u32)
+            .to_le_bytes()
+            .to_vec();
+        pkt.extend_from_slice(&(chunk as u16).to_le_bytes());
+
+        let (_, message) = issue_command(
+            port,
+            Command::ReadRange,
+            pkt,
+            true,
+            chunk,
+            Response::ReadRange,
+        )
+        .await?;
+        if message.len() < chunk {
+            return Err(BootloaderError::ShortResponse {
+                command: Command::ReadRange,
+                expected: chunk,
+                received: message.len(),
+            }
+            .into());
+        }
+        data.extend_from_slice(&message[..chunk]);
+    }
+    Ok(data)
+}
+
+#[async_trait]
+impl FlashReader for SerialStream {
+    async fn read(&mut self, address: u64, length: usize) -> Result<Vec<u8>, TockloaderError> {
+        read_range(self, address, length).await
+    }
+
+    /// The bootloader has no way of telling us how large the flash is.
+    fn flash_end(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// Write a single page of flash. `address` must be page-aligned.
+#[tracing::instrument(level = "debug", skip_all, fields(address = format_args!("{address:#x}")))]
+pub async fn write_page(
+    port: &mut SerialStream,
+    address: u64,
+    page: &[u8],
+) -> Result<(), TockloaderError> {
+    // The first four bytes are the address of the page, then its contents.
+    let mut pkt = (address as u32).to_le_bytes().to_vec();
+    pkt.extend_from_slice(page);
+
+    issue_command(port, Command::WritePage, pkt, true, 0, Response::OK).await?;
+    Ok(())
+}
+
+/// Write `data` at `address`, page by page. Pages that are only partially
+/// covered are read first, so the bytes around `data` are kept.
+///
+/// Cancellation is checked before each page. The bytes written are reported
+/// in the write phase, counting on from `written`, so that several calls can
+/// share one phase. Starting and finishing the phase is up to the caller.
+#[tracing::instrument(
+    level = "debug",
+    skip_all,
+    fields(address = format_args!("{address:#x}"), length = data.len())
+)]
+pub async fn write_flash(
+    port: &mut SerialStream,
+    address: u64,
+    data: &[u8],
+    progress: &Progress,
+    written: &mut u64,
+) -> Result<(), TockloaderError> {
+    let page_size = PAGE_SIZE as u64;
+    let end = address + data.len() as u64;
+
+    let mut page_address = address - address % page_size;
+    while page_address < end {
+        progress.check_cancelled()?;
+
+        let page_end = page_address + page_size;
+        let start = address.max(page_address);
+        let stop = end.min(page_end);
+
+        let mut page = if stop - start == page_size {
+            vec![0xFF; PAGE_SIZE]
+        } else {
+            read_range(port, page_address, PAGE_SIZE).await?
+        };
+        page[(start - page_address) as usize..(stop - page_address) as usize]
+            .copy_from_slice(&data[(start - address) as usize..(stop - address) as usize]);
+
+        write_page(port, page_address, &page).await?;
+        *written += stop - start;
+        progress.advance(Phase::Write, *written);
+        page_address = page_end;
+    }
+
+    Ok(())
+}
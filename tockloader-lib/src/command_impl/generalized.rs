@@ -1,12 +1,19 @@
 use async_trait::async_trait;
 
 use crate::attributes::app_attributes::AppAttributes;
+use crate::attributes::decode::DecodedAttribute;
 use crate::attributes::general_attributes::GeneralAttributes;
+use crate::attributes::memory_map::MemoryMap;
 use crate::board_settings::BoardSettings;
 use crate::connection::TockloaderConnection;
 use crate::errors::TockloaderError;
+use crate::kernel_image::KernelImage;
+use crate::progress::Progress;
 use crate::tabs::tab::Tab;
-use crate::{CommandInfo, CommandInstall, CommandList};
+use crate::{
+    CommandAttributes, CommandEraseApps, CommandFlashKernel, CommandInfo, CommandInstall,
+    CommandList, CommandMemoryMap, CommandReadWrite,
+};
 
 #[async_trait]
 impl CommandList for TockloaderConnection {
@@ -40,10 +47,126 @@ impl CommandInstall for TockloaderConnection {
         &mut self,
         settings: &BoardSettings,
         tab_file: Tab,
+        progress: &Progress,
     ) -> Result<(), TockloaderError> {
         match self {
-            TockloaderConnection::ProbeRS(conn) => conn.install_app(settings, tab_file).await,
-            TockloaderConnection::Serial(conn) => conn.install_app(settings, tab_file).await,
+            TockloaderConnection::ProbeRS(conn) => {
+                conn.install_app(settings, tab_file, progress).await
+            }
+            TockloaderConnection::Serial(conn) => {
+                conn.install_app(settings, tab_file, progress).await
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl CommandMemoryMap for TockloaderConnection {
+    async fn memory_map(&mut self, settings: &BoardSettings) -> Result<MemoryMap, TockloaderError> {
+        match self {
+            TockloaderConnection::ProbeRS(conn) => conn.memory_map(settings).await,
+            TockloaderConnection::Serial(conn) => conn.memory_map(settings).await,
+        }
+    }
+}
+
+#[async_trait]
+impl CommandAttributes for TockloaderConnection {
+    async fn list_attributes(&mut self) -> Result<Vec<Option<DecodedAttribute>>, TockloaderError> {
+        match self {
+            TockloaderConnection::ProbeRS(conn) => conn.list_attributes().await,
+            TockloaderConnection::Serial(conn) => conn.list_attributes().await,
+        }
+    }
+
+    async fn get_attribute(&mut self, key: &str) -> Result<Option<String>, TockloaderError> {
+        match self {
+            TockloaderConnection::ProbeRS(conn) => conn.get_attribute(key).await,
+            TockloaderConnection::Serial(conn) => conn.get_attribute(key).await,
+        }
+    }
+
+    async fn set_attribute(&mut self, key: &str, value: &str) -> Result<(), TockloaderError> {
+        match self {
+            TockloaderConnection::ProbeRS(conn) => conn.set_attribute(key, value).await,
+            TockloaderConnection::Serial(conn) => conn.set_attribute(key, value).await,
+        }
+    }
+
+    async fn remove_attribute(&mut self, key: &str) -> Result<(), TockloaderError> {
+        match self {
+            TockloaderConnection::ProbeRS(conn) => conn.remove_attribute(key).await,
+            TockloaderConnection::Serial(conn) => conn.remove_attribute(key).await,
+        }
+    }
+}
+
+#[async_trait]
+impl CommandFlashKernel for TockloaderConnection {
+    async fn flash_kernel(
+        &mut self,
+        settings: &BoardSettings,
+        kernel: KernelImage,
+        preserve_apps: bool,
+        progress: &Progress,
+    ) -> Result<(), TockloaderError> {
+        match self {
+            TockloaderConnection::ProbeRS(conn) => {
+                conn.flash_kernel(settings, kernel, preserve_apps, progress)
+                    .await
+            }
+            TockloaderConnection::Serial(conn) => {
+                conn.flash_kernel(settings, kernel, preserve_apps, progress)
+                    .await
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl CommandEraseApps for TockloaderConnection {
+    async fn erase_apps(
+        &mut self,
+        settings: &BoardSettings,
+        force: bool,
+        progress: &Progress,
+    ) -> Result<Vec<AppAttributes>, TockloaderError> {
+        match self {
+            TockloaderConnection::ProbeRS(conn) => conn.erase_apps(settings, force, progress).await,
+            TockloaderConnection::Serial(conn) => conn.erase_apps(settings, force, progress).await,
+        }
+    }
+}
+
+#[async_trait]
+impl CommandReadWrite for TockloaderConnection {
+    async fn read_memory(
+        &mut self,
+        address: u64,
+        length: usize,
+    ) -> Result<Vec<u8>, TockloaderError> {
+        match self {
+            TockloaderConnection::ProbeRS(conn) => conn.read_memory(address, length).await,
+            TockloaderConnection::Serial(conn) => conn.read_memory(address, length).await,
+        }
+    }
+
+    async fn write_memory(
+        &mut self,
+        address: u64,
+        data: &[u8],
+        progress: &Progress,
+    ) -> Result<(), TockloaderError> {
+        match self {
+            TockloaderConnection::ProbeRS(conn) => conn.write_memory(address, data, progress).await,
+            TockloaderConnection::Serial(conn) => conn.write_memory(address, data, progress).await,
+        }
+    }
+
+    async fn dump_apps(&mut self, settings: &BoardSettings) -> Result<Vec<u8>, TockloaderError> {
+        match self {
+            TockloaderConnection::ProbeRS(conn) => conn.dump_apps(settings).await,
+            TockloaderConnection::Serial(conn) => conn.dump_apps(settings).await,
         }
     }
 }
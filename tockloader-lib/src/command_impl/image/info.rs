@@ -0,0 +1,29 @@
+use async_trait::async_trait;
+
+use crate::attributes::app_attributes::AppAttributes;
+use crate::attributes::general_attributes::GeneralAttributes;
+use crate::attributes::system_attributes::SystemAttributes;
+use crate::board_settings::BoardSettings;
+use crate::errors::TockloaderError;
+use crate::flash_image::FlashImage;
+use crate::CommandInfo;
+
+#[async_trait]
+impl CommandInfo for FlashImage {
+    async fn info(
+        &mut self,
+        settings: &BoardSettings,
+    ) -> Result<GeneralAttributes, TockloaderError> {
+        // Dumps of the app region alone have no system attributes, so these
+        // will only be found in images of the whole flash.
+        let system_attributes =
+            SystemAttributes::read_system_attributes_image(&self.data, self.base_address)?;
+        let app_attributes = AppAttributes::read_apps_data_image(
+            &self.data,
+            self.base_address,
+            settings.start_address,
+        )?;
+
+        Ok(GeneralAttributes::new(system_attributes, app_attributes))
+    }
+}
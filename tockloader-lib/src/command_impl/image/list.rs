@@ -0,0 +1,17 @@
+use async_trait::async_trait;
+
+use crate::attributes::app_attributes::AppAttributes;
+use crate::board_settings::BoardSettings;
+use crate::errors::TockloaderError;
+use crate::flash_image::FlashImage;
+use crate::CommandList;
+
+#[async_trait]
+impl CommandList for FlashImage {
+    async fn list(
+        &mut self,
+        settings: &BoardSettings,
+    ) -> Result<Vec<AppAttributes>, TockloaderError> {
+        AppAttributes::read_apps_data_image(&self.data, self.base_address, settings.start_address)
+    }
+}
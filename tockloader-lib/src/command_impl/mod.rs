@@ -1,3 +1,4 @@
 pub mod generalized;
+pub mod image;
 pub mod probers;
 pub mod serial;
@@ -0,0 +1,86 @@
+use async_trait::async_trait;
+use probe_rs::flashing::DownloadOptions;
+use probe_rs::{MemoryInterface, Session};
+
+use crate::attributes::decode::{
+    decode_attribute, encode_attribute, find_key, find_slot_for_key, DecodedAttribute,
+    ATTRIBUTES_ADDRESS, ATTRIBUTES_COUNT, ATTRIBUTE_SIZE,
+};
+use crate::connection::{Connection, ProbeRSConnection};
+use crate::errors::{BoardError, ConnectionError, FlashError, TockloaderError};
+use crate::CommandAttributes;
+
+#[async_trait]
+impl CommandAttributes for ProbeRSConnection {
+    async fn list_attributes(&mut self) -> Result<Vec<Option<DecodedAttribute>>, TockloaderError> {
+        if !self.is_open() {
+            return Err(ConnectionError::NotOpen.into());
+        }
+        let session = self.session.as_mut().expect("Board must be open");
+
+        let mut core =
+            session
+                .core(self.target_info.core)
+                .map_err(|source| ConnectionError::CoreAccess {
+                    core: self.target_info.core,
+                    source,
+                })?;
+
+        let mut buf = [0u8; ATTRIBUTE_SIZE * ATTRIBUTES_COUNT];
+        core.read(ATTRIBUTES_ADDRESS, &mut buf)
+            .map_err(|source| FlashError::Read {
+                address: ATTRIBUTES_ADDRESS,
+                length: buf.len(),
+                source,
+            })?;
+
+        Ok(buf.chunks(ATTRIBUTE_SIZE).map(decode_attribute).collect())
+    }
+
+    async fn get_attribute(&mut self, key: &str) -> Result<Option<String>, TockloaderError> {
+        let mut slots = self.list_attributes().await?;
+        Ok(find_key(&slots, key)
+            .and_then(|index| slots.swap_remove(index))
+            .map(|attribute| attribute.value))
+    }
+
+    async fn set_attribute(&mut self, key: &str, value: &str) -> Result<(), TockloaderError> {
+        let slot = encode_attribute(key, value)?;
+        let slots = self.list_attributes().await?;
+        let index = find_slot_for_key(&slots, key)?;
+
+        let session = self.session.as_mut().expect("Board must be open");
+        write_attribute_slot(session, index, &slot)
+    }
+
+    async fn remove_attribute(&mut self, key: &str) -> Result<(), TockloaderError> {
+        let slots = self.list_attributes().await?;
+        let index = find_key(&slots, key).ok_or(BoardError::AttributeNotFound(key.to_owned()))?;
+
+        let session = self.session.as_mut().expect("Board must be open");
+        write_attribute_slot(session, index, &[0u8; ATTRIBUTE_SIZE])
+    }
+}
+
+/// Write a raw attribute slot directly to flash, keeping the other slots.
+#[tracing::instrument(level = "debug", skip(session, slot))]
+fn write_attribute_slot(
+    session: &mut Session,
+    index: usize,
+    slot: &[u8; ATTRIBUTE_SIZE],
+) -> Result<(), TockloaderError> {
+    let address = ATTRIBUTES_ADDRESS + (index * ATTRIBUTE_SIZE) as u64;
+    let write_error = |source| FlashError::Write {
+        address,
+        length: ATTRIBUTE_SIZE,
+        source,
+    };
+
+    let mut loader = session.target().flash_loader();
+    loader.add_data(address, slot).map_err(write_error)?;
+
+    let mut options = DownloadOptions::default();
+    options.keep_unwritten_bytes = true;
+
+    Ok(loader.commit(session, options).map_err(write_error)?)
+}
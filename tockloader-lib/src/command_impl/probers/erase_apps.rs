@@ -0,0 +1,60 @@
+use async_trait::async_trait;
+use probe_rs::flashing::DownloadOptions;
+use tracing::debug;
+
+use crate::attributes::app_attributes::AppAttributes;
+use crate::board_settings::BoardSettings;
+use crate::connection::{Connection, ProbeRSConnection};
+use crate::erase::ErasePlan;
+use crate::errors::{ConnectionError, FlashError, TockloaderError};
+use crate::progress::{Phase, Progress};
+use crate::{CommandEraseApps, CommandList};
+
+#[async_trait]
+impl CommandEraseApps for ProbeRSConnection {
+    async fn erase_apps(
+        &mut self,
+        settings: &BoardSettings,
+        force: bool,
+        progress: &Progress,
+    ) -> Result<Vec<AppAttributes>, TockloaderError> {
+        if !self.is_open() {
+            return Err(ConnectionError::NotOpen.into());
+        }
+
+        let plan = ErasePlan::scan(self, settings, force, progress).await?;
+
+        let session = self.session.as_mut().expect("Board must be open");
+        let total = plan.pages.iter().map(|(_, page)| page.len() as u64).sum();
+        progress.start(Phase::Erase, Some(total));
+        let mut done = 0;
+        // The pages are in an order that keeps the app list valid, so it is
+        // safe to stop between any two of them.
+        for (address, page) in &plan.pages {
+            progress.check_cancelled()?;
+
+            debug!(address = format_args!("{address:#x}"), "Erasing page");
+            let write_error = |source| FlashError::Write {
+                address: *address,
+                length: page.len(),
+                source,
+            };
+            let mut loader = session.target().flash_loader();
+            loader.add_data(*address, page).map_err(write_error)?;
+
+            let mut options = DownloadOptions::default();
+            options.keep_unwritten_bytes = true;
+            loader.commit(session, options).map_err(write_error)?;
+
+            done += page.len() as u64;
+            progress.advance(Phase::Erase, done);
+        }
+        progress.finish(Phase::Erase);
+
+        progress.start(Phase::Verify, None);
+        let apps = self.list(settings).await?;
+        plan.verify(&apps)?;
+        progress.finish(Phase::Verify);
+        Ok(apps)
+    }
+}
@@ -0,0 +1,126 @@
+use async_trait::async_trait;
+use probe_rs::{MemoryInterface, Session};
+
+use crate::board_settings::BoardSettings;
+use crate::command_impl::probers::download_options;
+use crate::connection::{Connection, ProbeRSConnection};
+use crate::errors::{ConnectionError, FlashError, TockloaderError};
+use crate::kernel_image::{KernelBounds, KernelImage, KernelSegment};
+use crate::progress::{Phase, Progress};
+use crate::{CommandFlashKernel, CommandInfo};
+
+#[async_trait]
+impl CommandFlashKernel for ProbeRSConnection {
+    async fn flash_kernel(
+        &mut self,
+        settings: &BoardSettings,
+        kernel: KernelImage,
+        preserve_apps: bool,
+        progress: &Progress,
+    ) -> Result<(), TockloaderError> {
+        if !self.is_open() {
+            return Err(ConnectionError::NotOpen.into());
+        }
+
+        let bounds = KernelBounds::from_info(self.info(settings).await, settings)?;
+
+        let segments = kernel.into_segments(bounds.kernel_start)?;
+        bounds.check(&segments)?;
+
+        let session = self.session.as_mut().expect("Board must be open");
+        let core_index = self.target_info.core;
+
+        let backup = if preserve_apps {
+            Some(read_flash(
+                session,
+                core_index,
+                bounds.apps_start,
+                bounds.apps_end,
+            )?)
+        } else {
+            None
+        };
+
+        progress.check_cancelled()?;
+        write_segments(session, &segments, progress)?;
+
+        if let Some(backup) = backup {
+            progress.start(Phase::Verify, Some(backup.len() as u64));
+            let current = read_flash(session, core_index, bounds.apps_start, bounds.apps_end)?;
+            progress.finish(Phase::Verify);
+            if current != backup {
+                write_segments(
+                    session,
+                    &[KernelSegment {
+                        address: bounds.apps_start,
+                        data: backup,
+                    }],
+                    progress,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[tracing::instrument(
+    level = "debug",
+    skip(session),
+    fields(start = format_args!("{start:#x}"), end = format_args!("{end:#x}"))
+)]
+fn read_flash(
+    session: &mut Session,
+    core_index: usize,
+    start: u64,
+    end: u64,
+) -> Result<Vec<u8>, TockloaderError> {
+    let mut core = session
+        .core(core_index)
+        .map_err(|source| ConnectionError::CoreAccess {
+            core: core_index,
+            source,
+        })?;
+
+    let mut data = vec![0u8; (end - start) as usize];
+    core.read(start, &mut data)
+        .map_err(|source| FlashError::Read {
+            address: start,
+            length: data.len(),
+            source,
+        })?;
+    Ok(data)
+}
+
+#[tracing::instrument(level = "debug", skip_all, fields(segments = segments.len()))]
+fn write_segments(
+    session: &mut Session,
+    segments: &[KernelSegment],
+    progress: &Progress,
+) -> Result<(), TockloaderError> {
+    let mut loader = session.target().flash_loader();
+    for segment in segments {
+        loader
+            .add_data(segment.address, &segment.data)
+            .map_err(|source| FlashError::Write {
+                address: segment.address,
+                length: segment.data.len(),
+                source,
+            })?;
+    }
+
+    let options = download_options(progress);
+
+    // The loader writes all segments at once, so report the whole range.
+    let start = segments.iter().map(|segment| segment.address).min();
+    let end = segments.iter().map(KernelSegment::end).max();
+    loader.commit(session, options).map_err(|source| {
+        let address = start.unwrap_or_default();
+        FlashError::Write {
+            address,
+            length: (end.unwrap_or_default() - address) as usize,
+            source,
+        }
+        .into()
+    })
+}
@@ -4,8 +4,9 @@ use crate::attributes::app_attributes::AppAttributes;
 use crate::attributes::general_attributes::GeneralAttributes;
 use crate::attributes::system_attributes::SystemAttributes;
 use crate::board_settings::BoardSettings;
+use crate::command_impl::probers::ProbeFlash;
 use crate::connection::{Connection, ProbeRSConnection};
-use crate::errors::TockloaderError;
+use crate::errors::{ConnectionError, TockloaderError};
 use crate::CommandInfo;
 
 #[async_trait]
@@ -15,18 +16,24 @@ impl CommandInfo for ProbeRSConnection {
         settings: &BoardSettings,
     ) -> Result<GeneralAttributes, TockloaderError> {
         if !self.is_open() {
-            return Err(TockloaderError::ConnectionNotOpen);
+            return Err(ConnectionError::NotOpen.into());
         }
         let session = self.session.as_mut().expect("Board must be open");
 
-        let mut core = session
-            .core(self.target_info.core)
-            .map_err(|e| TockloaderError::CoreAccessError(self.target_info.core, e))?;
-
         // TODO(george-cosma): extract these informations without bootloader
-        let system_attributes = SystemAttributes::read_system_attributes_probe(&mut core)?;
+        let system_attributes = {
+            let mut core = session.core(self.target_info.core).map_err(|source| {
+                ConnectionError::CoreAccess {
+                    core: self.target_info.core,
+                    source,
+                }
+            })?;
+            SystemAttributes::read_system_attributes_probe(&mut core)?
+        };
+
+        let mut flash = ProbeFlash::new(session, self.target_info.core, settings.start_address);
         let app_attributes =
-            AppAttributes::read_apps_data_probe(&mut core, settings.start_address)?;
+            AppAttributes::read_apps_data(&mut flash, settings.start_address).await?;
 
         Ok(GeneralAttributes::new(system_attributes, app_attributes))
     }
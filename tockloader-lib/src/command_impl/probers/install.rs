@@ -1,11 +1,14 @@
 use async_trait::async_trait;
 use probe_rs::flashing::DownloadOptions;
-use probe_rs::MemoryInterface;
+use probe_rs::{MemoryInterface, Session};
 use tbf_parser::parse::parse_tbf_header_lengths;
+use tbf_parser::types::InitialTbfParseError;
+use tracing::debug;
 
 use crate::board_settings::BoardSettings;
 use crate::connection::{Connection, ProbeRSConnection};
-use crate::errors::TockloaderError;
+use crate::errors::{BoardError, ConnectionError, FlashError, TockloaderError};
+use crate::progress::{Phase, Progress};
 use crate::tabs::tab::Tab;
 use crate::CommandInstall;
 
@@ -15,61 +18,58 @@ impl CommandInstall for ProbeRSConnection {
         &mut self,
         settings: &BoardSettings,
         tab_file: Tab,
+        progress: &Progress,
     ) -> Result<(), TockloaderError> {
         if !self.is_open() {
-            return Err(TockloaderError::ConnectionNotOpen);
+            return Err(ConnectionError::NotOpen.into());
         }
         let session = self.session.as_mut().expect("Board must be open");
+        let core_index = self.target_info.core;
 
+        progress.start(Phase::Connect, None);
         let mut core = session
-            .core(self.target_info.core)
-            .map_err(|e| TockloaderError::CoreAccessError(self.target_info.core, e))?;
+            .core(core_index)
+            .map_err(|source| ConnectionError::CoreAccess {
+                core: core_index,
+                source,
+            })?;
+        progress.finish(Phase::Connect);
 
         // TODO(george-cosma): extract these informations without bootloader
         // TODO(george-cosma): extract board name and kernel version to verify app compatability
 
         let mut address = settings.start_address;
 
-        // TODO(george-cosma): double-check/rework this
-
-        // Read a block of 200 8-bit words// Loop to check if there are another apps installed
+        // Skip over the apps that are already installed, including the ones
+        // that can not be parsed. Only their lengths are needed.
+        progress.start(Phase::Scan, None);
         loop {
-            let mut buff = vec![0u8; 200];
-            core.read(address, &mut buff)
-                .map_err(TockloaderError::ProbeRsReadError)?;
-
-            let (_ver, _header_len, whole_len) = match parse_tbf_header_lengths(
-                &buff[0..8]
-                    .try_into()
-                    .expect("Buffer length must be at least 8 bytes long."),
-            ) {
-                Ok((ver, header_len, whole_len)) if header_len != 0 => (ver, header_len, whole_len),
+            progress.advance(Phase::Scan, address - settings.start_address);
+            let mut lengths = [0u8; 8];
+            core.read(address, &mut lengths)
+                .map_err(|source| FlashError::Read {
+                    address,
+                    length: lengths.len(),
+                    source,
+                })?;
+            match parse_tbf_header_lengths(&lengths) {
+                Ok((_, _, total_size)) => address += total_size as u64,
+                Err(InitialTbfParseError::InvalidHeader(size)) if size >= 8 => {
+                    address += size as u64
+                }
                 _ => break, // No more apps
-            };
-            address += whole_len as u64;
+            }
         }
+        progress.finish(Phase::Scan);
 
         // TODO: extract arch(?)
         let arch = settings
             .arch
             .clone()
-            .ok_or(TockloaderError::MisconfiguredBoard(
-                "No architecture found.".to_owned(),
-            ))?;
+            .ok_or(BoardError::MissingAttribute("arch"))?;
 
-        let mut binary = tab_file.extract_binary(&arch)?;
-        let size = binary.len() as u64;
-
-        // Make sure the app is aligned to a multiple of its size
-        let multiple = address / size;
-
-        let (new_address, _gap_size) = if multiple * size != address {
-            let new_address = ((address + size) / size) * size;
-            let gap_size = new_address - address;
-            (new_address, gap_size)
-        } else {
-            (address, 0)
-        };
+        // The app may be placed past `address`, with padding in front of it.
+        let mut binary = tab_file.install_data(&arch, address)?;
 
         // TODO(george-cosma): This point MIGHT mark a good point to split
         // this function (for probe-rs).
@@ -88,11 +88,11 @@ impl CommandInstall for ProbeRSConnection {
 
         if needs_padding {
             let remaining = page_size - (binary.len() % page_size);
-            dbg!(remaining);
             for _i in 0..remaining {
                 binary.push(0xFF);
             }
         }
+        let size = binary.len() as u64;
 
         // Get indices of pages that have valid data to write
         let mut valid_pages: Vec<u8> = Vec::new();
@@ -130,8 +130,11 @@ impl CommandInstall for ProbeRSConnection {
             valid_pages.push(i);
         }
 
+        progress.start(Phase::Write, Some((valid_pages.len() * page_size) as u64));
+        let mut written = Vec::with_capacity(valid_pages.len());
         for i in valid_pages {
-            println!("Writing page number {i}");
+            progress.check_cancelled()?;
+
             // Create the packet that we send to the bootloader. First four
             // bytes are the address of the page
             let mut pkt = Vec::new();
@@ -143,24 +146,68 @@ impl CommandInstall for ProbeRSConnection {
             {
                 pkt.push(b);
             }
+            let page_address = (address as u32 + (i as usize * page_size) as u32).into();
+            let write_error = |source| FlashError::Write {
+                address: page_address,
+                length: page_size,
+                source,
+            };
+            debug!(address = format_args!("{page_address:#x}"), "Writing page");
             let mut loader = session.target().flash_loader();
 
-            loader
-                .add_data(
-                    (new_address as u32 + (i as usize * page_size) as u32).into(),
-                    &pkt,
-                )
-                .map_err(TockloaderError::ProbeRsWriteError)?;
+            loader.add_data(page_address, &pkt).map_err(write_error)?;
 
             let mut options = DownloadOptions::default();
             options.keep_unwritten_bytes = true;
 
             // Finally, the data can be programmed
-            loader
-                .commit(session, options)
-                .map_err(TockloaderError::ProbeRsWriteError)?;
+            loader.commit(session, options).map_err(write_error)?;
+
+            written.push((page_address, pkt));
+            progress.advance(Phase::Write, (written.len() * page_size) as u64);
+        }
+        progress.finish(Phase::Write);
+
+        verify_pages(session, core_index, &written, progress)
+    }
+}
+
+/// Read back the pages that were written, and check that they hold what was
+/// written to them.
+#[tracing::instrument(level = "debug", skip_all, fields(pages = pages.len()))]
+fn verify_pages(
+    session: &mut Session,
+    core_index: usize,
+    pages: &[(u64, Vec<u8>)],
+    progress: &Progress,
+) -> Result<(), TockloaderError> {
+    let mut core = session
+        .core(core_index)
+        .map_err(|source| ConnectionError::CoreAccess {
+            core: core_index,
+            source,
+        })?;
+
+    let total = pages.iter().map(|(_, page)| page.len() as u64).sum();
+    progress.start(Phase::Verify, Some(total));
+    let mut done = 0;
+    for (address, page) in pages {
+        progress.check_cancelled()?;
+
+        let mut data = vec![0u8; page.len()];
+        core.read(*address, &mut data)
+            .map_err(|source| FlashError::Read {
+                address: *address,
+                length: data.len(),
+                source,
+            })?;
+        if data != *page {
+            return Err(FlashError::VerifyFailed { address: *address }.into());
         }
 
-        Ok(())
+        done += page.len() as u64;
+        progress.advance(Phase::Verify, done);
     }
+    progress.finish(Phase::Verify);
+    Ok(())
 }
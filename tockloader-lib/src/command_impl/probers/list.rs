@@ -2,8 +2,9 @@ use async_trait::async_trait;
 
 use crate::attributes::app_attributes::AppAttributes;
 use crate::board_settings::BoardSettings;
+use crate::command_impl::probers::ProbeFlash;
 use crate::connection::{Connection, ProbeRSConnection};
-use crate::errors::TockloaderError;
+use crate::errors::{ConnectionError, TockloaderError};
 use crate::CommandList;
 
 #[async_trait]
@@ -13,14 +14,11 @@ impl CommandList for ProbeRSConnection {
         settings: &BoardSettings,
     ) -> Result<Vec<AppAttributes>, TockloaderError> {
         if !self.is_open() {
-            return Err(TockloaderError::ConnectionNotOpen);
+            return Err(ConnectionError::NotOpen.into());
         }
         let session = self.session.as_mut().expect("Board must be open");
 
-        let mut core = session
-            .core(self.target_info.core)
-            .map_err(|e| TockloaderError::CoreAccessError(self.target_info.core, e))?;
-
-        AppAttributes::read_apps_data_probe(&mut core, settings.start_address)
+        let mut flash = ProbeFlash::new(session, self.target_info.core, settings.start_address);
+        AppAttributes::read_apps_data(&mut flash, settings.start_address).await
     }
 }
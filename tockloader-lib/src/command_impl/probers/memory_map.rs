@@ -0,0 +1,26 @@
+use async_trait::async_trait;
+
+use crate::attributes::memory_map::MemoryMap;
+use crate::board_settings::BoardSettings;
+use crate::command_impl::probers::flash_end;
+use crate::connection::ProbeRSConnection;
+use crate::errors::TockloaderError;
+use crate::{CommandInfo, CommandMemoryMap};
+
+#[async_trait]
+impl CommandMemoryMap for ProbeRSConnection {
+    async fn memory_map(&mut self, settings: &BoardSettings) -> Result<MemoryMap, TockloaderError> {
+        let attributes = self.info(settings).await?;
+        let session = self.session.as_ref().expect("Board must be open");
+
+        // The end of the flash bank holding the apps is known by probe-rs.
+        let flash_end = flash_end(session, settings.start_address);
+
+        MemoryMap::new(
+            &attributes.system,
+            &attributes.apps,
+            settings.start_address,
+            flash_end,
+        )
+    }
+}
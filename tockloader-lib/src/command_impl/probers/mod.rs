@@ -1,3 +1,119 @@
+pub mod attributes;
+pub mod erase_apps;
+pub mod flash_kernel;
 pub mod info;
 pub mod install;
 pub mod list;
+pub mod memory_map;
+pub mod read_write;
+
+use std::cell::Cell;
+
+use async_trait::async_trait;
+use probe_rs::config::MemoryRegion;
+use probe_rs::flashing::{DownloadOptions, FlashProgress, ProgressEvent as FlashEvent};
+use probe_rs::{MemoryInterface, Session};
+
+use crate::attributes::app_attributes::FlashReader;
+use crate::errors::{ConnectionError, FlashError, TockloaderError};
+use crate::progress::{Phase, Progress};
+
+/// Options for the probe-rs flash loader that keep the bytes around the data
+/// being written, and report the erase and write steps of the loader to
+/// `progress`. The loader can not be stopped once it started, so cancellation
+/// has to be checked before committing.
+pub(crate) fn download_options(progress: &Progress) -> DownloadOptions {
+    let mut options = DownloadOptions::default();
+    options.keep_unwritten_bytes = true;
+
+    let progress = progress.clone();
+    let erase_total = Cell::new(0);
+    let done = Cell::new(0);
+    options.progress = Some(FlashProgress::new(move |event| match event {
+        FlashEvent::Initialized { flash_layout } => {
+            erase_total.set(flash_layout.sectors().iter().map(|s| s.size()).sum());
+        }
+        FlashEvent::StartedErasing => {
+            done.set(0);
+            progress.start(Phase::Erase, Some(erase_total.get()));
+        }
+        FlashEvent::SectorErased { size, .. } => {
+            done.set(done.get() + size);
+            progress.advance(Phase::Erase, done.get());
+        }
+        FlashEvent::FinishedErasing | FlashEvent::FailedErasing => {
+            progress.finish(Phase::Erase);
+        }
+        FlashEvent::StartedProgramming { length } => {
+            done.set(0);
+            progress.start(Phase::Write, Some(length));
+        }
+        FlashEvent::PageProgrammed { size, .. } => {
+            done.set(done.get() + size as u64);
+            progress.advance(Phase::Write, done.get());
+        }
+        FlashEvent::FinishedProgramming | FlashEvent::FailedProgramming => {
+            progress.finish(Phase::Write);
+        }
+        _ => {}
+    }));
+    options
+}
+
+/// End of the flash bank holding `address`, as known by probe-rs.
+pub(crate) fn flash_end(session: &Session, address: u64) -> Option<u64> {
+    session
+        .target()
+        .memory_map
+        .iter()
+        .find_map(|region| match region {
+            MemoryRegion::Nvm(region) if region.range.contains(&address) => Some(region.range.end),
+            _ => None,
+        })
+}
+
+/// Reads the apps through a debug probe. The core is attached for each read,
+/// as it can not be held across reads.
+pub(crate) struct ProbeFlash<'a> {
+    session: &'a mut Session,
+    core: usize,
+    flash_end: Option<u64>,
+}
+
+impl<'a> ProbeFlash<'a> {
+    /// Read from the flash bank holding `start_address`.
+    pub(crate) fn new(session: &'a mut Session, core: usize, start_address: u64) -> Self {
+        let flash_end = flash_end(session, start_address);
+        ProbeFlash {
+            session,
+            core,
+            flash_end,
+        }
+    }
+}
+
+#[async_trait]
+impl FlashReader for ProbeFlash<'_> {
+    async fn read(&mut self, address: u64, length: usize) -> Result<Vec<u8>, TockloaderError> {
+        let mut core =
+            self.session
+                .core(self.core)
+                .map_err(|source| ConnectionError::CoreAccess {
+                    core: self.core,
+                    source,
+                })?;
+
+        let mut data = vec![0u8; length];
+        core.read(address, &mut data)
+            .map_err(|source| FlashError::Read {
+                address,
+                length,
+                source,
+            })?;
+        Ok(data)
+    }
+
+    fn flash_end(&self) -> Option<u64> {
+        self.flash_end
+    }
+}
@@ -0,0 +1,92 @@
+use async_trait::async_trait;
+use probe_rs::MemoryInterface;
+
+use crate::board_settings::BoardSettings;
+use crate::command_impl::probers::download_options;
+use crate::connection::{Connection, ProbeRSConnection};
+use crate::errors::{ConnectionError, FlashError, TockloaderError};
+use crate::progress::Progress;
+use crate::{CommandList, CommandReadWrite};
+
+#[async_trait]
+impl CommandReadWrite for ProbeRSConnection {
+    #[tracing::instrument(level = "debug", skip(self), fields(address = format_args!("{address:#x}")))]
+    async fn read_memory(
+        &mut self,
+        address: u64,
+        length: usize,
+    ) -> Result<Vec<u8>, TockloaderError> {
+        if !self.is_open() {
+            return Err(ConnectionError::NotOpen.into());
+        }
+        let session = self.session.as_mut().expect("Board must be open");
+
+        let mut core =
+            session
+                .core(self.target_info.core)
+                .map_err(|source| ConnectionError::CoreAccess {
+                    core: self.target_info.core,
+                    source,
+                })?;
+
+        let mut data = vec![0u8; length];
+        core.read(address, &mut data)
+            .map_err(|source| FlashError::Read {
+                address,
+                length,
+                source,
+            })?;
+        Ok(data)
+    }
+
+    #[tracing::instrument(
+        level = "debug",
+        skip_all,
+        fields(address = format_args!("{address:#x}"), length = data.len())
+    )]
+    async fn write_memory(
+        &mut self,
+        address: u64,
+        data: &[u8],
+        progress: &Progress,
+    ) -> Result<(), TockloaderError> {
+        if !self.is_open() {
+            return Err(ConnectionError::NotOpen.into());
+        }
+        let session = self.session.as_mut().expect("Board must be open");
+
+        let mut loader = session.target().flash_loader();
+        loader
+            .add_data(address, data)
+            .map_err(|source| FlashError::Write {
+                address,
+                length: data.len(),
+                source,
+            })?;
+
+        progress.check_cancelled()?;
+        loader
+            .commit(session, download_options(progress))
+            .map_err(|source| {
+                FlashError::Write {
+                    address,
+                    length: data.len(),
+                    source,
+                }
+                .into()
+            })
+    }
+
+    async fn dump_apps(&mut self, settings: &BoardSettings) -> Result<Vec<u8>, TockloaderError> {
+        let apps = self.list(settings).await?;
+        let end = apps
+            .last()
+            .map_or(settings.start_address, |app| app.address + app.size as u64);
+
+        self.read_memory(
+            settings.start_address,
+            (end - settings.start_address) as usize,
+        )
+        .await
+    }
+}
@@ -0,0 +1,92 @@
+use async_trait::async_trait;
+use tokio_serial::SerialStream;
+
+use crate::attributes::decode::{
+    decode_attribute, encode_attribute, find_key, find_slot_for_key, DecodedAttribute,
+    ATTRIBUTES_COUNT, ATTRIBUTE_SIZE,
+};
+use crate::bootloader_serial::{issue_command, wake_bootloader, Command, Response};
+use crate::connection::{Connection, SerialConnection};
+use crate::errors::{BoardError, BootloaderError, ConnectionError, TockloaderError};
+use crate::CommandAttributes;
+
+#[async_trait]
+impl CommandAttributes for SerialConnection {
+    async fn list_attributes(&mut self) -> Result<Vec<Option<DecodedAttribute>>, TockloaderError> {
+        let stream = ping(self).await?;
+
+        let mut attributes = Vec::with_capacity(ATTRIBUTES_COUNT);
+        for index in 0..ATTRIBUTES_COUNT {
+            let (_, slot) = issue_command(
+                stream,
+                Command::GetAttribute,
+                vec![index as u8],
+                true,
+                ATTRIBUTE_SIZE,
+                Response::GetAttribute,
+            )
+            .await?;
+
+            if slot.len() < ATTRIBUTE_SIZE {
+                return Err(BootloaderError::ShortResponse {
+                    command: Command::GetAttribute,
+                    expected: ATTRIBUTE_SIZE,
+                    received: slot.len(),
+                }
+                .into());
+            }
+            attributes.push(decode_attribute(&slot));
+        }
+
+        Ok(attributes)
+    }
+
+    async fn get_attribute(&mut self, key: &str) -> Result<Option<String>, TockloaderError> {
+        let mut slots = self.list_attributes().await?;
+        Ok(find_key(&slots, key)
+            .and_then(|index| slots.swap_remove(index))
+            .map(|attribute| attribute.value))
+    }
+
+    async fn set_attribute(&mut self, key: &str, value: &str) -> Result<(), TockloaderError> {
+        let slot = encode_attribute(key, value)?;
+        let slots = self.list_attributes().await?;
+        let index = find_slot_for_key(&slots, key)?;
+
+        let stream = self.stream.as_mut().expect("Board must be open");
+        write_attribute_slot(stream, index, &slot).await
+    }
+
+    async fn remove_attribute(&mut self, key: &str) -> Result<(), TockloaderError> {
+        let slots = self.list_attributes().await?;
+        let index = find_key(&slots, key).ok_or(BoardError::AttributeNotFound(key.to_owned()))?;
+
+        let stream = self.stream.as_mut().expect("Board must be open");
+        write_attribute_slot(stream, index, &[0u8; ATTRIBUTE_SIZE]).await
+    }
+}
+
+/// Make sure the bootloader is listening before issuing commands.
+async fn ping(conn: &mut SerialConnection) -> Result<&mut SerialStream, TockloaderError> {
+    if !conn.is_open() {
+        return Err(ConnectionError::NotOpen.into());
+    }
+    let stream = conn.stream.as_mut().expect("Board must be open");
+
+    wake_bootloader(stream).await?;
+
+    Ok(stream)
+}
+
+/// Ask the bootloader to overwrite a raw attribute slot.
+async fn write_attribute_slot(
+    stream: &mut SerialStream,
+    index: usize,
+    slot: &[u8; ATTRIBUTE_SIZE],
+) -> Result<(), TockloaderError> {
+    let mut pkt = vec![index as u8];
+    pkt.extend_from_slice(slot);
+
+    issue_command(stream, Command::SetAttribute, pkt, true, 0, Response::OK).await?;
+    Ok(())
+}
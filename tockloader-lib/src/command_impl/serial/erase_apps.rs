@@ -0,0 +1,51 @@
+use async_trait::async_trait;
+use tracing::debug;
+
+use crate::attributes::app_attributes::AppAttributes;
+use crate::board_settings::BoardSettings;
+use crate::bootloader_serial::write_page;
+use crate::connection::{Connection, SerialConnection};
+use crate::erase::ErasePlan;
+use crate::errors::{ConnectionError, TockloaderError};
+use crate::progress::{Phase, Progress};
+use crate::{CommandEraseApps, CommandList};
+
+#[async_trait]
+impl CommandEraseApps for SerialConnection {
+    async fn erase_apps(
+        &mut self,
+        settings: &BoardSettings,
+        force: bool,
+        progress: &Progress,
+    ) -> Result<Vec<AppAttributes>, TockloaderError> {
+        if !self.is_open() {
+            return Err(ConnectionError::NotOpen.into());
+        }
+
+        // Listing the apps wakes the bootloader up.
+        let plan = ErasePlan::scan(self, settings, force, progress).await?;
+
+        let stream = self.stream.as_mut().expect("Board must be open");
+        let total = plan.pages.iter().map(|(_, page)| page.len() as u64).sum();
+        progress.start(Phase::Erase, Some(total));
+        let mut done = 0;
+        // The pages are in an order that keeps the app list valid, so it is
+        // safe to stop between any two of them.
+        for (address, page) in &plan.pages {
+            progress.check_cancelled()?;
+
+            debug!(address = format_args!("{address:#x}"), "Erasing page");
+            write_page(stream, *address, page).await?;
+
+            done += page.len() as u64;
+            progress.advance(Phase::Erase, done);
+        }
+        progress.finish(Phase::Erase);
+
+        progress.start(Phase::Verify, None);
+        let apps = self.list(settings).await?;
+        plan.verify(&apps)?;
+        progress.finish(Phase::Verify);
+        Ok(apps)
+    }
+}
@@ -0,0 +1,92 @@
+use async_trait::async_trait;
+use tokio_serial::SerialStream;
+
+use crate::board_settings::BoardSettings;
+use crate::bootloader_serial::{read_range, wake_bootloader, write_flash};
+use crate::connection::{Connection, SerialConnection};
+use crate::errors::{ConnectionError, TockloaderError};
+use crate::kernel_image::{KernelBounds, KernelImage, KernelSegment};
+use crate::progress::{Phase, Progress};
+use crate::{CommandFlashKernel, CommandInfo};
+
+#[async_trait]
+impl CommandFlashKernel for SerialConnection {
+    async fn flash_kernel(
+        &mut self,
+        settings: &BoardSettings,
+        kernel: KernelImage,
+        preserve_apps: bool,
+        progress: &Progress,
+    ) -> Result<(), TockloaderError> {
+        if !self.is_open() {
+            return Err(ConnectionError::NotOpen.into());
+        }
+
+        let bounds = KernelBounds::from_info(self.info(settings).await, settings)?;
+
+        let segments = kernel.into_segments(bounds.kernel_start)?;
+        bounds.check(&segments)?;
+
+        let stream = self.stream.as_mut().expect("Board must be open");
+
+        progress.start(Phase::Connect, None);
+        wake_bootloader(stream).await?;
+        progress.finish(Phase::Connect);
+
+        let apps_len = (bounds.apps_end - bounds.apps_start) as usize;
+        let backup = if preserve_apps {
+            Some(read_range(stream, bounds.apps_start, apps_len).await?)
+        } else {
+            None
+        };
+
+        write_segments(stream, &segments, progress).await?;
+
+        if let Some(backup) = backup {
+            progress.start(Phase::Verify, Some(apps_len as u64));
+            let current = read_range(stream, bounds.apps_start, apps_len).await?;
+            progress.finish(Phase::Verify);
+            if current != backup {
+                write_segments(
+                    stream,
+                    &[KernelSegment {
+                        address: bounds.apps_start,
+                        data: backup,
+                    }],
+                    progress,
+                )
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+async fn write_segments(
+    stream: &mut SerialStream,
+    segments: &[KernelSegment],
+    progress: &Progress,
+) -> Result<(), TockloaderError> {
+    let total = segments
+        .iter()
+        .map(|segment| segment.data.len() as u64)
+        .sum();
+    progress.start(Phase::Write, Some(total));
+
+    // Segments are sorted, so a page shared by two segments is read back
+    // with the first one already written.
+    let mut written = 0;
+    for segment in segments {
+        write_flash(
+            stream,
+            segment.address,
+            &segment.data,
+            progress,
+            &mut written,
+        )
+        .await?;
+    }
+    progress.finish(Phase::Write);
+    Ok(())
+}
@@ -3,6 +3,7 @@ use async_trait::async_trait;
 use crate::board_settings::BoardSettings;
 use crate::connection::SerialConnection;
 use crate::errors::TockloaderError;
+use crate::progress::Progress;
 use crate::tabs::tab::Tab;
 use crate::CommandInstall;
 
@@ -12,6 +13,7 @@ impl CommandInstall for SerialConnection {
         &mut self,
         _settings: &BoardSettings,
         _tab_file: Tab,
+        _progress: &Progress,
     ) -> Result<(), TockloaderError> {
         todo!()
     }
@@ -1,12 +1,10 @@
-use std::time::Duration;
-
 use async_trait::async_trait;
 
 use crate::attributes::app_attributes::AppAttributes;
 use crate::board_settings::BoardSettings;
-use crate::bootloader_serial::{ping_bootloader_and_wait_for_response, Response};
+use crate::bootloader_serial::wake_bootloader;
 use crate::connection::{Connection, SerialConnection};
-use crate::errors::TockloaderError;
+use crate::errors::{ConnectionError, TockloaderError};
 use crate::CommandList;
 
 #[async_trait]
@@ -16,18 +14,12 @@ impl CommandList for SerialConnection {
         settings: &BoardSettings,
     ) -> Result<Vec<AppAttributes>, TockloaderError> {
         if !self.is_open() {
-            return Err(TockloaderError::ConnectionNotOpen);
+            return Err(ConnectionError::NotOpen.into());
         }
         let stream = self.stream.as_mut().expect("Board must be open");
 
-        let response = ping_bootloader_and_wait_for_response(stream).await?;
-
-        if response as u8 != Response::Pong as u8 {
-            tokio::time::sleep(Duration::from_millis(100)).await;
-            // TODO: more robust retry system (and configurable)
-            let _ = ping_bootloader_and_wait_for_response(stream).await?;
-        }
+        wake_bootloader(stream).await?;
 
-        AppAttributes::read_apps_data_serial(stream, settings.start_address).await
+        AppAttributes::read_apps_data(stream, settings.start_address).await
     }
 }
@@ -0,0 +1,22 @@
+use async_trait::async_trait;
+
+use crate::attributes::memory_map::MemoryMap;
+use crate::board_settings::BoardSettings;
+use crate::connection::SerialConnection;
+use crate::errors::TockloaderError;
+use crate::{CommandInfo, CommandMemoryMap};
+
+#[async_trait]
+impl CommandMemoryMap for SerialConnection {
+    async fn memory_map(&mut self, settings: &BoardSettings) -> Result<MemoryMap, TockloaderError> {
+        let attributes = self.info(settings).await?;
+
+        // The bootloader has no way of telling us how large the flash is.
+        MemoryMap::new(
+            &attributes.system,
+            &attributes.apps,
+            settings.start_address,
+            None,
+        )
+    }
+}
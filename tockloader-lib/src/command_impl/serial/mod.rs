@@ -1,3 +1,8 @@
+pub mod attributes;
+pub mod erase_apps;
+pub mod flash_kernel;
 pub mod info;
 pub mod install;
 pub mod list;
+pub mod memory_map;
+pub mod read_write;
@@ -0,0 +1,58 @@
+use async_trait::async_trait;
+
+use crate::board_settings::BoardSettings;
+use crate::bootloader_serial::{read_range, wake_bootloader, write_flash};
+use crate::connection::{Connection, SerialConnection};
+use crate::errors::{ConnectionError, TockloaderError};
+use crate::progress::{Phase, Progress};
+use crate::{CommandList, CommandReadWrite};
+
+#[async_trait]
+impl CommandReadWrite for SerialConnection {
+    async fn read_memory(
+        &mut self,
+        address: u64,
+        length: usize,
+    ) -> Result<Vec<u8>, TockloaderError> {
+        if !self.is_open() {
+            return Err(ConnectionError::NotOpen.into());
+        }
+        let stream = self.stream.as_mut().expect("Board must be open");
+
+        wake_bootloader(stream).await?;
+
+        read_range(stream, address, length).await
+    }
+
+    async fn write_memory(
+        &mut self,
+        address: u64,
+        data: &[u8],
+        progress: &Progress,
+    ) -> Result<(), TockloaderError> {
+        if !self.is_open() {
+            return Err(ConnectionError::NotOpen.into());
+        }
+        let stream = self.stream.as_mut().expect("Board must be open");
+
+        wake_bootloader(stream).await?;
+
+        progress.start(Phase::Write, Some(data.len() as u64));
+        write_flash(stream, address, data, progress, &mut 0).await?;
+        progress.finish(Phase::Write);
+        Ok(())
+    }
+
+    async fn dump_apps(&mut self, settings: &BoardSettings) -> Result<Vec<u8>, TockloaderError> {
+        let apps = self.list(settings).await?;
+        let end = apps
+            .last()
+            .map_or(settings.start_address, |app| app.address + app.size as u64);
+
+        self.read_memory(
+            settings.start_address,
+            (end - settings.start_address) as usize,
+        )
+        .await
+    }
+}
@@ -1,21 +1,41 @@
 use std::time::Duration;
 
 use async_trait::async_trait;
-use probe_rs::probe::DebugProbeInfo;
+use probe_rs::probe::{DebugProbeInfo, WireProtocol};
 use probe_rs::{Permissions, Session};
 use tokio::io::AsyncWriteExt;
 use tokio_serial::{FlowControl, Parity, SerialPort, SerialStream, StopBits};
+use tracing::info;
 
-use crate::errors::TockloaderError;
+use crate::errors::{ConnectionError, TockloaderError};
 
 pub struct ProbeTargetInfo {
     pub chip: String,
     pub core: usize,
+    /// Wire protocol used to talk to the target. If `None`, the probe's own
+    /// default is used.
+    pub protocol: Option<WireProtocol>,
+    /// Clock speed of the wire protocol in kHz. If `None`, the probe's own
+    /// default is used.
+    pub speed_khz: Option<u32>,
+    /// Hold the target in reset while attaching. Required by chips that
+    /// remap or disable the debug pins once the firmware is running.
+    pub connect_under_reset: bool,
+    /// Allow probe-rs to erase the whole chip, for example to unlock a
+    /// read-protected device.
+    pub allow_erase_all: bool,
 }
 
 impl ProbeTargetInfo {
     pub fn default(chip: String) -> Self {
-        Self { chip, core: 0 }
+        Self {
+            chip,
+            core: 0,
+            protocol: None,
+            speed_khz: None,
+            connect_under_reset: false,
+            allow_erase_all: false,
+        }
     }
 }
 
@@ -74,24 +94,53 @@ impl ProbeRSConnection {
 
 #[async_trait]
 impl Connection for ProbeRSConnection {
+    #[tracing::instrument(
+        skip_all,
+        fields(probe = %self.debug_probe.identifier, chip = %self.target_info.chip),
+        err(level = "debug")
+    )]
     async fn open(&mut self) -> Result<(), TockloaderError> {
-        let probe = self
+        let mut probe = self
             .debug_probe
             .open()
-            .map_err(TockloaderError::ProbeRsInitializationError)?;
+            .map_err(ConnectionError::ProbeRsInitialization)?;
+
+        // Protocol and speed can only be changed before attaching.
+        if let Some(protocol) = self.target_info.protocol {
+            probe
+                .select_protocol(protocol)
+                .map_err(ConnectionError::ProbeRsInitialization)?;
+        }
 
-        self.session = Some(
+        if let Some(speed_khz) = self.target_info.speed_khz {
             probe
-                .attach(&self.target_info.chip, Permissions::default())
-                .map_err(TockloaderError::ProbeRsCommunicationError)?,
-        );
+                .set_speed(speed_khz)
+                .map_err(ConnectionError::ProbeRsInitialization)?;
+        }
+
+        let permissions = if self.target_info.allow_erase_all {
+            Permissions::new().allow_erase_all()
+        } else {
+            Permissions::default()
+        };
+
+        let session = if self.target_info.connect_under_reset {
+            probe.attach_under_reset(&self.target_info.chip, permissions)
+        } else {
+            probe.attach(&self.target_info.chip, permissions)
+        };
+
+        self.session = Some(session.map_err(ConnectionError::ProbeRsCommunication)?);
+        info!("Attached to the target");
 
         Ok(())
     }
 
     async fn close(&mut self) -> Result<(), TockloaderError> {
         // Session implements Drop, so we don't need to explicitly close it.
-        self.session = None;
+        if self.session.take().is_some() {
+            info!(chip = %self.target_info.chip, "Detached from the target");
+        }
         Ok(())
     }
 
@@ -121,6 +170,11 @@ impl SerialConnection {
 
 #[async_trait]
 impl Connection for SerialConnection {
+    #[tracing::instrument(
+        skip_all,
+        fields(port = %self.port, baud_rate = self.target_info.baud_rate),
+        err(level = "debug")
+    )]
     async fn open(&mut self) -> Result<(), TockloaderError> {
         let builder = tokio_serial::new(&self.port, self.target_info.baud_rate)
             .parity(self.target_info.parity)
@@ -129,22 +183,24 @@ impl Connection for SerialConnection {
             .timeout(self.target_info.timeout);
 
         let mut stream =
-            SerialStream::open(&builder).map_err(TockloaderError::SerialInitializationError)?;
+            SerialStream::open(&builder).map_err(ConnectionError::SerialInitialization)?;
 
         stream
             .write_request_to_send(self.target_info.request_to_send)
-            .map_err(TockloaderError::SerialInitializationError)?;
+            .map_err(ConnectionError::SerialInitialization)?;
         stream
             .write_data_terminal_ready(self.target_info.data_terminal_ready)
-            .map_err(TockloaderError::SerialInitializationError)?;
+            .map_err(ConnectionError::SerialInitialization)?;
 
         self.stream = Some(stream);
+        info!("Opened serial port");
         Ok(())
     }
 
     async fn close(&mut self) -> Result<(), TockloaderError> {
         if let Some(mut stream) = self.stream.take() {
-            stream.shutdown().await?;
+            stream.shutdown().await.map_err(ConnectionError::SerialIo)?;
+            info!(port = %self.port, "Closed serial port");
         }
         Ok(())
     }
@@ -0,0 +1,174 @@
+// Licensed under the Apache License, Version 2.0 or the MIT License.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+// Copyright OXIDOS AUTOMOTIVE 2024.
+
+use std::fs;
+use std::path::Path;
+
+use rsa::pkcs1::DecodeRsaPrivateKey;
+use rsa::pkcs8::DecodePrivateKey;
+use rsa::traits::PublicKeyParts;
+use rsa::{BigUint, Pkcs1v15Sign, RsaPrivateKey};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use tbf_parser::builder::{reserve_footer_space, write_footer};
+use tbf_parser::owned::parse_owned_tbf_header;
+use tbf_parser::parse::parse_tbf_header_lengths;
+use tbf_parser::types::{TbfFooterV2Credentials, TbfFooterV2RSA, TbfFooterV2SHA};
+
+use crate::errors::{ParseError, TabError, TockloaderError};
+
+/// Public exponent the kernel assumes for RSA keys, as footers only hold the
+/// modulus.
+const RSA_PUBLIC_EXPONENT: u32 = 65537;
+
+/// A credentials footer to add to a TBF.
+pub enum Credential {
+    Sha256,
+    Sha384,
+    Sha512,
+    /// A PKCS#1 v1.5 signature of the SHA-512 hash, with a 3072 or 4096 bit
+    /// key.
+    Rsa(Box<RsaPrivateKey>),
+}
+
+impl Credential {
+    /// Read an RSA private key from a PKCS#8 or PKCS#1 PEM file.
+    pub fn read_rsa_key(path: &Path) -> Result<Self, TockloaderError> {
+        let pem = fs::read_to_string(path).map_err(|source| TockloaderError::File {
+            path: path.to_owned(),
+            source,
+        })?;
+        let key = RsaPrivateKey::from_pkcs8_pem(&pem)
+            .or_else(|_| RsaPrivateKey::from_pkcs1_pem(&pem))
+            .map_err(|e| TabError::InvalidSigningKey(e.to_string()))?;
+
+        if key.size() != 384 && key.size() != 512 {
+            return Err(TabError::InvalidSigningKey(format!(
+                "{} bit keys are not supported, use 3072 or 4096 bits.",
+                key.size() * 8
+            ))
+            .into());
+        }
+        if *key.e() != BigUint::from(RSA_PUBLIC_EXPONENT) {
+            return Err(TabError::InvalidSigningKey(format!(
+                "The public exponent must be {RSA_PUBLIC_EXPONENT}."
+            ))
+            .into());
+        }
+        Ok(Credential::Rsa(Box::new(key)))
+    }
+
+    /// Size of the encoded footer: the TLV header, the format and the data.
+    fn footer_size(&self) -> u32 {
+        8 + match self {
+            Credential::Sha256 => 32,
+            Credential::Sha384 => 48,
+            Credential::Sha512 => 64,
+            Credential::Rsa(key) => 2 * key.size() as u32,
+        }
+    }
+
+    /// Compute the footer for the integrity region of a TBF.
+    fn compute(&self, integrity_region: &[u8]) -> Result<TbfFooterV2Credentials, TockloaderError> {
+        let footer = match self {
+            Credential::Sha256 => TbfFooterV2Credentials::SHA256(TbfFooterV2SHA::new(
+                Sha256::digest(integrity_region).into(),
+            )),
+            Credential::Sha384 => TbfFooterV2Credentials::SHA384(TbfFooterV2SHA::new(
+                Sha384::digest(integrity_region).into(),
+            )),
+            Credential::Sha512 => TbfFooterV2Credentials::SHA512(TbfFooterV2SHA::new(
+                Sha512::digest(integrity_region).into(),
+            )),
+            Credential::Rsa(key) => {
+                let signature = key
+                    .sign(
+                        Pkcs1v15Sign::new::<Sha512>(),
+                        &Sha512::digest(integrity_region),
+                    )
+                    .map_err(|e| TabError::InvalidSigningKey(e.to_string()))?;
+                let public_key = key.n().to_bytes_be();
+
+                // Both are exactly as long as the key, which was checked.
+                let wrong_size = |_| TabError::InvalidSigningKey("Unexpected key size.".to_owned());
+                match key.size() {
+                    384 => TbfFooterV2Credentials::Rsa3072Key(TbfFooterV2RSA::new(
+                        public_key.try_into().map_err(wrong_size)?,
+                        signature.try_into().map_err(wrong_size)?,
+                    )),
+                    _ => TbfFooterV2Credentials::Rsa4096Key(TbfFooterV2RSA::new(
+                        public_key.try_into().map_err(wrong_size)?,
+                        signature.try_into().map_err(wrong_size)?,
+                    )),
+                }
+            }
+        };
+        Ok(footer)
+    }
+}
+
+/// Add credentials footers to a TBF, after the footers it already has.
+///
+/// Apps with a Main header are converted to a Program header, and the TBF is
+/// grown if there is not enough reserved footer space.
+pub fn sign_tbf(tbf: &[u8], credentials: &[Credential]) -> Result<Vec<u8>, TockloaderError> {
+    // Reserving space changes the header, so it must happen before hashing.
+    let footers_size = credentials.iter().map(Credential::footer_size).sum();
+    let mut tbf = reserve_footer_space(tbf, footers_size).map_err(TabError::TbfBuild)?;
+
+    let (version, header_size, _) = parse_tbf_header_lengths(
+        &tbf[0..8]
+            .try_into()
+            .expect("Buffer length must be at least 8 bytes long."),
+    )
+    .expect("The header was checked when reserving space");
+    let header =
+        parse_owned_tbf_header(&tbf[0..header_size as usize], version).map_err(ParseError::Tbf)?;
+    let integrity_region = tbf[0..header.get_binary_end() as usize].to_vec();
+
+    for credential in credentials {
+        let footer = credential.compute(&integrity_region)?;
+        write_footer(&mut tbf, &footer).map_err(TabError::TbfBuild)?;
+    }
+    Ok(tbf)
+}
+
+#[cfg(test)]
+mod test {
+    use tbf_parser::builder::TbfHeaderBuilder;
+    use tbf_parser::parse::parse_tbf_footer;
+    use tbf_parser::verify::{check_credentials, CredentialsCheck};
+
+    use super::*;
+
+    #[test]
+    fn sign_with_hashes() {
+        let mut builder = TbfHeaderBuilder::new();
+        builder.set_program_version(None);
+        builder.set_package_name("blink").unwrap();
+        let tbf = builder.build(&[0x5A; 100], 0, 256).unwrap();
+
+        let signed = sign_tbf(&tbf, &[Credential::Sha256, Credential::Sha512]).unwrap();
+        let (version, header_size, total_size) =
+            parse_tbf_header_lengths(&signed[0..8].try_into().unwrap())
+                .ok()
+                .unwrap();
+        assert_eq!(total_size as usize, signed.len());
+        let header = parse_owned_tbf_header(&signed[0..header_size as usize], version).unwrap();
+        let binary_end = header.get_binary_end() as usize;
+
+        let mut offset = binary_end;
+        for expected in [
+            TbfFooterV2Credentials::SHA256(TbfFooterV2SHA::new([0; 32])),
+            TbfFooterV2Credentials::SHA512(TbfFooterV2SHA::new([0; 64])),
+        ] {
+            let (footer, length) = parse_tbf_footer(&signed[offset..]).unwrap();
+            assert_eq!(footer.get_type(), expected.get_type());
+            assert_eq!(
+                check_credentials(&signed[0..binary_end], &footer),
+                CredentialsCheck::Valid
+            );
+            offset += 4 + length as usize;
+        }
+    }
+}
@@ -0,0 +1,255 @@
+// Licensed under the Apache License, Version 2.0 or the MIT License.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+// Copyright OXIDOS AUTOMOTIVE 2024.
+
+//! Erasing the apps of a board, shared by both connection types.
+//!
+//! The app region is read once and the new layout is computed here. Sticky
+//! apps stay where they are, so they keep the alignment they were installed
+//! with. The apps before them are replaced by padding, and the ones after the
+//! last sticky app are erased. The connections only write the pages that
+//! change, then check the result with [`ErasePlan::verify`].
+
+use tbf_parser::builder::build_padding;
+use tracing::info;
+
+use crate::attributes::app_attributes::AppAttributes;
+use crate::board_settings::BoardSettings;
+use crate::bootloader_serial::PAGE_SIZE;
+use crate::errors::{FlashError, TockloaderError};
+use crate::progress::{Phase, Progress};
+use crate::{CommandList, CommandReadWrite};
+
+/// The pages to write to erase the apps, and the apps left afterwards.
+pub(crate) struct ErasePlan {
+    /// Page-aligned addresses of the pages that change, with their new
+    /// contents, in increasing address order. Writing them in this order
+    /// keeps the app list valid after every page: the first page of an
+    /// erased app either turns it into padding of the same size or ends the
+    /// list, and the pages after it do not matter to the kernel.
+    pub(crate) pages: Vec<(u64, Vec<u8>)>,
+    /// Addresses of the TBF objects that `list` must return afterwards: the
+    /// apps that are kept, and the padding that replaces the erased apps
+    /// before them.
+    kept: Vec<u64>,
+}
+
+impl ErasePlan {
+    /// Walk the apps of the board and work out how to erase them. Sticky apps
+    /// are kept unless `force` is set.
+    pub(crate) async fn scan<C>(
+        connection: &mut C,
+        settings: &BoardSettings,
+        force: bool,
+        progress: &Progress,
+    ) -> Result<ErasePlan, TockloaderError>
+    where
+        C: CommandList + CommandReadWrite + Send,
+    {
+        progress.start(Phase::Scan, None);
+        let apps = connection.list(settings).await?;
+        let end = apps
+            .last()
+            .map_or(settings.start_address, |app| app.address + app.size as u64);
+        let length = (end - settings.start_address).next_multiple_of(PAGE_SIZE as u64);
+        let region = connection
+            .read_memory(settings.start_address, length as usize)
+            .await?;
+        progress.finish(Phase::Scan);
+
+        ErasePlan::new(&region, settings.start_address, &apps, force)
+    }
+
+    /// Lay out the apps that are kept from `region`, the page-aligned app
+    /// region starting at `start` which holds `apps`.
+    fn new(
+        region: &[u8],
+        start: u64,
+        apps: &[AppAttributes],
+        force: bool,
+    ) -> Result<ErasePlan, TockloaderError> {
+        let is_kept = |app: &AppAttributes| !force && app.tbf_header.sticky();
+        let kept_end = apps
+            .iter()
+            .filter(|app| is_kept(app))
+            .map(|app| app.address + app.size as u64)
+            .max()
+            .unwrap_or(start);
+
+        let mut new_region = region.to_vec();
+        let mut kept = Vec::new();
+        for app in apps {
+            let offset = (app.address - start) as usize;
+            let size = app.size as usize;
+            if is_kept(app) {
+                info!(
+                    name = app.tbf_header.get_package_name().unwrap_or_default(),
+                    address = format_args!("{:#x}", app.address),
+                    "Keeping sticky app"
+                );
+            } else if app.address < kept_end {
+                let padding = build_padding(app.size).map_err(|source| FlashError::Padding {
+                    address: app.address,
+                    source,
+                })?;
+                new_region[offset..offset + size].copy_from_slice(&padding);
+            } else {
+                new_region[offset..offset + size].fill(0xFF);
+                continue;
+            }
+            kept.push(app.address);
+        }
+
+        let pages = region
+            .chunks(PAGE_SIZE)
+            .zip(new_region.chunks(PAGE_SIZE))
+            .enumerate()
+            .filter(|(_, (old, new))| old != new)
+            .map(|(i, (_, new))| (start + (i * PAGE_SIZE) as u64, new.to_vec()))
+            .collect();
+
+        Ok(ErasePlan { pages, kept })
+    }
+
+    /// Check that `apps`, listed after writing the pages, are exactly the
+    /// ones that were kept.
+    pub(crate) fn verify(&self, apps: &[AppAttributes]) -> Result<(), TockloaderError> {
+        let found: Vec<u64> = apps.iter().map(|app| app.address).collect();
+        if found != self.kept {
+            return Err(FlashError::AppsNotErased {
+                expected: self.kept.clone(),
+                found,
+            }
+            .into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tbf_parser::builder::TbfHeaderBuilder;
+    use tbf_parser::owned::parse_owned_tbf_list;
+    use tbf_parser::parse::TbfListEntry;
+
+    use super::*;
+
+    const START: u64 = 0x40000;
+
+    fn app(name: &str, sticky: bool, size: u32) -> Vec<u8> {
+        let mut builder = TbfHeaderBuilder::new();
+        builder.set_package_name(name).unwrap();
+        builder.set_sticky(sticky);
+        builder.build(&[0xAA; 64], 0, size).unwrap()
+    }
+
+    /// Lay out `tbfs` from `START`, padded to whole pages of erased flash.
+    fn region(tbfs: &[Vec<u8>]) -> Vec<u8> {
+        let mut region = tbfs.concat();
+        region.resize(region.len().next_multiple_of(PAGE_SIZE), 0xFF);
+        region
+    }
+
+    fn write_page(region: &mut [u8], address: u64, page: &[u8]) {
+        let offset = (address - START) as usize;
+        region[offset..offset + page.len()].copy_from_slice(page);
+    }
+
+    /// Write the pages of `plan` to `region` and list the apps left.
+    fn apply(plan: &ErasePlan, region: &mut [u8]) -> Vec<AppAttributes> {
+        for (address, page) in &plan.pages {
+            write_page(region, *address, page);
+        }
+        AppAttributes::read_apps_data_image(region, START, START).unwrap()
+    }
+
+    #[test]
+    fn keeps_sticky_apps_in_place() {
+        let mut region = region(&[
+            app("blink", false, 0x400),
+            app("sensors", true, 0x400),
+            app("hello", false, 0x200),
+        ]);
+        let apps = AppAttributes::read_apps_data_image(&region, START, START).unwrap();
+
+        let plan = ErasePlan::new(&region, START, &apps, false).unwrap();
+        // The pages of the sticky app are left alone, and so is the second
+        // page of the first app, which already holds only zeros.
+        let pages: Vec<u64> = plan.pages.iter().map(|(address, _)| *address).collect();
+        assert_eq!(pages, [START, START + 0x800]);
+
+        let left = apply(&plan, &mut region);
+        assert_eq!(left.len(), 2);
+        assert!(!left[0].tbf_header.is_app());
+        assert_eq!(left[0].size, 0x400);
+        assert_eq!(left[1].address, START + 0x400);
+        assert_eq!(left[1].tbf_header.get_package_name(), Some("sensors"));
+        assert!(plan.verify(&left).is_ok());
+        assert!(plan.verify(&apps).is_err());
+    }
+
+    #[test]
+    fn force_erases_sticky_apps() {
+        let mut region = region(&[app("sensors", true, 0x200), app("blink", false, 0x200)]);
+        let apps = AppAttributes::read_apps_data_image(&region, START, START).unwrap();
+
+        let plan = ErasePlan::new(&region, START, &apps, true).unwrap();
+        assert!(plan
+            .pages
+            .iter()
+            .all(|(_, page)| page.iter().all(|b| *b == 0xFF)));
+
+        let left = apply(&plan, &mut region);
+        assert!(left.is_empty());
+        assert!(plan.verify(&left).is_ok());
+    }
+
+    #[test]
+    fn sticky_app_in_place_is_not_rewritten() {
+        let mut region = region(&[app("sensors", true, 0x200), app("blink", false, 0x200)]);
+        let apps = AppAttributes::read_apps_data_image(&region, START, START).unwrap();
+
+        let plan = ErasePlan::new(&region, START, &apps, false).unwrap();
+        assert_eq!(plan.pages.len(), 1);
+        assert_eq!(plan.pages[0].0, START + 0x200);
+
+        let left = apply(&plan, &mut region);
+        assert!(plan.verify(&left).is_ok());
+    }
+
+    #[test]
+    fn list_stays_valid_after_every_page() {
+        let mut region = region(&[
+            app("blink", false, 0x400),
+            app("sensors", true, 0x200),
+            app("hello", false, 0x400),
+            app("buttons", true, 0x200),
+            app("leds", false, 0x400),
+        ]);
+        let apps = AppAttributes::read_apps_data_image(&region, START, START).unwrap();
+
+        let plan = ErasePlan::new(&region, START, &apps, false).unwrap();
+        for (address, page) in &plan.pages {
+            write_page(&mut region, *address, page);
+            assert!(parse_owned_tbf_list(&region, START)
+                .all(|entry| !matches!(entry, TbfListEntry::Invalid { .. })));
+        }
+        let left = AppAttributes::read_apps_data_image(&region, START, START).unwrap();
+        assert!(plan.verify(&left).is_ok());
+    }
+
+    #[test]
+    fn app_too_small_for_padding() {
+        let region = region(&[app("blink", false, 0x200), app("sensors", true, 0x200)]);
+        let mut apps = AppAttributes::read_apps_data_image(&region, START, START).unwrap();
+        apps[0].size = 8;
+
+        assert!(matches!(
+            ErasePlan::new(&region, START, &apps, false),
+            Err(TockloaderError::Flash(FlashError::Padding {
+                address: START,
+                ..
+            }))
+        ));
+    }
+}
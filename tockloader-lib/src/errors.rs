@@ -2,58 +2,227 @@
 // SPDX-License-Identifier: Apache-2.0 OR MIT
 // Copyright OXIDOS AUTOMOTIVE 2024.
 
+//! Errors returned by tockloader.
+//!
+//! Every operation returns a [`TockloaderError`], which only says which part
+//! of tockloader failed. The error it wraps carries the details: the address,
+//! the bootloader command or the app involved, and the underlying error as its
+//! [`source`](std::error::Error::source). Messages never repeat their source,
+//! so that printing the whole chain shows every cause once.
+
 use std::io;
+use std::path::PathBuf;
+
+use tbf_parser::builder::TbfBuildError;
+use tbf_parser::types::TbfParseError;
 use thiserror::Error;
 
-// TODO(george-cosma): Split this. Possibly each meta-function (install, list,
-// ...) should have its own error type + error type for connection. We can have
-// more detailed errors as "sources" for more generic error types, if we TRULY
-// need to know why probe-rs failed. Or serial.
+pub use crate::bootloader_serial::{Command, Response};
 
 #[derive(Debug, Error)]
 pub enum TockloaderError {
+    #[error(transparent)]
+    Connection(#[from] ConnectionError),
+
+    #[error(transparent)]
+    Bootloader(#[from] BootloaderError),
+
+    #[error(transparent)]
+    Flash(#[from] FlashError),
+
+    #[error(transparent)]
+    Board(#[from] BoardError),
+
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+
+    #[error(transparent)]
+    Tab(#[from] TabError),
+
+    #[error("Operation was cancelled.")]
+    Cancelled,
+
+    #[error("Failed to access {}.", path.display())]
+    File {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+}
+
+/// Opening or talking to the board failed, before any command reached it.
+///
+/// These are worth retrying once the board is reconnected or reset.
+#[derive(Debug, Error)]
+pub enum ConnectionError {
     #[error("Failed due to the connection not being open.")]
-    ConnectionNotOpen,
+    NotOpen,
 
-    #[error("Error occurred while trying to access core: {0}")]
-    CoreAccessError(usize, probe_rs::Error),
+    #[error("Failed to initialize probe_rs connection due to a communication error.")]
+    ProbeRsInitialization(#[from] probe_rs::probe::DebugProbeError),
 
-    #[error("Failed to initialize probe_rs connection due to a communication error. Inner: {0}")]
-    ProbeRsInitializationError(#[from] probe_rs::probe::DebugProbeError),
+    #[error("Failed to establish communication with board.")]
+    ProbeRsCommunication(#[source] probe_rs::Error),
 
-    #[error("Failed to establish communication with board. Inner: {0}")]
-    ProbeRsCommunicationError(probe_rs::Error),
+    #[error("Error occurred while trying to access core {core}.")]
+    CoreAccess {
+        core: usize,
+        #[source]
+        source: probe_rs::Error,
+    },
 
-    #[error("Failed to read from debug probe. Inner: {0}")]
-    ProbeRsReadError(probe_rs::Error),
+    #[error("Failed to initialize serial connection due to a communication error.")]
+    SerialInitialization(#[from] tokio_serial::Error),
 
-    #[error("Failed to write binary. Inner: {0}")]
-    ProbeRsWriteError(#[from] probe_rs::flashing::FlashError),
+    #[error("Failed to perform read/write operations on serial port.")]
+    SerialIo(#[from] io::Error),
+}
 
-    #[error("Failed to initialize serial connection due to a communication error. Inner: {0}")]
-    SerialInitializationError(#[from] tokio_serial::Error),
+/// The bootloader answered a command, but not the way it should have.
+#[derive(Debug, Error)]
+pub enum BootloaderError {
+    #[error("Bootloader sent {received:#04x} instead of a response to {command:?}.")]
+    NotAResponse { command: Command, received: u8 },
+
+    #[error("Bootloader answered {command:?} with {received:#04x}, expected {expected:?}.")]
+    UnexpectedResponse {
+        command: Command,
+        expected: Response,
+        received: u8,
+    },
+
+    #[error("Bootloader sent {received} of the {expected} bytes expected for {command:?}.")]
+    ShortResponse {
+        command: Command,
+        expected: usize,
+        received: usize,
+    },
+}
 
-    #[error("Bootloader did not respond properly: {0}")]
-    BootloaderError(u8),
+impl BootloaderError {
+    /// The command the bootloader was answering.
+    pub fn command(&self) -> Command {
+        match self {
+            BootloaderError::NotAResponse { command, .. }
+            | BootloaderError::UnexpectedResponse { command, .. }
+            | BootloaderError::ShortResponse { command, .. } => *command,
+        }
+    }
+}
 
-    #[error("No binary found for {0} architecture.")]
-    NoBinaryError(String),
+/// Reading or writing the flash of the board failed.
+#[derive(Debug, Error)]
+pub enum FlashError {
+    #[error("Failed to read {length} bytes at {address:#x} from debug probe.")]
+    Read {
+        address: u64,
+        length: usize,
+        #[source]
+        source: probe_rs::Error,
+    },
+
+    #[error("Failed to write {length} bytes at {address:#x}.")]
+    Write {
+        address: u64,
+        length: usize,
+        #[source]
+        source: probe_rs::flashing::FlashError,
+    },
+
+    #[error("Flash at {address:#x} does not hold the data that was written to it.")]
+    VerifyFailed { address: u64 },
+
+    #[error("Expected the apps at {expected:#x?} to be left after erasing, found {found:#x?}.")]
+    AppsNotErased { expected: Vec<u64>, found: Vec<u64> },
+
+    #[error("Failed to replace the app at {address:#x} with padding.")]
+    Padding {
+        address: u64,
+        #[source]
+        source: TbfBuildError,
+    },
+}
 
-    #[error("App data could not be parsed.")]
-    ParsingError(tbf_parser::types::TbfParseError),
+/// The board or its bootloader attributes are not set up the way tockloader
+/// expects.
+#[derive(Debug, Error)]
+pub enum BoardError {
+    #[error("Expected board attribute '{0}' to be present.")]
+    MissingAttribute(&'static str),
+
+    #[error("No bootloader attribute with key '{0}' found.")]
+    AttributeNotFound(String),
+
+    #[error("Invalid bootloader attribute: {0}")]
+    InvalidAttribute(String),
 
-    #[error("Failed to perform read/write operations on serial port. Inner: {0}")]
-    IOError(#[from] io::Error),
+    #[error("Invalid start address '{0}'.")]
+    InvalidStartAddress(String),
 
-    #[error("Expected board attribute to be present")]
-    MisconfiguredBoard(String),
+    #[error("Data may be corrupted. Bootloader version is not UTF-8.")]
+    CorruptBootloaderVersion,
+
+    #[error("Kernel is missing the '{0}' attribute.")]
+    MissingKernelAttribute(&'static str),
+}
 
-    #[error("Failed to use tab from provided path. Inner: {0}")]
-    UnusableTab(io::Error),
+/// Data read from the board or from a file could not be parsed.
+#[derive(Debug, Error)]
+pub enum ParseError {
+    #[error("App data at {address:#x} could not be parsed.")]
+    App {
+        address: u64,
+        #[source]
+        source: TbfParseError,
+    },
+
+    #[error("TBF could not be parsed.")]
+    Tbf(#[source] TbfParseError),
+
+    #[error("Invalid kernel image: {0}")]
+    KernelImage(String),
+
+    #[error("Invalid app ELF: {0}")]
+    Elf(String),
+}
+
+/// A TAB could not be read, written or used.
+#[derive(Debug, Error)]
+pub enum TabError {
+    #[error("Failed to use tab.")]
+    Unusable(#[source] io::Error),
 
-    #[error("Failed to parse metadata. Inner: {0}")]
-    InvalidMetadata(toml::de::Error),
+    #[error("Failed to write tab.")]
+    Unwritable(#[source] io::Error),
 
     #[error("No metadata.toml found.")]
     NoMetadata,
+
+    #[error("Failed to parse metadata.")]
+    InvalidMetadata(#[source] toml::de::Error),
+
+    #[error("Failed to write metadata.")]
+    UnwritableMetadata(#[source] toml::ser::Error),
+
+    #[error("'{0}' is not named like a TBF file, expected <arch>.tbf.")]
+    InvalidTbfName(String),
+
+    #[error("{0} does not start with a TBF header.")]
+    NoTbfHeader(String),
+
+    #[error("No binary of {app} found for {arch} architecture.")]
+    NoBinary { app: String, arch: String },
+
+    #[error("No {arch} binary of {app} can be placed after the installed apps, which end at {address:#x}.")]
+    NoFittingBinary {
+        app: String,
+        arch: String,
+        address: u64,
+    },
+
+    #[error("Failed to build TBF header.")]
+    TbfBuild(#[source] TbfBuildError),
+
+    #[error("Invalid signing key: {0}")]
+    InvalidSigningKey(String),
 }
@@ -0,0 +1,30 @@
+// Licensed under the Apache License, Version 2.0 or the MIT License.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+// Copyright OXIDOS AUTOMOTIVE 2024.
+
+use std::fs;
+use std::path::Path;
+
+use crate::errors::TockloaderError;
+
+/// A copy of the flash of a board, such as the output of the `dump` command,
+/// that can be listed and inspected without the board.
+pub struct FlashImage {
+    pub(crate) data: Vec<u8>,
+    /// Address of the first byte of `data`.
+    pub(crate) base_address: u64,
+}
+
+impl FlashImage {
+    pub fn new(data: Vec<u8>, base_address: u64) -> Self {
+        FlashImage { data, base_address }
+    }
+
+    pub fn open(path: &Path, base_address: u64) -> Result<Self, TockloaderError> {
+        let data = fs::read(path).map_err(|source| TockloaderError::File {
+            path: path.to_owned(),
+            source,
+        })?;
+        Ok(FlashImage::new(data, base_address))
+    }
+}
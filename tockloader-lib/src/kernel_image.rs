@@ -0,0 +1,329 @@
+// Licensed under the Apache License, Version 2.0 or the MIT License.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+// Copyright OXIDOS AUTOMOTIVE 2024.
+
+use std::fs;
+use std::path::Path;
+
+use object::elf::{FileHeader32, PT_LOAD};
+use object::read::elf::{FileHeader, ProgramHeader};
+use object::Endianness;
+
+use crate::attributes::general_attributes::GeneralAttributes;
+use crate::board_settings::BoardSettings;
+use crate::errors::{ParseError, TockloaderError};
+
+/// A contiguous block of data to be written at `address`.
+pub struct KernelSegment {
+    pub address: u64,
+    pub data: Vec<u8>,
+}
+
+impl KernelSegment {
+    pub fn end(&self) -> u64 {
+        self.address + self.data.len() as u64
+    }
+}
+
+/// A kernel image, as loaded from a `.bin`, `.elf` or `.hex` file.
+pub enum KernelImage {
+    /// A raw binary. If no address is given, it is written at the start of the
+    /// kernel reported by the board.
+    Binary { data: Vec<u8>, address: Option<u64> },
+    /// An image that carries its own addresses.
+    Segments(Vec<KernelSegment>),
+}
+
+impl KernelImage {
+    /// Load a kernel image, guessing its format from the file extension.
+    /// `address` is only used for raw binaries.
+    pub fn open(path: &Path, address: Option<u64>) -> Result<Self, TockloaderError> {
+        let data = fs::read(path).map_err(|source| TockloaderError::File {
+            path: path.to_owned(),
+            source,
+        })?;
+
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("elf") => Self::from_elf(&data),
+            Some("hex") | Some("ihex") => Self::from_hex(&data),
+            Some("bin") => Ok(Self::from_bin(data, address)),
+            _ => Err(ParseError::KernelImage(
+                "Unknown file extension, expected .bin, .elf or .hex.".to_owned(),
+            )
+            .into()),
+        }
+    }
+
+    pub fn from_bin(data: Vec<u8>, address: Option<u64>) -> Self {
+        KernelImage::Binary { data, address }
+    }
+
+    /// Load the `PT_LOAD` segments of a 32-bit ELF file, at their physical
+    /// addresses.
+    pub fn from_elf(data: &[u8]) -> Result<Self, TockloaderError> {
+        let invalid = |e: object::read::Error| ParseError::KernelImage(e.to_string());
+
+        let header = FileHeader32::<Endianness>::parse(data).map_err(invalid)?;
+        let endian = header.endian().map_err(invalid)?;
+
+        let mut segments = Vec::new();
+        for segment in header.program_headers(endian, data).map_err(invalid)? {
+            if segment.p_type(endian) != PT_LOAD {
+                continue;
+            }
+
+            let segment_data = segment
+                .data(endian, data)
+                .map_err(|_| ParseError::KernelImage("Truncated ELF segment.".to_owned()))?;
+            if segment_data.is_empty() {
+                continue;
+            }
+
+            segments.push(KernelSegment {
+                address: segment.p_paddr(endian) as u64,
+                data: segment_data.to_vec(),
+            });
+        }
+
+        Ok(KernelImage::Segments(merge_segments(segments)))
+    }
+
+    /// Load the data records of an Intel HEX file.
+    pub fn from_hex(data: &[u8]) -> Result<Self, TockloaderError> {
+        let text = std::str::from_utf8(data)
+            .map_err(|_| ParseError::KernelImage("HEX file is not valid text.".to_owned()))?;
+
+        let mut base_address = 0;
+        let mut segments = Vec::new();
+        for record in ihex::Reader::new(text) {
+            match record.map_err(|e| ParseError::KernelImage(e.to_string()))? {
+                ihex::Record::Data { offset, value } => segments.push(KernelSegment {
+                    address: base_address + offset as u64,
+                    data: value,
+                }),
+                ihex::Record::ExtendedSegmentAddress(address) => {
+                    base_address = (address as u64) * 16;
+                }
+                ihex::Record::ExtendedLinearAddress(address) => {
+                    base_address = (address as u64) << 16;
+                }
+                ihex::Record::EndOfFile
+                | ihex::Record::StartSegmentAddress { .. }
+                | ihex::Record::StartLinearAddress(_) => {}
+            }
+        }
+
+        Ok(KernelImage::Segments(merge_segments(segments)))
+    }
+
+    /// Get the segments to write, placing raw binaries at `default_address`
+    /// if they have no address of their own.
+    pub(crate) fn into_segments(
+        self,
+        default_address: Option<u64>,
+    ) -> Result<Vec<KernelSegment>, TockloaderError> {
+        let segments = match self {
+            KernelImage::Binary { data, address } => {
+                let address = address.or(default_address).ok_or(ParseError::KernelImage(
+                    "The kernel start address is unknown, please provide one.".to_owned(),
+                ))?;
+                vec![KernelSegment { address, data }]
+            }
+            KernelImage::Segments(segments) => segments,
+        };
+
+        if segments.is_empty() {
+            return Err(ParseError::KernelImage("The image contains no data.".to_owned()).into());
+        }
+        Ok(segments)
+    }
+}
+
+/// Sort segments by address and join the ones that are adjacent.
+fn merge_segments(mut segments: Vec<KernelSegment>) -> Vec<KernelSegment> {
+    segments.sort_by_key(|segment| segment.address);
+
+    let mut merged: Vec<KernelSegment> = Vec::new();
+    for segment in segments {
+        match merged.last_mut() {
+            Some(last) if last.end() == segment.address => last.data.extend(segment.data),
+            _ => merged.push(segment),
+        }
+    }
+    merged
+}
+
+/// The part of the flash a new kernel may be written to.
+pub(crate) struct KernelBounds {
+    /// Start of the current kernel, if the board has a valid one.
+    pub kernel_start: Option<u64>,
+    pub apps_start: u64,
+    /// End of the last installed app.
+    pub apps_end: u64,
+}
+
+impl KernelBounds {
+    /// Compute the bounds from the result of `info`. A board without a
+    /// bootloader or kernel has no attributes to read, which `info` reports
+    /// as a `BoardError`; then only the app start address is checked. Any
+    /// other error, like failing to read the flash, is returned.
+    pub(crate) fn from_info(
+        info: Result<GeneralAttributes, TockloaderError>,
+        settings: &BoardSettings,
+    ) -> Result<Self, TockloaderError> {
+        match info {
+            Ok(attributes) => Ok(KernelBounds::new(Some(&attributes), settings)),
+            Err(TockloaderError::Board(_)) => Ok(KernelBounds::new(None, settings)),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Compute the bounds from what was read from the board, if anything
+    /// could be read at all. A board with no kernel has no attributes.
+    fn new(attributes: Option<&GeneralAttributes>, settings: &BoardSettings) -> Self {
+        let Some(attributes) = attributes else {
+            return KernelBounds {
+                kernel_start: None,
+                apps_start: settings.start_address,
+                apps_end: settings.start_address,
+            };
+        };
+        let system = &attributes.system;
+
+        // The kernel attributes are only meaningful if a kernel is present.
+        let kernel_start = system
+            .kernel_bin_start
+            .filter(|_| system.sentinel.as_deref() == Some("TOCK"))
+            .map(u64::from);
+        let apps_start = system.appaddr.map_or(settings.start_address, |appaddr| {
+            appaddr.min(settings.start_address)
+        });
+        let apps_end = attributes
+            .apps
+            .last()
+            .map_or(apps_start, |app| app.address + app.size as u64);
+
+        KernelBounds {
+            kernel_start,
+            apps_start,
+            apps_end,
+        }
+    }
+
+    /// Make sure the kernel stays between the bootloader and the apps.
+    pub(crate) fn check(&self, segments: &[KernelSegment]) -> Result<(), TockloaderError> {
+        for segment in segments {
+            if let Some(kernel_start) = self.kernel_start {
+                if segment.address < kernel_start {
+                    return Err(ParseError::KernelImage(format!(
+                        "Data at {:#x} would overwrite the bootloader, the kernel starts at {:#x}.",
+                        segment.address, kernel_start
+                    ))
+                    .into());
+                }
+            }
+            if segment.end() > self.apps_start {
+                return Err(ParseError::KernelImage(format!(
+                    "Data up to {:#x} would overwrite the apps, which start at {:#x}.",
+                    segment.end(),
+                    self.apps_start
+                ))
+                .into());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::attributes::system_attributes::SystemAttributes;
+    use crate::errors::{BoardError, ConnectionError};
+
+    use super::*;
+
+    fn segment(address: u64, length: usize) -> KernelSegment {
+        KernelSegment {
+            address,
+            data: vec![0xAA; length],
+        }
+    }
+
+    fn settings() -> BoardSettings {
+        BoardSettings {
+            arch: None,
+            start_address: 0x40000,
+        }
+    }
+
+    #[test]
+    fn merge_sorts_and_joins_adjacent_segments() {
+        let merged = merge_segments(vec![
+            segment(0x10100, 0x100),
+            segment(0x20000, 0x10),
+            segment(0x10000, 0x100),
+        ]);
+        let ranges: Vec<_> = merged.iter().map(|s| (s.address, s.end())).collect();
+        assert_eq!(ranges, [(0x10000, 0x10200), (0x20000, 0x20010)]);
+    }
+
+    #[test]
+    fn check_keeps_kernel_between_bootloader_and_apps() {
+        let bounds = KernelBounds {
+            kernel_start: Some(0x10000),
+            apps_start: 0x40000,
+            apps_end: 0x48000,
+        };
+        assert!(bounds.check(&[segment(0x10000, 0x30000)]).is_ok());
+        assert!(bounds.check(&[segment(0xFF00, 0x200)]).is_err());
+        assert!(bounds.check(&[segment(0x3FF00, 0x101)]).is_err());
+        assert!(bounds
+            .check(&[segment(0x10000, 0x100), segment(0x40000, 0x10)])
+            .is_err());
+
+        let no_kernel = KernelBounds {
+            kernel_start: None,
+            ..bounds
+        };
+        assert!(no_kernel.check(&[segment(0, 0x100)]).is_ok());
+    }
+
+    #[test]
+    fn bounds_without_kernel_attributes() {
+        let bounds = KernelBounds::from_info(
+            Err(BoardError::CorruptBootloaderVersion.into()),
+            &settings(),
+        )
+        .unwrap();
+        assert_eq!(bounds.kernel_start, None);
+        assert_eq!((bounds.apps_start, bounds.apps_end), (0x40000, 0x40000));
+
+        let mut system = SystemAttributes::new();
+        system.kernel_bin_start = Some(0x10000);
+        system.sentinel = Some("\u{ff}\u{ff}\u{ff}\u{ff}".to_owned());
+        let bounds =
+            KernelBounds::from_info(Ok(GeneralAttributes::new(system, vec![])), &settings())
+                .unwrap();
+        assert_eq!(bounds.kernel_start, None);
+    }
+
+    #[test]
+    fn bounds_from_kernel_attributes() {
+        let mut system = SystemAttributes::new();
+        system.kernel_bin_start = Some(0x10000);
+        system.sentinel = Some("TOCK".to_owned());
+        system.appaddr = Some(0x40000);
+        let bounds =
+            KernelBounds::from_info(Ok(GeneralAttributes::new(system, vec![])), &settings())
+                .unwrap();
+        assert_eq!(bounds.kernel_start, Some(0x10000));
+        assert_eq!(bounds.apps_start, 0x40000);
+    }
+
+    #[test]
+    fn bounds_return_read_errors() {
+        assert!(
+            KernelBounds::from_info(Err(ConnectionError::NotOpen.into()), &settings()).is_err()
+        );
+    }
+}
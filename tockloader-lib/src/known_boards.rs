@@ -15,10 +15,7 @@ impl KnownBoard for NucleoF4 {
     }
 
     fn probe_target_info(&self) -> ProbeTargetInfo {
-        ProbeTargetInfo {
-            chip: "STM32F429ZIT".to_string(),
-            core: 0,
-        }
+        ProbeTargetInfo::default("STM32F429ZIT".to_string())
     }
 
     fn get_settings(&self) -> BoardSettings {
@@ -37,10 +34,7 @@ impl KnownBoard for MicrobitV2 {
     }
 
     fn probe_target_info(&self) -> ProbeTargetInfo {
-        ProbeTargetInfo {
-            chip: "nRF52833".to_string(),
-            core: 0,
-        }
+        ProbeTargetInfo::default("nRF52833".to_string())
     }
 
     fn get_settings(&self) -> BoardSettings {
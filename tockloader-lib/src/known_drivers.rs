@@ -0,0 +1,96 @@
+// Licensed under the Apache License, Version 2.0 or the MIT License.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+// Copyright OXIDOS AUTOMOTIVE 2024.
+
+/// Driver numbers of the capsules shipped with the Tock kernel. Reference:
+/// https://github.com/tock/tock/blob/master/capsules/core/src/driver.rs
+const KNOWN_DRIVERS: &[(u32, &str)] = &[
+    // Base
+    (0x00000, "Alarm"),
+    (0x00001, "Console"),
+    (0x00002, "Led"),
+    (0x00003, "Button"),
+    (0x00004, "Gpio"),
+    (0x00005, "Adc"),
+    (0x00006, "Dac"),
+    (0x00007, "AnalogComparator"),
+    (0x00008, "LowLevelDebug"),
+    (0x00009, "ReadOnlyState"),
+    (0x00010, "Pwm"),
+    (0x00011, "Servo"),
+    // Kernel
+    (0x10000, "Ipc"),
+    // HW Buses
+    (0x20001, "Spi"),
+    (0x20002, "SpiPeripheral"),
+    (0x20003, "I2cMaster"),
+    (0x20005, "UsbUser"),
+    (0x20006, "I2cMasterSlave"),
+    (0x20007, "Can"),
+    // Radio
+    (0x30000, "BleAdvertising"),
+    (0x30001, "Ieee802154"),
+    (0x30002, "Udp"),
+    (0x30003, "LoRaPhySPI"),
+    (0x30004, "LoRaPhyGPIO"),
+    (0x30005, "Thread"),
+    (0x30006, "Eui64"),
+    // Cryptography
+    (0x40001, "Rng"),
+    (0x40002, "Crc"),
+    (0x40003, "Hmac"),
+    (0x40004, "CtapHid"),
+    (0x40005, "Sha"),
+    (0x40006, "Aes"),
+    // Storage
+    (0x50000, "AppFlash"),
+    (0x50001, "NvmStorage"),
+    (0x50002, "SdCard"),
+    (0x50003, "Kv"),
+    (0x50004, "IsolatedNonvolatileStorage"),
+    // Sensors
+    (0x60000, "Temperature"),
+    (0x60001, "Humidity"),
+    (0x60002, "AmbientLight"),
+    (0x60004, "NineDof"),
+    (0x60005, "Proximity"),
+    (0x60006, "SoundPressure"),
+    (0x60007, "AirQuality"),
+    (0x60008, "Pressure"),
+    (0x60009, "Distance"),
+    (0x6000A, "Moisture"),
+    (0x6000B, "RainFall"),
+    // Sensor ICs
+    (0x70000, "Tsl2561"),
+    (0x70001, "Tmp006"),
+    (0x70004, "Lps25hb"),
+    (0x70005, "L3gd20"),
+    (0x70006, "Lsm303dlch"),
+    (0x70007, "Mlx90614"),
+    (0x70008, "Lsm6dsoxtr"),
+    // Other ICs
+    (0x80000, "Ltc294x"),
+    (0x80001, "Max17205"),
+    (0x80002, "Pca9544a"),
+    (0x80003, "GpioAsync"),
+    (0x80004, "Nrf51822Serialization"),
+    (0x80005, "Hd44780"),
+    // Misc
+    (0x90000, "Buzzer"),
+    (0x90001, "Screen"),
+    (0x90002, "Touch"),
+    (0x90003, "TextScreen"),
+    (0x90004, "SevenSegment"),
+    (0x90005, "KeyboardHid"),
+    (0x90007, "DateTime"),
+    (0x90008, "CycleCount"),
+];
+
+/// Get the name of the upstream Tock capsule that uses `driver_number`, if
+/// there is one.
+pub fn driver_name(driver_number: u32) -> Option<&'static str> {
+    KNOWN_DRIVERS
+        .iter()
+        .find(|(number, _)| *number == driver_number)
+        .map(|(_, name)| *name)
+}
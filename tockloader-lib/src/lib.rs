@@ -7,8 +7,14 @@ pub mod board_settings;
 pub(crate) mod bootloader_serial;
 pub mod command_impl;
 pub mod connection;
-mod errors;
+pub mod credentials;
+pub(crate) mod erase;
+pub mod errors;
+pub mod flash_image;
+pub mod kernel_image;
 pub mod known_boards;
+pub mod known_drivers;
+pub mod progress;
 pub mod tabs;
 
 use async_trait::async_trait;
@@ -16,9 +22,13 @@ use probe_rs::probe::DebugProbeInfo;
 use tokio_serial::SerialPortInfo;
 
 use crate::attributes::app_attributes::AppAttributes;
+use crate::attributes::decode::DecodedAttribute;
 use crate::attributes::general_attributes::GeneralAttributes;
+use crate::attributes::memory_map::MemoryMap;
 use crate::board_settings::BoardSettings;
-use crate::errors::TockloaderError;
+use crate::errors::{ConnectionError, TockloaderError};
+use crate::kernel_image::KernelImage;
+use crate::progress::Progress;
 use crate::tabs::tab::Tab;
 
 pub fn list_debug_probes() -> Vec<DebugProbeInfo> {
@@ -26,7 +36,7 @@ pub fn list_debug_probes() -> Vec<DebugProbeInfo> {
 }
 
 pub fn list_serial_ports() -> Result<Vec<SerialPortInfo>, TockloaderError> {
-    tokio_serial::available_ports().map_err(TockloaderError::SerialInitializationError)
+    Ok(tokio_serial::available_ports().map_err(ConnectionError::SerialInitialization)?)
 }
 
 // TODO(george-cosma): Examine if we need to split these functions into smaller
@@ -57,5 +67,76 @@ pub trait CommandInstall {
         &mut self,
         settings: &BoardSettings,
         tab_file: Tab,
+        progress: &Progress,
     ) -> Result<(), TockloaderError>;
 }
+
+#[async_trait]
+pub trait CommandMemoryMap {
+    async fn memory_map(&mut self, settings: &BoardSettings) -> Result<MemoryMap, TockloaderError>;
+}
+
+/// Access to the key/value attributes stored by the Tock bootloader.
+#[async_trait]
+pub trait CommandAttributes {
+    /// Read every attribute slot. Empty slots are `None`.
+    async fn list_attributes(&mut self) -> Result<Vec<Option<DecodedAttribute>>, TockloaderError>;
+
+    async fn get_attribute(&mut self, key: &str) -> Result<Option<String>, TockloaderError>;
+
+    /// Overwrite the attribute with the given key, or store it in the first
+    /// empty slot if there is none.
+    async fn set_attribute(&mut self, key: &str, value: &str) -> Result<(), TockloaderError>;
+
+    async fn remove_attribute(&mut self, key: &str) -> Result<(), TockloaderError>;
+}
+
+#[async_trait]
+pub trait CommandFlashKernel {
+    /// Write a kernel image below the apps. If `preserve_apps` is set, the
+    /// installed apps are read back after flashing and restored if the flash
+    /// loader or bootloader damaged them.
+    async fn flash_kernel(
+        &mut self,
+        settings: &BoardSettings,
+        kernel: KernelImage,
+        preserve_apps: bool,
+        progress: &Progress,
+    ) -> Result<(), TockloaderError>;
+}
+
+#[async_trait]
+pub trait CommandEraseApps {
+    /// Erase the apps starting at the start address, rewriting only the
+    /// pages that hold them. Sticky apps are kept where they are, unless
+    /// `force` is set, with padding in place of the apps erased before them.
+    /// Returns the apps and padding left on the board.
+    async fn erase_apps(
+        &mut self,
+        settings: &BoardSettings,
+        force: bool,
+        progress: &Progress,
+    ) -> Result<Vec<AppAttributes>, TockloaderError>;
+}
+
+/// Raw access to the flash of the board.
+#[async_trait]
+pub trait CommandReadWrite {
+    async fn read_memory(
+        &mut self,
+        address: u64,
+        length: usize,
+    ) -> Result<Vec<u8>, TockloaderError>;
+
+    /// Write `data` at `address`, keeping the surrounding bytes intact.
+    async fn write_memory(
+        &mut self,
+        address: u64,
+        data: &[u8],
+        progress: &Progress,
+    ) -> Result<(), TockloaderError>;
+
+    /// Read the app region, from the start address up to the end of the last
+    /// installed app. The result can be inspected offline.
+    async fn dump_apps(&mut self, settings: &BoardSettings) -> Result<Vec<u8>, TockloaderError>;
+}
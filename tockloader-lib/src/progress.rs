@@ -0,0 +1,187 @@
+// Licensed under the Apache License, Version 2.0 or the MIT License.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+// Copyright OXIDOS AUTOMOTIVE 2024.
+
+//! Progress reporting and cancellation for long operations.
+//!
+//! Commands that write to the board take a [`Progress`]. It forwards what the
+//! command is doing to a [`ProgressObserver`], and lets another task stop the
+//! command through a [`CancellationToken`]. The token is checked between
+//! pages, so a cancelled command never leaves a page half written.
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::errors::TockloaderError;
+
+/// The steps a long operation goes through. Not every operation goes through
+/// all of them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    /// Attaching to the core or waking the bootloader up.
+    Connect,
+    /// Walking the apps already on the board.
+    Scan,
+    Erase,
+    Write,
+    /// Reading back what was written.
+    Verify,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Phase::Connect => "Connecting",
+            Phase::Scan => "Scanning",
+            Phase::Erase => "Erasing",
+            Phase::Write => "Writing",
+            Phase::Verify => "Verifying",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProgressEvent {
+    /// `total` is the number of bytes the phase will go through, if it is
+    /// known in advance.
+    Started {
+        phase: Phase,
+        total: Option<u64>,
+    },
+    /// `done` bytes of the phase are finished, out of `total`.
+    Advanced {
+        phase: Phase,
+        done: u64,
+    },
+    Finished {
+        phase: Phase,
+    },
+}
+
+/// Receives the progress of an operation. Closures can be used directly, for
+/// example to forward the events to a channel.
+pub trait ProgressObserver: Send + Sync {
+    fn on_progress(&self, event: ProgressEvent);
+}
+
+impl<F: Fn(ProgressEvent) + Send + Sync> ProgressObserver for F {
+    fn on_progress(&self, event: ProgressEvent) {
+        self(event)
+    }
+}
+
+/// Stops an operation from another task. Clones share the same state.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Where a command reports its progress, and how it learns it was cancelled.
+/// The default reports nothing and can not be cancelled.
+#[derive(Clone, Default)]
+pub struct Progress {
+    observer: Option<Arc<dyn ProgressObserver>>,
+    cancellation: CancellationToken,
+}
+
+impl Progress {
+    pub fn new(observer: impl ProgressObserver + 'static) -> Self {
+        Progress {
+            observer: Some(Arc::new(observer)),
+            cancellation: CancellationToken::new(),
+        }
+    }
+
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    pub fn cancellation(&self) -> &CancellationToken {
+        &self.cancellation
+    }
+
+    pub(crate) fn start(&self, phase: Phase, total: Option<u64>) {
+        self.emit(ProgressEvent::Started { phase, total });
+    }
+
+    pub(crate) fn advance(&self, phase: Phase, done: u64) {
+        self.emit(ProgressEvent::Advanced { phase, done });
+    }
+
+    pub(crate) fn finish(&self, phase: Phase) {
+        self.emit(ProgressEvent::Finished { phase });
+    }
+
+    /// Fail with [`TockloaderError::Cancelled`] if the operation was
+    /// cancelled.
+    pub(crate) fn check_cancelled(&self) -> Result<(), TockloaderError> {
+        if self.cancellation.is_cancelled() {
+            return Err(TockloaderError::Cancelled);
+        }
+        Ok(())
+    }
+
+    fn emit(&self, event: ProgressEvent) {
+        if let Some(observer) = &self.observer {
+            observer.on_progress(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[test]
+    fn forwards_events_and_cancellation() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let token = CancellationToken::new();
+        let progress = Progress::new(move |event| sink.lock().unwrap().push(event))
+            .with_cancellation(token.clone());
+
+        progress.start(Phase::Write, Some(1024));
+        progress.advance(Phase::Write, 512);
+        assert!(progress.check_cancelled().is_ok());
+        token.cancel();
+        assert!(matches!(
+            progress.check_cancelled(),
+            Err(TockloaderError::Cancelled)
+        ));
+        progress.finish(Phase::Write);
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                ProgressEvent::Started {
+                    phase: Phase::Write,
+                    total: Some(1024)
+                },
+                ProgressEvent::Advanced {
+                    phase: Phase::Write,
+                    done: 512
+                },
+                ProgressEvent::Finished {
+                    phase: Phase::Write
+                },
+            ]
+        );
+    }
+}
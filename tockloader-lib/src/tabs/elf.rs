@@ -0,0 +1,350 @@
+// Licensed under the Apache License, Version 2.0 or the MIT License.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+// Copyright OXIDOS AUTOMOTIVE 2024.
+
+use object::elf::{FileHeader32, PF_W, PT_LOAD, SHF_WRITE, SHT_REL};
+use object::read::elf::{FileHeader, ProgramHeader, SectionHeader};
+use object::{Endianness, SectionIndex};
+use tbf_parser::builder::TbfHeaderBuilder;
+use tbf_parser::types::{
+    TbfHeaderDriverPermission, TbfHeaderV2FixedAddresses, TbfHeaderV2KernelVersion,
+};
+
+use crate::errors::{ParseError, TabError, TockloaderError};
+use crate::tabs::metadata::TockKernelVersion;
+
+/// Position-independent libtock-c apps are linked with flash at this address.
+const PIC_FLASH_ADDRESS: u32 = 0x80000000;
+/// Position-independent libtock-c apps are linked with RAM at this address.
+const PIC_RAM_ADDRESS: u32 = 0x00000000;
+/// Value of a fixed address TLV field the app does not depend on.
+const NO_FIXED_ADDRESS: u32 = 0xFFFFFFFF;
+/// Apps at a fixed flash address get their header placed on this alignment.
+const FIXED_HEADER_ALIGNMENT: u32 = 256;
+/// Segments further apart than this are most likely not meant for flash.
+const MAX_BINARY_SIZE: u64 = 16 * 1024 * 1024;
+
+/// How to turn an app ELF into a TBF object.
+pub struct ElfConversionOptions {
+    pub package_name: String,
+    /// Stack size, only needed if the ELF has no `.stack` section of its own
+    /// (libtock-c apps). Defaults to 2048 bytes.
+    pub stack_size: Option<u32>,
+    pub app_heap_size: u32,
+    pub kernel_heap_size: u32,
+    /// The kernel version the app was compiled for, if any.
+    pub kernel_version: Option<TockKernelVersion>,
+    /// `(driver number, command number)` pairs the app is allowed to call.
+    /// No permissions header is written if empty.
+    pub permissions: Vec<(u32, u32)>,
+    /// Size of the header and the protected trailer together. By default the
+    /// trailer is empty, unless the app needs one to reach its fixed flash
+    /// address.
+    pub protected_region_size: Option<u32>,
+    /// Binary version for the Program header, or `None` to use a Main header,
+    /// which cannot have credentials footers.
+    pub binary_version: Option<u32>,
+    /// Space kept after the binary for credentials footers.
+    pub minimum_footer_size: u32,
+}
+
+impl ElfConversionOptions {
+    pub fn new(package_name: String) -> Self {
+        ElfConversionOptions {
+            package_name,
+            stack_size: None,
+            app_heap_size: 1024,
+            kernel_heap_size: 1024,
+            kernel_version: None,
+            permissions: Vec::new(),
+            protected_region_size: None,
+            binary_version: Some(0),
+            minimum_footer_size: 0,
+        }
+    }
+}
+
+/// A TBF object built from an ELF.
+pub struct ConvertedApp {
+    pub tbf: Vec<u8>,
+    /// Where the TBF must be placed in flash, if the app is not
+    /// position-independent.
+    pub fixed_flash_address: Option<u32>,
+    pub fixed_ram_address: Option<u32>,
+}
+
+impl ConvertedApp {
+    /// Name of the TBF in a TAB, following the `<arch>.tbf` or
+    /// `<arch>.<flash>.<ram>.tbf` convention.
+    pub fn filename(&self, arch: &str) -> String {
+        match (self.fixed_flash_address, self.fixed_ram_address) {
+            (None, None) => format!("{arch}.tbf"),
+            (flash, ram) => format!(
+                "{arch}.{:#x}.{:#x}.tbf",
+                flash.unwrap_or(NO_FIXED_ADDRESS),
+                ram.unwrap_or(NO_FIXED_ADDRESS)
+            ),
+        }
+    }
+}
+
+/// Convert a relocatable or fixed-address Tock app ELF to a TBF object.
+///
+/// The binary is made of the loadable segments, in the order of their flash
+/// addresses, followed by the relocations of writeable sections prefixed by
+/// their length, as libtock-c expects. The TBF is padded to a power of two.
+pub fn convert_elf(
+    elf: &[u8],
+    options: &ElfConversionOptions,
+) -> Result<ConvertedApp, TockloaderError> {
+    let invalid = |e: object::read::Error| ParseError::Elf(e.to_string());
+
+    let header = FileHeader32::<Endianness>::parse(elf).map_err(invalid)?;
+    let endian = header.endian().map_err(invalid)?;
+
+    let mut flash_segments = Vec::new();
+    let mut ram_start: Option<u32> = None;
+    let mut ram_size = 0;
+    for segment in header.program_headers(endian, elf).map_err(invalid)? {
+        if segment.p_type(endian) != PT_LOAD {
+            continue;
+        }
+        if segment.p_flags(endian) & PF_W != 0 {
+            let address = segment.p_vaddr(endian);
+            ram_start = Some(ram_start.map_or(address, |start| start.min(address)));
+            ram_size += segment.p_memsz(endian);
+        }
+
+        let data = segment
+            .data(endian, elf)
+            .map_err(|_| ParseError::Elf("Truncated ELF segment.".to_owned()))?;
+        if !data.is_empty() {
+            flash_segments.push((segment.p_paddr(endian), data));
+        }
+    }
+    flash_segments.sort_by_key(|(address, _)| *address);
+
+    let flash_start =
+        flash_segments
+            .first()
+            .map(|(address, _)| *address)
+            .ok_or(ParseError::Elf(
+                "The ELF has no loadable segments.".to_owned(),
+            ))?;
+    let mut binary: Vec<u8> = Vec::new();
+    for (address, data) in &flash_segments {
+        let offset = (address - flash_start) as u64;
+        if offset + data.len() as u64 > MAX_BINARY_SIZE {
+            return Err(ParseError::Elf(format!(
+                "Segment at {address:#x} is too far from the start of flash at {flash_start:#x}."
+            ))
+            .into());
+        }
+        let offset = offset as usize;
+        if binary.len() < offset + data.len() {
+            binary.resize(offset + data.len(), 0);
+        }
+        binary[offset..offset + data.len()].copy_from_slice(data);
+    }
+
+    let entry = header.e_entry(endian);
+    if entry < flash_start || entry >= flash_start + binary.len() as u32 {
+        return Err(ParseError::Elf(format!(
+            "The entry point {entry:#x} is outside of the app binary."
+        ))
+        .into());
+    }
+
+    let sections = header.sections(endian, elf).map_err(invalid)?;
+    let mut relocations = Vec::new();
+    let mut has_stack_section = false;
+    for section in sections.iter() {
+        let name = sections.section_name(endian, section).map_err(invalid)?;
+        if name == b".stack" {
+            has_stack_section = true;
+        }
+        if section.sh_type(endian) != SHT_REL {
+            continue;
+        }
+        let target = sections
+            .section(SectionIndex(section.sh_info(endian) as usize))
+            .map_err(invalid)?;
+        if target.sh_flags(endian) & SHF_WRITE != 0 {
+            relocations.extend_from_slice(section.data(endian, elf).map_err(invalid)?);
+        }
+    }
+    binary.extend_from_slice(&(relocations.len() as u32).to_le_bytes());
+    binary.extend_from_slice(&relocations);
+
+    // A `.stack` section is part of the RAM segments already.
+    let stack_size = if has_stack_section {
+        0
+    } else {
+        options.stack_size.unwrap_or(2048)
+    };
+    let minimum_ram_size = ram_size + stack_size + options.app_heap_size + options.kernel_heap_size;
+
+    let fixed_flash = (flash_start != PIC_FLASH_ADDRESS).then_some(flash_start);
+    let fixed_ram = ram_start.filter(|start| *start != PIC_RAM_ADDRESS);
+
+    let build_error = TabError::TbfBuild;
+    let mut builder = TbfHeaderBuilder::new();
+    builder.set_program_version(options.binary_version);
+    builder.set_init_fn_offset(entry - flash_start);
+    builder.set_minimum_ram_size(minimum_ram_size);
+    builder
+        .set_package_name(&options.package_name)
+        .map_err(build_error)?;
+    builder.set_kernel_version(
+        options.kernel_version.map(|version| {
+            TbfHeaderV2KernelVersion::new(version.major as u16, version.minor as u16)
+        }),
+    );
+    for permission in driver_permissions(&options.permissions) {
+        builder.add_permission(permission).map_err(build_error)?;
+    }
+    if fixed_flash.is_some() || fixed_ram.is_some() {
+        builder.set_fixed_addresses(Some(TbfHeaderV2FixedAddresses::new(
+            fixed_ram.unwrap_or(NO_FIXED_ADDRESS),
+            fixed_flash.unwrap_or(NO_FIXED_ADDRESS),
+        )));
+    }
+
+    let header_size = builder.header_size();
+    let protected_region_size = match (options.protected_region_size, fixed_flash) {
+        (Some(size), _) => size,
+        // Start the TBF on an aligned address right before the binary.
+        (None, Some(flash)) => {
+            let tbf_start =
+                flash.saturating_sub(header_size) / FIXED_HEADER_ALIGNMENT * FIXED_HEADER_ALIGNMENT;
+            flash - tbf_start
+        }
+        (None, None) => header_size,
+    };
+    if protected_region_size < header_size {
+        return Err(ParseError::Elf(format!(
+            "The protected region of {protected_region_size} bytes cannot hold the \
+             {header_size} bytes TBF header."
+        ))
+        .into());
+    }
+
+    let total_size = (protected_region_size + binary.len() as u32 + options.minimum_footer_size)
+        .next_power_of_two();
+    let tbf = builder
+        .build(&binary, protected_region_size - header_size, total_size)
+        .map_err(build_error)?;
+
+    Ok(ConvertedApp {
+        tbf,
+        fixed_flash_address: fixed_flash.map(|flash| flash - protected_region_size),
+        fixed_ram_address: fixed_ram,
+    })
+}
+
+/// Group the allowed commands by driver and by blocks of 64 commands.
+fn driver_permissions(commands: &[(u32, u32)]) -> Vec<TbfHeaderDriverPermission> {
+    let mut masks: Vec<(u32, u32, u64)> = Vec::new();
+    for (driver, command) in commands {
+        let offset = command / 64;
+        let bit = 1 << (command % 64);
+        match masks
+            .iter_mut()
+            .find(|(d, o, _)| d == driver && *o == offset)
+        {
+            Some((_, _, mask)) => *mask |= bit,
+            None => masks.push((*driver, offset, bit)),
+        }
+    }
+
+    masks
+        .into_iter()
+        .map(|(driver, offset, mask)| TbfHeaderDriverPermission::new(driver, offset, mask))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use tbf_parser::parse::{parse_tbf_header, parse_tbf_header_lengths};
+
+    use super::*;
+
+    const TEXT: [u8; 64] = [0xAA; 64];
+    const DATA: [u8; 16] = [0xDD; 16];
+
+    /// A minimal ARM executable with a text segment and a data segment that is
+    /// loaded from flash, right after the text.
+    fn elf(flash: u32, ram: u32) -> Vec<u8> {
+        let mut elf = vec![0x7F, b'E', b'L', b'F', 1, 1, 1];
+        elf.resize(16, 0);
+        for half in [2u16, 40] {
+            elf.extend_from_slice(&half.to_le_bytes());
+        }
+        for word in [1, flash + 0x11, 52, 0, 0] {
+            elf.extend_from_slice(&word.to_le_bytes());
+        }
+        for half in [52u16, 32, 2, 40, 0, 0] {
+            elf.extend_from_slice(&half.to_le_bytes());
+        }
+
+        let text_offset = 52 + 2 * 32;
+        let data_offset = text_offset + TEXT.len() as u32;
+        let text = [PT_LOAD, text_offset, flash, flash, 64, 64, 5, 4];
+        let data = [PT_LOAD, data_offset, ram, flash + 64, 16, 32, PF_W | 4, 4];
+        for word in text.into_iter().chain(data) {
+            elf.extend_from_slice(&word.to_le_bytes());
+        }
+
+        elf.extend_from_slice(&TEXT);
+        elf.extend_from_slice(&DATA);
+        elf
+    }
+
+    #[test]
+    fn position_independent() {
+        let mut options = ElfConversionOptions::new("blink".to_owned());
+        options.kernel_version = Some(TockKernelVersion { major: 2, minor: 1 });
+        options.permissions = vec![(2, 0), (2, 1), (2, 65)];
+
+        let app = convert_elf(&elf(PIC_FLASH_ADDRESS, PIC_RAM_ADDRESS), &options).unwrap();
+        assert_eq!(app.filename("cortex-m4"), "cortex-m4.tbf");
+
+        let (_, header_size, total_size) =
+            parse_tbf_header_lengths(&app.tbf[0..8].try_into().unwrap())
+                .ok()
+                .unwrap();
+        assert!(total_size.is_power_of_two());
+        let header = parse_tbf_header(&app.tbf[0..header_size as usize], 2).unwrap();
+        assert_eq!(header.get_package_name(), Some("blink"));
+        assert_eq!(header.get_kernel_version(), Some((2, 1)));
+        assert_eq!(header.get_minimum_app_ram_size(), 32 + 2048 + 1024 + 1024);
+        assert_eq!(header.get_init_function_offset(), header_size as u32 + 0x11);
+        assert_eq!(header.get_fixed_address_flash(), None);
+        assert_eq!(header.get_all_command_permissions().unwrap().len(), 2);
+
+        let binary = &app.tbf[header_size as usize..header.get_binary_end() as usize];
+        assert_eq!(binary[..64], TEXT);
+        assert_eq!(binary[64..80], DATA);
+        // No relocations.
+        assert_eq!(binary[80..84], [0; 4]);
+    }
+
+    #[test]
+    fn fixed_addresses() {
+        let options = ElfConversionOptions::new("blink".to_owned());
+
+        let app = convert_elf(&elf(0x40080, 0x20008000), &options).unwrap();
+        assert_eq!(app.fixed_flash_address, Some(0x40000));
+        assert_eq!(
+            app.filename("cortex-m4"),
+            "cortex-m4.0x40000.0x20008000.tbf"
+        );
+
+        let header_size = u16::from_le_bytes([app.tbf[2], app.tbf[3]]);
+        let header = parse_tbf_header(&app.tbf[0..header_size as usize], 2).unwrap();
+        assert_eq!(header.get_protected_size(), 0x80);
+        assert_eq!(header.get_fixed_address_flash(), Some(0x40080));
+        assert_eq!(header.get_fixed_address_ram(), Some(0x20008000));
+        assert_eq!(app.tbf[0x80..0x80 + 64], TEXT);
+    }
+}
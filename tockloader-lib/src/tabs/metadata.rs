@@ -1,69 +1,102 @@
-// Licensed under the Apache License, Version 2.0 or the MIT License.
-// SPDX-License-Identifier: Apache-2.0 OR MIT
-// Copyright OXIDOS AUTOMOTIVE 2024.
-
-use crate::errors::TockloaderError;
-use serde::Deserialize;
-
-#[allow(dead_code)]
-#[derive(Deserialize)]
-pub(super) struct Metadata {
-    #[serde(rename = "tab-version")]
-    pub tab_version: i64,
-    pub name: String,
-    #[serde(rename = "minimum-tock-kernel-version")]
-    pub minimum_tock_kernel_version: TockKernelVersion,
-    #[serde(rename = "build-date")]
-    pub build_date: toml::value::Datetime,
-    #[serde(
-        default,
-        deserialize_with = "deserialize_boards",
-        rename = "only-for-boards"
-    )]
-    pub only_for_boards: Option<Vec<String>>,
-}
-
-impl Metadata {
-    pub fn new(metadata: String) -> Result<Self, TockloaderError> {
-        toml::from_str(&metadata).map_err(TockloaderError::InvalidMetadata)
-    }
-}
-
-fn deserialize_boards<'de, D>(deserializer: D) -> Result<Option<Vec<String>>, D::Error>
-where
-    D: serde::Deserializer<'de>,
-{
-    let opt = Option::<String>::deserialize(deserializer)?;
-    Ok(opt.map(|s| s.split(',').map(|s| s.trim().to_string()).collect()))
-}
-
-#[allow(dead_code)]
-#[derive(Debug)]
-pub(super) struct TockKernelVersion {
-    pub major: u32,
-    pub minor: u32,
-}
-
-impl<'de> Deserialize<'de> for TockKernelVersion {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: serde::Deserializer<'de>,
-    {
-        let s = String::deserialize(deserializer)?;
-        let parts: Vec<&str> = s.split('.').collect();
-        if parts.len() != 2 {
-            return Err(serde::de::Error::custom(
-                "Invalid version string. It needs to contain exactly one dot.",
-            ));
-        }
-
-        let major = parts[0]
-            .parse::<u32>()
-            .map_err(|_| serde::de::Error::custom("Invalid Major Version"))?;
-        let minor = parts[1]
-            .parse::<u32>()
-            .map_err(|_| serde::de::Error::custom("Invalid Minor Version"))?;
-
-        Ok(TockKernelVersion { major, minor })
-    }
-}
+// Licensed under the Apache License, Version 2.0 or the MIT License.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+// Copyright OXIDOS AUTOMOTIVE 2024.
+
+use std::str::FromStr;
+
+use crate::errors::{TabError, TockloaderError};
+use serde::{Deserialize, Serialize, Serializer};
+
+/// The contents of the `metadata.toml` file of a TAB.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Metadata {
+    #[serde(rename = "tab-version")]
+    pub tab_version: i64,
+    pub name: String,
+    #[serde(rename = "minimum-tock-kernel-version")]
+    pub minimum_tock_kernel_version: TockKernelVersion,
+    #[serde(rename = "build-date")]
+    pub build_date: toml::value::Datetime,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_boards",
+        serialize_with = "serialize_boards",
+        skip_serializing_if = "Option::is_none",
+        rename = "only-for-boards"
+    )]
+    pub only_for_boards: Option<Vec<String>>,
+    /// Keys this version of tockloader does not know about.
+    #[serde(flatten)]
+    pub unknown: toml::Table,
+}
+
+impl Metadata {
+    pub(super) fn new(metadata: String) -> Result<Self, TockloaderError> {
+        Ok(toml::from_str(&metadata).map_err(TabError::InvalidMetadata)?)
+    }
+
+    /// Render the metadata as the contents of a `metadata.toml` file.
+    pub(super) fn to_toml(&self) -> Result<String, TockloaderError> {
+        Ok(toml::to_string(self).map_err(TabError::UnwritableMetadata)?)
+    }
+}
+
+fn deserialize_boards<'de, D>(deserializer: D) -> Result<Option<Vec<String>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let opt = Option::<String>::deserialize(deserializer)?;
+    Ok(opt.map(|s| s.split(',').map(|s| s.trim().to_string()).collect()))
+}
+
+fn serialize_boards<S: Serializer>(
+    boards: &Option<Vec<String>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match boards {
+        Some(boards) => serializer.serialize_str(&boards.join(", ")),
+        None => serializer.serialize_none(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TockKernelVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl FromStr for TockKernelVersion {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split('.').collect();
+        if parts.len() != 2 {
+            return Err("Invalid version string. It needs to contain exactly one dot.");
+        }
+
+        let major = parts[0]
+            .parse::<u32>()
+            .map_err(|_| "Invalid Major Version")?;
+        let minor = parts[1]
+            .parse::<u32>()
+            .map_err(|_| "Invalid Minor Version")?;
+
+        Ok(TockKernelVersion { major, minor })
+    }
+}
+
+impl<'de> Deserialize<'de> for TockKernelVersion {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for TockKernelVersion {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{}.{}", self.major, self.minor))
+    }
+}
@@ -2,5 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0 OR MIT
 // Copyright OXIDOS AUTOMOTIVE 2024.
 
-mod metadata;
+pub mod elf;
+pub mod metadata;
 pub mod tab;
+pub mod writer;
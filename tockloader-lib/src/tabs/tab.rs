@@ -2,49 +2,162 @@
 // SPDX-License-Identifier: Apache-2.0 OR MIT
 // Copyright OXIDOS AUTOMOTIVE 2024.
 
-use crate::errors::TockloaderError;
+use crate::attributes::app_attributes::AppAttributes;
+use crate::errors::{TabError, TockloaderError};
 use crate::tabs::metadata::Metadata;
 use std::fs::File;
 use std::io::Read;
 use tar::Archive;
+use tbf_parser::builder::{build_padding, MIN_PADDING_SIZE};
 
-struct TbfFile {
-    pub filename: String,
-    pub data: Vec<u8>,
+/// A TBF file inside a TAB, along with the `.bin` and `.elf` files that share
+/// its name, if the TAB has them.
+pub struct TbfFile {
+    pub(super) filename: String,
+    pub(super) data: Vec<u8>,
+    pub(super) bin: Option<Vec<u8>>,
+    pub(super) elf: Option<Vec<u8>>,
+}
+
+impl TbfFile {
+    pub fn filename(&self) -> &str {
+        &self.filename
+    }
+
+    /// The architecture this file was built for, taken from its name
+    /// (`cortex-m4.tbf`, or `cortex-m4.0x40000.0x20000000.tbf` for apps built
+    /// for a fixed address).
+    pub fn arch(&self) -> &str {
+        self.filename
+            .split_once('.')
+            .map_or(self.filename.as_str(), |(arch, _)| arch)
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn bin(&self) -> Option<&[u8]> {
+        self.bin.as_deref()
+    }
+
+    pub fn elf(&self) -> Option<&[u8]> {
+        self.elf.as_deref()
+    }
+
+    /// Where this TBF must be written in flash, if it was built for a fixed
+    /// address. This is read from the fixed addresses TLV if there is one, or
+    /// else from the `<arch>.<flash>.<ram>.tbf` name of the file.
+    pub fn fixed_flash_address(&self) -> Option<u64> {
+        let from_header = self.parse().ok().and_then(|app| {
+            let flash = app.tbf_header.get_fixed_address_flash()?;
+            // The TLV holds the start of the binary, after the protected region.
+            (flash as u64).checked_sub(app.tbf_header.get_protected_size() as u64)
+        });
+        from_header.or_else(|| self.name_addresses().and_then(|(flash, _)| flash))
+    }
+
+    /// The RAM address this app was linked for, if it was built for a fixed
+    /// address, read the same way as `fixed_flash_address()`.
+    pub fn fixed_ram_address(&self) -> Option<u64> {
+        let from_header = self
+            .parse()
+            .ok()
+            .and_then(|app| app.tbf_header.get_fixed_address_ram())
+            .map(u64::from);
+        from_header.or_else(|| self.name_addresses().and_then(|(_, ram)| ram))
+    }
+
+    /// The flash and RAM addresses in an `<arch>.<flash>.<ram>.tbf` name.
+    fn name_addresses(&self) -> Option<(Option<u64>, Option<u64>)> {
+        let stem = self.filename.strip_suffix(".tbf")?;
+        let mut parts = stem.split('.').skip(1);
+        let (Some(flash), Some(ram), None) = (parts.next(), parts.next(), parts.next()) else {
+            return None;
+        };
+
+        let parse = |address: &str| {
+            address
+                .strip_prefix("0x")
+                .and_then(|hex| u64::from_str_radix(hex, 16).ok())
+                .filter(|address| *address != 0xFFFFFFFF)
+        };
+        Some((parse(flash), parse(ram)))
+    }
+
+    /// Parse the header, TLVs and footers of this file.
+    pub fn parse(&self) -> Result<AppAttributes, TockloaderError> {
+        AppAttributes::read_app_data(&self.data, 0)?
+            .ok_or(TabError::NoTbfHeader(self.filename.clone()).into())
+    }
 }
 
 pub struct Tab {
-    metadata: Metadata,
-    tbf_files: Vec<TbfFile>,
+    pub(super) metadata: Metadata,
+    pub(super) tbf_files: Vec<TbfFile>,
 }
 
 impl Tab {
     pub fn open(path: String) -> Result<Self, TockloaderError> {
+        let file = File::open(&path).map_err(|source| TockloaderError::File {
+            path: path.into(),
+            source,
+        })?;
+        Self::from_reader(file)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, TockloaderError> {
+        Self::from_reader(bytes)
+    }
+
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, TockloaderError> {
         let mut metadata = None;
         let mut tbf_files = Vec::new();
-        let file = File::open(path).map_err(TockloaderError::UnusableTab)?;
-        let mut archive = Archive::new(file);
-        for file in archive.entries().map_err(TockloaderError::UnusableTab)? {
-            let mut file = file.map_err(TockloaderError::UnusableTab)?;
-            let path = file.path().map_err(TockloaderError::UnusableTab)?;
+        let mut siblings = Vec::new();
+        let mut archive = Archive::new(reader);
+        for file in archive.entries().map_err(TabError::Unusable)? {
+            let mut file = file.map_err(TabError::Unusable)?;
+            let path = file.path().map_err(TabError::Unusable)?;
             let file_name = match path.file_name().and_then(|name| name.to_str()) {
                 Some(name) => name.to_owned(),
                 None => continue,
             };
             if file_name == "metadata.toml" {
                 let mut buf = String::new();
-                file.read_to_string(&mut buf)
-                    .map_err(TockloaderError::UnusableTab)?;
+                file.read_to_string(&mut buf).map_err(TabError::Unusable)?;
                 metadata = Some(Metadata::new(buf)?);
             } else if file_name.ends_with(".tbf") {
                 let mut data = Vec::new();
 
-                file.read_to_end(&mut data)
-                    .map_err(TockloaderError::UnusableTab)?;
+                file.read_to_end(&mut data).map_err(TabError::Unusable)?;
                 tbf_files.push(TbfFile {
                     filename: file_name.to_string(),
                     data,
+                    bin: None,
+                    elf: None,
                 });
+            } else if file_name.ends_with(".bin") || file_name.ends_with(".elf") {
+                let mut data = Vec::new();
+
+                file.read_to_end(&mut data).map_err(TabError::Unusable)?;
+                siblings.push((file_name, data));
+            }
+        }
+
+        // The archive may store the siblings before or after their TBF file.
+        for (file_name, data) in siblings {
+            let (stem, extension) = file_name
+                .rsplit_once('.')
+                .expect("Sibling files have an extension");
+            let Some(tbf_file) = tbf_files
+                .iter_mut()
+                .find(|tbf_file| tbf_file.filename.strip_suffix(".tbf") == Some(stem))
+            else {
+                continue;
+            };
+            match extension {
+                "bin" => tbf_file.bin = Some(data),
+                _ => tbf_file.elf = Some(data),
             }
         }
 
@@ -53,8 +166,27 @@ impl Tab {
                 metadata,
                 tbf_files,
             }),
-            None => Err(TockloaderError::NoMetadata),
+            None => Err(TabError::NoMetadata.into()),
+        }
+    }
+
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    pub fn tbf_files(&self) -> &[TbfFile] {
+        &self.tbf_files
+    }
+
+    /// The architectures this TAB has binaries for, without duplicates.
+    pub fn architectures(&self) -> Vec<&str> {
+        let mut architectures: Vec<&str> = Vec::new();
+        for file in &self.tbf_files {
+            if !architectures.contains(&file.arch()) {
+                architectures.push(file.arch());
+            }
         }
+        architectures
     }
 
     pub fn is_compatible_with_kernel_verison(&self, _kernel_version: u32) -> bool {
@@ -72,6 +204,75 @@ impl Tab {
         }
     }
 
+    /// Choose the TBF of `arch` to install after the apps that end at
+    /// `address`, and the address to write it at.
+    ///
+    /// Position-independent binaries are placed at the next address aligned
+    /// to their size. Binaries built for a fixed flash address can only be
+    /// placed there, so they fit if that address is not taken yet. Any gap
+    /// left before the binary must be large enough to hold padding, so that
+    /// the kernel can still walk the app list. The binary that can be placed
+    /// closest to `address` wins.
+    pub fn select_binary(
+        &self,
+        arch: &str,
+        address: u64,
+    ) -> Result<(u64, &TbfFile), TockloaderError> {
+        let mut candidates = self
+            .tbf_files
+            .iter()
+            .filter(|file| file.arch() == arch)
+            .peekable();
+        if candidates.peek().is_none() {
+            return Err(self.no_binary(arch));
+        }
+
+        candidates
+            .filter_map(|file| {
+                let start = match file.fixed_flash_address() {
+                    Some(flash) => flash,
+                    None => {
+                        let size = file.data.len() as u64;
+                        if size == 0 {
+                            return None;
+                        }
+                        address.div_ceil(size) * size
+                    }
+                };
+                let gap = start.checked_sub(address)?;
+                (gap == 0 || gap >= MIN_PADDING_SIZE as u64).then_some((start, file))
+            })
+            .min_by_key(|(start, _)| *start)
+            .ok_or_else(|| {
+                TabError::NoFittingBinary {
+                    app: self.metadata.name.clone(),
+                    arch: arch.to_owned(),
+                    address,
+                }
+                .into()
+            })
+    }
+
+    /// The data to write at `address` to install this app after the apps
+    /// that end there: padding over the gap left before the binary chosen by
+    /// `select_binary()`, then the binary itself.
+    pub fn install_data(&self, arch: &str, address: u64) -> Result<Vec<u8>, TockloaderError> {
+        let (start, file) = self.select_binary(arch, address)?;
+        let mut data = match start - address {
+            0 => Vec::new(),
+            gap => {
+                let gap = u32::try_from(gap).map_err(|_| TabError::NoFittingBinary {
+                    app: self.metadata.name.clone(),
+                    arch: arch.to_owned(),
+                    address,
+                })?;
+                build_padding(gap).map_err(TabError::TbfBuild)?
+            }
+        };
+        data.extend_from_slice(file.data());
+        Ok(data)
+    }
+
     pub fn extract_binary(&self, arch: &str) -> Result<Vec<u8>, TockloaderError> {
         for file in &self.tbf_files {
             if file.filename.starts_with(arch) {
@@ -79,6 +280,170 @@ impl Tab {
             }
         }
 
-        Err(TockloaderError::NoBinaryError(arch.to_owned()))
+        Err(self.no_binary(arch))
+    }
+
+    fn no_binary(&self, arch: &str) -> TockloaderError {
+        TabError::NoBinary {
+            app: self.metadata.name.clone(),
+            arch: arch.to_owned(),
+        }
+        .into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tbf_parser::types::TbfHeaderV2FixedAddresses;
+    use tbf_parser::verify::CredentialsCheck;
+
+    use super::*;
+
+    fn append(builder: &mut tar::Builder<Vec<u8>>, name: &str, data: &[u8]) {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, name, data).unwrap();
+    }
+
+    #[test]
+    fn tab_from_bytes() {
+        let tbf = include_bytes!("../../../tbf-parser/tests/flashes/footerSHA256.dat");
+        let metadata = "tab-version = 1\n\
+                        name = \"blink\"\n\
+                        minimum-tock-kernel-version = \"2.1\"\n\
+                        build-date = 2024-01-01T00:00:00Z\n\
+                        only-for-boards = \"microbit_v2, nrf52dk\"\n\
+                        signer = \"ci\"\n";
+
+        let mut builder = tar::Builder::new(Vec::new());
+        append(&mut builder, "blink/cortex-m4.elf", b"\x7fELF");
+        append(&mut builder, "blink/metadata.toml", metadata.as_bytes());
+        append(&mut builder, "blink/cortex-m4.tbf", tbf);
+        append(&mut builder, "blink/cortex-m0.0x10000.0x20000000.tbf", tbf);
+        let bytes = builder.into_inner().unwrap();
+
+        let tab = Tab::from_bytes(&bytes).unwrap();
+        let metadata = tab.metadata();
+        assert_eq!(metadata.name, "blink");
+        assert_eq!(metadata.minimum_tock_kernel_version.minor, 1);
+        assert_eq!(
+            metadata.only_for_boards.as_deref(),
+            Some(&["microbit_v2".to_owned(), "nrf52dk".to_owned()][..])
+        );
+        assert_eq!(metadata.unknown["signer"].as_str(), Some("ci"));
+
+        assert_eq!(tab.architectures(), ["cortex-m4", "cortex-m0"]);
+        let files = tab.tbf_files();
+        assert_eq!(files[0].elf(), Some(&b"\x7fELF"[..]));
+        assert_eq!(files[0].bin(), None);
+        assert_eq!(files[1].elf(), None);
+
+        let app = files[0].parse().unwrap();
+        assert_eq!(app.tbf_header.get_package_name(), Some("_heart"));
+        assert_eq!(app.size, 8192);
+        assert_eq!(app.tbf_footers[0].check, CredentialsCheck::Valid);
+    }
+
+    #[test]
+    fn select_binary() {
+        let tbf = include_bytes!("../../../tbf-parser/tests/flashes/footerSHA256.dat");
+
+        // This one only has its address in the header.
+        let mut header = tbf_parser::builder::TbfHeaderBuilder::new();
+        header.set_fixed_addresses(Some(TbfHeaderV2FixedAddresses::new(0x20000000, 0x50080)));
+        let protected_trailer = 0x80 - header.header_size();
+        let fixed_tbf = header.build(&[0; 16], protected_trailer, 512).unwrap();
+
+        let mut builder = tar::Builder::new(Vec::new());
+        let metadata = "tab-version = 1\n\
+                        name = \"blink\"\n\
+                        minimum-tock-kernel-version = \"2.1\"\n\
+                        build-date = 2024-01-01T00:00:00Z\n";
+        append(&mut builder, "metadata.toml", metadata.as_bytes());
+        append(&mut builder, "cortex-m4.tbf", tbf);
+        append(&mut builder, "rv32imc.0x40000.0x20000000.tbf", tbf);
+        append(&mut builder, "rv32imc.0x48000.0x20004000.tbf", tbf);
+        append(&mut builder, "rv32imc.tbf", &fixed_tbf);
+        let tab = Tab::from_bytes(&builder.into_inner().unwrap()).unwrap();
+
+        let (address, file) = tab.select_binary("cortex-m4", 0x40100).unwrap();
+        assert_eq!((address, file.filename()), (0x42000, "cortex-m4.tbf"));
+
+        let (address, file) = tab.select_binary("rv32imc", 0x30000).unwrap();
+        assert_eq!(
+            (address, file.filename()),
+            (0x40000, "rv32imc.0x40000.0x20000000.tbf")
+        );
+        assert_eq!(file.fixed_ram_address(), Some(0x20000000));
+
+        let (address, file) = tab.select_binary("rv32imc", 0x40100).unwrap();
+        assert_eq!(
+            (address, file.filename()),
+            (0x48000, "rv32imc.0x48000.0x20004000.tbf")
+        );
+
+        let (address, file) = tab.select_binary("rv32imc", 0x48100).unwrap();
+        assert_eq!((address, file.filename()), (0x50000, "rv32imc.tbf"));
+
+        assert!(matches!(
+            tab.select_binary("rv32imc", 0x50100),
+            Err(TockloaderError::Tab(TabError::NoFittingBinary {
+                address: 0x50100,
+                ..
+            }))
+        ));
+        assert!(matches!(
+            tab.select_binary("cortex-m0", 0x40000),
+            Err(TockloaderError::Tab(TabError::NoBinary { .. }))
+        ));
+    }
+
+    #[test]
+    fn install_past_gap() {
+        let tbf = include_bytes!("../../../tbf-parser/tests/flashes/footerSHA256.dat");
+        let mut builder = tar::Builder::new(Vec::new());
+        let metadata = "tab-version = 1\n\
+                        name = \"blink\"\n\
+                        minimum-tock-kernel-version = \"2.1\"\n\
+                        build-date = 2024-01-01T00:00:00Z\n";
+        append(&mut builder, "metadata.toml", metadata.as_bytes());
+        append(&mut builder, "cortex-m4.tbf", tbf);
+        let tab = Tab::from_bytes(&builder.into_inner().unwrap()).unwrap();
+
+        let start = 0x40000;
+        let mut region = tbf_parser::builder::build_padding(0x100).unwrap();
+        region.extend(tab.install_data("cortex-m4", start + 0x100).unwrap());
+
+        let apps = AppAttributes::read_apps_data_image(&region, start, start).unwrap();
+        let app = apps.last().unwrap();
+        assert_eq!(app.address, 0x42000);
+        assert_eq!(app.tbf_header.get_package_name(), Some("_heart"));
+        assert_eq!(app.tbf_footers[0].check, CredentialsCheck::Valid);
+    }
+
+    #[test]
+    fn skip_gap_too_small_for_padding() {
+        let mut header = tbf_parser::builder::TbfHeaderBuilder::new();
+        header.set_fixed_addresses(Some(TbfHeaderV2FixedAddresses::new(0x20000000, 0x40180)));
+        let protected_trailer = 0x80 - header.header_size();
+        let fixed_tbf = header.build(&[0; 16], protected_trailer, 512).unwrap();
+
+        let mut builder = tar::Builder::new(Vec::new());
+        let metadata = "tab-version = 1\n\
+                        name = \"blink\"\n\
+                        minimum-tock-kernel-version = \"2.1\"\n\
+                        build-date = 2024-01-01T00:00:00Z\n";
+        append(&mut builder, "metadata.toml", metadata.as_bytes());
+        append(&mut builder, "rv32imc.tbf", &fixed_tbf);
+        let tab = Tab::from_bytes(&builder.into_inner().unwrap()).unwrap();
+
+        assert!(tab.select_binary("rv32imc", 0x40100).is_ok());
+        assert!(tab.select_binary("rv32imc", 0x400F0).is_ok());
+        assert!(matches!(
+            tab.select_binary("rv32imc", 0x400F8),
+            Err(TockloaderError::Tab(TabError::NoFittingBinary { .. }))
+        ));
     }
 }
@@ -0,0 +1,310 @@
+// Licensed under the Apache License, Version 2.0 or the MIT License.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+// Copyright OXIDOS AUTOMOTIVE 2024.
+
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tar::{Builder, Header};
+use tbf_parser::builder::{TbfBuildError, TbfHeaderEditor};
+use tbf_parser::types::TbfFooterV2CredentialsType;
+use toml::value::{Date, Datetime, Offset, Time};
+
+use crate::attributes::app_attributes::AppAttributes;
+use crate::credentials::{sign_tbf, Credential};
+use crate::errors::{ParseError, TabError, TockloaderError};
+use crate::tabs::elf::{convert_elf, ElfConversionOptions};
+use crate::tabs::metadata::{Metadata, TockKernelVersion};
+use crate::tabs::tab::{Tab, TbfFile};
+
+/// Version of the TAB format written by [`TabWriter`].
+const TAB_VERSION: i64 = 1;
+
+/// Assembles a TAB file from TBF files, either from scratch or by modifying
+/// an existing TAB.
+pub struct TabWriter {
+    metadata: Metadata,
+    tbf_files: Vec<TbfFile>,
+}
+
+impl TabWriter {
+    /// Start an empty TAB, built now.
+    pub fn new(name: String, minimum_tock_kernel_version: TockKernelVersion) -> Self {
+        TabWriter {
+            metadata: Metadata {
+                tab_version: TAB_VERSION,
+                name,
+                minimum_tock_kernel_version,
+                build_date: now(),
+                only_for_boards: None,
+                unknown: toml::Table::new(),
+            },
+            tbf_files: Vec::new(),
+        }
+    }
+
+    /// Start from the contents of an existing TAB, to repackage it. Unknown
+    /// metadata keys and `.bin`/`.elf` files are kept.
+    pub fn from_tab(tab: Tab) -> Self {
+        TabWriter {
+            metadata: tab.metadata,
+            tbf_files: tab.tbf_files,
+        }
+    }
+
+    pub fn set_build_date(&mut self, build_date: Datetime) {
+        self.metadata.build_date = build_date;
+    }
+
+    pub fn set_only_for_boards(&mut self, boards: Option<Vec<String>>) {
+        self.metadata.only_for_boards = boards;
+    }
+
+    /// Add a TBF file, replacing any file with the same name. The name must
+    /// follow the `<arch>.tbf` or `<arch>.<flash>.<ram>.tbf` convention.
+    pub fn add_tbf(&mut self, filename: String, data: Vec<u8>) -> Result<(), TockloaderError> {
+        if !filename.ends_with(".tbf") || filename.contains('/') {
+            return Err(TabError::InvalidTbfName(filename).into());
+        }
+        // Refuse files the board would not be able to load.
+        AppAttributes::read_app_data(&data, 0)?.ok_or(TabError::NoTbfHeader(filename.clone()))?;
+
+        self.tbf_files.retain(|file| file.filename != filename);
+        self.tbf_files.push(TbfFile {
+            filename,
+            data,
+            bin: None,
+            elf: None,
+        });
+        Ok(())
+    }
+
+    /// Convert an app ELF named `<arch>.elf` and add the resulting TBF, keeping
+    /// the ELF next to it.
+    pub fn add_elf(
+        &mut self,
+        filename: &str,
+        elf: Vec<u8>,
+        options: &ElfConversionOptions,
+    ) -> Result<(), TockloaderError> {
+        let arch = filename
+            .strip_suffix(".elf")
+            .filter(|arch| !arch.is_empty() && !arch.contains(['.', '/']))
+            .ok_or(ParseError::Elf(format!(
+                "'{filename}' is not named like <arch>.elf."
+            )))?;
+        let app = convert_elf(&elf, options)?;
+
+        let filename = app.filename(arch);
+        self.add_tbf(filename.clone(), app.tbf)?;
+        if let Some(file) = self
+            .tbf_files
+            .iter_mut()
+            .find(|file| file.filename == filename)
+        {
+            file.elf = Some(elf);
+        }
+        Ok(())
+    }
+
+    /// Add credentials footers to every TBF file.
+    pub fn sign(&mut self, credentials: &[Credential]) -> Result<(), TockloaderError> {
+        for file in &mut self.tbf_files {
+            file.data = sign_tbf(&file.data, credentials)?;
+        }
+        Ok(())
+    }
+
+    /// Edit the TBF header of every TBF file with `edit`. Returns the types
+    /// of the credentials that were dropped, see `TbfHeaderEditor`.
+    pub fn edit_headers(
+        &mut self,
+        edit: impl Fn(&mut TbfHeaderEditor) -> Result<(), TbfBuildError>,
+    ) -> Result<Vec<TbfFooterV2CredentialsType>, TockloaderError> {
+        let mut dropped = Vec::new();
+        for file in &mut self.tbf_files {
+            let mut editor = TbfHeaderEditor::new(&file.data).map_err(TabError::TbfBuild)?;
+            edit(&mut editor).map_err(TabError::TbfBuild)?;
+            let (data, credentials) = editor.build().map_err(TabError::TbfBuild)?;
+            file.data = data;
+            for format in credentials {
+                if !dropped.contains(&format) {
+                    dropped.push(format);
+                }
+            }
+        }
+        Ok(dropped)
+    }
+
+    /// Remove every TBF file built for `arch`, along with its siblings.
+    pub fn remove_arch(&mut self, arch: &str) -> Result<(), TockloaderError> {
+        let count = self.tbf_files.len();
+        self.tbf_files.retain(|file| file.arch() != arch);
+
+        if self.tbf_files.len() == count {
+            return Err(TabError::NoBinary {
+                app: self.metadata.name.clone(),
+                arch: arch.to_owned(),
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Write the TAB as a tar archive.
+    pub fn write<W: Write>(&self, writer: W) -> Result<(), TockloaderError> {
+        let mut builder = Builder::new(writer);
+
+        append(
+            &mut builder,
+            "metadata.toml",
+            self.metadata.to_toml()?.as_bytes(),
+        )?;
+        for file in &self.tbf_files {
+            append(&mut builder, &file.filename, &file.data)?;
+
+            let stem = file.filename.trim_end_matches(".tbf");
+            if let Some(bin) = &file.bin {
+                append(&mut builder, &format!("{stem}.bin"), bin)?;
+            }
+            if let Some(elf) = &file.elf {
+                append(&mut builder, &format!("{stem}.elf"), elf)?;
+            }
+        }
+
+        builder
+            .into_inner()
+            .and_then(|mut writer| writer.flush())
+            .map_err(|e| TabError::Unwritable(e).into())
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, TockloaderError> {
+        let mut bytes = Vec::new();
+        self.write(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), TockloaderError> {
+        let file = File::create(path).map_err(|source| TockloaderError::File {
+            path: path.to_owned(),
+            source,
+        })?;
+        self.write(file)
+    }
+}
+
+fn append<W: Write>(
+    builder: &mut Builder<W>,
+    name: &str,
+    data: &[u8],
+) -> Result<(), TockloaderError> {
+    let mut header = Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+
+    builder
+        .append_data(&mut header, name, data)
+        .map_err(|e| TabError::Unwritable(e).into())
+}
+
+/// The current time in UTC, with a precision of one second.
+fn now() -> Datetime {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let days = (seconds / 86400) as i64;
+    let seconds = seconds % 86400;
+
+    // Convert days since the epoch to a civil date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    Datetime {
+        date: Some(Date {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+        }),
+        time: Some(Time {
+            hour: (seconds / 3600) as u8,
+            minute: (seconds / 60 % 60) as u8,
+            second: (seconds % 60) as u8,
+            nanosecond: 0,
+        }),
+        offset: Some(Offset::Z),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TBF: &[u8] = include_bytes!("../../../tbf-parser/tests/flashes/footerSHA256.dat");
+
+    fn writer() -> TabWriter {
+        let mut writer =
+            TabWriter::new("blink".to_owned(), TockKernelVersion { major: 2, minor: 1 });
+        writer.set_only_for_boards(Some(vec!["microbit_v2".to_owned(), "nrf52dk".to_owned()]));
+        writer
+            .add_tbf("cortex-m4.tbf".to_owned(), TBF.to_vec())
+            .unwrap();
+        writer
+            .add_tbf("rv32imc.0x40000.0x20000000.tbf".to_owned(), TBF.to_vec())
+            .unwrap();
+        writer
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = std::env::temp_dir().join(format!("tockloader-{}.tab", std::process::id()));
+        writer().save(&path).unwrap();
+        let tab = Tab::open(path.to_string_lossy().into_owned());
+        std::fs::remove_file(&path).unwrap();
+        let tab = tab.unwrap();
+
+        let metadata = tab.metadata();
+        assert_eq!(metadata.tab_version, TAB_VERSION);
+        assert_eq!(metadata.name, "blink");
+        assert_eq!(
+            metadata.minimum_tock_kernel_version,
+            TockKernelVersion { major: 2, minor: 1 }
+        );
+        assert_eq!(
+            metadata.only_for_boards.as_deref(),
+            Some(&["microbit_v2".to_owned(), "nrf52dk".to_owned()][..])
+        );
+        assert_eq!(tab.architectures(), ["cortex-m4", "rv32imc"]);
+        assert_eq!(tab.extract_binary("cortex-m4").unwrap(), TBF);
+    }
+
+    #[test]
+    fn repackage() {
+        let tab = Tab::from_bytes(&writer().to_bytes().unwrap()).unwrap();
+        let build_date = tab.metadata().build_date;
+
+        let mut writer = TabWriter::from_tab(tab);
+        writer.remove_arch("cortex-m4").unwrap();
+        assert!(writer.remove_arch("cortex-m4").is_err());
+        writer
+            .add_tbf("cortex-m0.tbf".to_owned(), TBF.to_vec())
+            .unwrap();
+        assert!(writer
+            .add_tbf("cortex-m3.tbf".to_owned(), vec![0xFF; 64])
+            .is_err());
+
+        let tab = Tab::from_bytes(&writer.to_bytes().unwrap()).unwrap();
+        assert_eq!(tab.architectures(), ["rv32imc", "cortex-m0"]);
+        assert_eq!(tab.metadata().build_date, build_date);
+    }
+}